@@ -2,7 +2,8 @@ use anyhow::{Result, anyhow};
 use embedded_hal::delay::DelayNs;
 use embedded_io_adapters::std::FromStd;
 use inquire::Select;
-use sds011::{Config, SDS011};
+use sds011::Config;
+use sds011::blocking::SDS011;
 use std::env;
 use std::thread::sleep;
 use std::time::Duration;
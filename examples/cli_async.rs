@@ -2,7 +2,8 @@ use anyhow::{Result, anyhow};
 use embedded_hal_async::delay::DelayNs;
 use embedded_io_adapters::tokio_1::FromTokio;
 use inquire::Select;
-use sds011::{Config, SDS011};
+use sds011::Config;
+use sds011::asynch::SDS011;
 use std::env;
 use std::time::Duration;
 use tokio::time::sleep;
@@ -0,0 +1,643 @@
+//! Air quality indices.
+//!
+//! Converts PM2.5 and PM10 concentrations into the index of one of several
+//! national or European [`Scale`]s, together with its category, the
+//! breakpoints of the band it falls into and the color suggested for it.
+//!
+//! Every scale is defined on averages over a certain period (see the
+//! variants of [`Scale`]), e.g. 24 hours for the US EPA AQI. A single
+//! measurement only gives a rough idea; for a proper index, average the
+//! measurements over that period and pass the result (or, for a current
+//! US AQI, the [`NowCast`](crate::nowcast::NowCast)):
+//!
+//! ```ignore
+//! let average = Measurement::new(pm25_sum / n, pm10_sum / n);
+//! let aqi = Scale::UsEpa.index(&average);
+//! println!("AQI {} ({}, {})", aqi.index, aqi.category.name, aqi.category.color);
+//! ```
+//!
+//! Concentrations are given in 0.1 µg/m3, like in a [`Measurement`], and
+//! truncated to the precision of the scale's table. Concentrations above
+//! the highest breakpoint continue the index linearly with the slope of the
+//! highest band, so categorical scales like the EAQI stay at their top level.
+
+use crate::Measurement;
+use core::fmt::{self, Display, Formatter};
+
+/// An air quality index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scale {
+    /// The US EPA Air Quality Index (2024 revision), 0 to 500,
+    /// from 24-hour averages.
+    UsEpa,
+    /// The European Common Air Quality Index (CAQI), 0 to 100 and above,
+    /// from hourly averages.
+    CaqiHourly,
+    /// The CAQI from daily averages.
+    CaqiDaily,
+    /// The European Air Quality Index (EAQI) of the EEA, levels 1 to 6,
+    /// from 24-hour running averages.
+    Eaqi,
+    /// The UK Daily Air Quality Index (DAQI), 1 to 10,
+    /// from 24-hour running averages.
+    UkDaqi,
+    /// The Indian National Air Quality Index (NAQI), 0 to 500,
+    /// from 24-hour averages.
+    IndiaNaqi,
+    /// The Chinese Air Quality Index (HJ 633-2012), 0 to 500,
+    /// from 24-hour averages.
+    ChinaAqi,
+}
+
+/// A pollutant measured by the sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pollutant {
+    /// Particles up to 2.5 µm.
+    Pm25,
+    /// Particles up to 10 µm.
+    Pm10,
+}
+
+/// A color suggested for a category.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Color {
+    /// Red component.
+    pub red: u8,
+    /// Green component.
+    pub green: u8,
+    /// Blue component.
+    pub blue: u8,
+}
+
+impl Color {
+    const fn rgb([red, green, blue]: [u8; 3]) -> Self {
+        Self { red, green, blue }
+    }
+}
+
+/// Formats the color as `#RRGGBB`.
+impl Display for Color {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.red, self.green, self.blue)
+    }
+}
+
+/// A category of a scale, e.g. "Good".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Category {
+    /// The category's position on its scale, starting with 1 for the best.
+    pub level: u8,
+    /// The category's name, as published.
+    pub name: &'static str,
+    /// The color suggested for the category.
+    pub color: Color,
+}
+
+/// The breakpoints between which a concentration falls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Band {
+    /// The lowest concentration of the band, in 0.1 µg/m3.
+    pub low: u16,
+    /// The highest concentration of the band, in 0.1 µg/m3,
+    /// or `None` if the band is open-ended.
+    pub high: Option<u16>,
+    /// The index at `low`.
+    pub index_low: u16,
+    /// The index at `high`, or `index_low` if the band is open-ended.
+    pub index_high: u16,
+    category: u8,
+}
+
+/// The index of a concentration on a scale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Aqi {
+    /// The scale of the index.
+    pub scale: Scale,
+    /// The pollutant the index was computed from.
+    pub pollutant: Pollutant,
+    /// The (sub-)index itself.
+    pub index: u16,
+    /// The category the index falls into.
+    pub category: Category,
+    /// The band the concentration falls into.
+    pub band: Band,
+}
+
+/// A breakpoint table.
+struct Table {
+    bands: &'static [Band],
+    categories: &'static [Category],
+    /// the table's precision, in 0.1 µg/m3
+    step: u16,
+    /// round indices up instead of to the nearest integer
+    ceil: bool,
+}
+
+const fn category(level: u8, name: &'static str, rgb: [u8; 3]) -> Category {
+    Category {
+        level,
+        name,
+        color: Color::rgb(rgb),
+    }
+}
+
+/// A band from `low` to `high` (in µg/m3, scaled by 10), with indices
+/// from `index_low` to `index_high`, in category number `category`.
+const fn band(low: u16, high: u16, index_low: u16, index_high: u16, category: u8) -> Band {
+    Band {
+        low,
+        high: Some(high),
+        index_low,
+        index_high,
+        category,
+    }
+}
+
+/// An open-ended band from `low`, starting at `index`.
+const fn open(low: u16, index: u16, category: u8) -> Band {
+    Band {
+        low,
+        high: None,
+        index_low: index,
+        index_high: index,
+        category,
+    }
+}
+
+const US_CATEGORIES: &[Category] = &[
+    category(1, "Good", [0x00, 0xE4, 0x00]),
+    category(2, "Moderate", [0xFF, 0xFF, 0x00]),
+    category(3, "Unhealthy for Sensitive Groups", [0xFF, 0x7E, 0x00]),
+    category(4, "Unhealthy", [0xFF, 0x00, 0x00]),
+    category(5, "Very Unhealthy", [0x8F, 0x3F, 0x97]),
+    category(6, "Hazardous", [0x7E, 0x00, 0x23]),
+];
+
+const US_PM25: Table = Table {
+    bands: &[
+        band(0, 90, 0, 50, 1),
+        band(91, 354, 51, 100, 2),
+        band(355, 554, 101, 150, 3),
+        band(555, 1254, 151, 200, 4),
+        band(1255, 2254, 201, 300, 5),
+        band(2255, 3254, 301, 500, 6),
+    ],
+    categories: US_CATEGORIES,
+    step: 1,
+    ceil: false,
+};
+
+const US_PM10: Table = Table {
+    bands: &[
+        band(0, 540, 0, 50, 1),
+        band(550, 1540, 51, 100, 2),
+        band(1550, 2540, 101, 150, 3),
+        band(2550, 3540, 151, 200, 4),
+        band(3550, 4240, 201, 300, 5),
+        band(4250, 6040, 301, 500, 6),
+    ],
+    categories: US_CATEGORIES,
+    step: 10,
+    ceil: false,
+};
+
+const CAQI_CATEGORIES: &[Category] = &[
+    category(1, "Very low", [0x79, 0xBC, 0x6A]),
+    category(2, "Low", [0xBB, 0xCF, 0x4C]),
+    category(3, "Medium", [0xEE, 0xC2, 0x0B]),
+    category(4, "High", [0xF2, 0x93, 0x05]),
+    category(5, "Very high", [0xE8, 0x41, 0x6F]),
+];
+
+const CAQI_HOURLY_PM25: Table = Table {
+    bands: &[
+        band(0, 150, 0, 25, 1),
+        band(150, 300, 25, 50, 2),
+        band(300, 550, 50, 75, 3),
+        band(550, 1100, 75, 100, 4),
+        open(1100, 100, 5),
+    ],
+    categories: CAQI_CATEGORIES,
+    step: 1,
+    ceil: false,
+};
+
+const CAQI_HOURLY_PM10: Table = Table {
+    bands: &[
+        band(0, 250, 0, 25, 1),
+        band(250, 500, 25, 50, 2),
+        band(500, 900, 50, 75, 3),
+        band(900, 1800, 75, 100, 4),
+        open(1800, 100, 5),
+    ],
+    categories: CAQI_CATEGORIES,
+    step: 1,
+    ceil: false,
+};
+
+const CAQI_DAILY_PM25: Table = Table {
+    bands: &[
+        band(0, 100, 0, 25, 1),
+        band(100, 200, 25, 50, 2),
+        band(200, 300, 50, 75, 3),
+        band(300, 600, 75, 100, 4),
+        open(600, 100, 5),
+    ],
+    categories: CAQI_CATEGORIES,
+    step: 1,
+    ceil: false,
+};
+
+const CAQI_DAILY_PM10: Table = Table {
+    bands: &[
+        band(0, 150, 0, 25, 1),
+        band(150, 300, 25, 50, 2),
+        band(300, 500, 50, 75, 3),
+        band(500, 1000, 75, 100, 4),
+        open(1000, 100, 5),
+    ],
+    categories: CAQI_CATEGORIES,
+    step: 1,
+    ceil: false,
+};
+
+const EAQI_CATEGORIES: &[Category] = &[
+    category(1, "Good", [0x50, 0xF0, 0xE6]),
+    category(2, "Fair", [0x50, 0xCC, 0xAA]),
+    category(3, "Moderate", [0xF0, 0xE6, 0x41]),
+    category(4, "Poor", [0xFF, 0x50, 0x50]),
+    category(5, "Very poor", [0x96, 0x00, 0x32]),
+    category(6, "Extremely poor", [0x7D, 0x21, 0x81]),
+];
+
+const EAQI_PM25: Table = Table {
+    bands: &[
+        band(0, 100, 1, 1, 1),
+        band(100, 200, 2, 2, 2),
+        band(200, 250, 3, 3, 3),
+        band(250, 500, 4, 4, 4),
+        band(500, 750, 5, 5, 5),
+        band(750, 8000, 6, 6, 6),
+    ],
+    categories: EAQI_CATEGORIES,
+    step: 1,
+    ceil: false,
+};
+
+const EAQI_PM10: Table = Table {
+    bands: &[
+        band(0, 200, 1, 1, 1),
+        band(200, 400, 2, 2, 2),
+        band(400, 500, 3, 3, 3),
+        band(500, 1000, 4, 4, 4),
+        band(1000, 1500, 5, 5, 5),
+        band(1500, 12000, 6, 6, 6),
+    ],
+    categories: EAQI_CATEGORIES,
+    step: 1,
+    ceil: false,
+};
+
+const DAQI_CATEGORIES: &[Category] = &[
+    category(1, "Low", [0x9C, 0xFF, 0x9C]),
+    category(2, "Low", [0x31, 0xFF, 0x00]),
+    category(3, "Low", [0x31, 0xCF, 0x00]),
+    category(4, "Moderate", [0xFF, 0xFF, 0x00]),
+    category(5, "Moderate", [0xFF, 0xCF, 0x00]),
+    category(6, "Moderate", [0xFF, 0x9A, 0x00]),
+    category(7, "High", [0xFF, 0x64, 0x64]),
+    category(8, "High", [0xFF, 0x00, 0x00]),
+    category(9, "High", [0x99, 0x00, 0x00]),
+    category(10, "Very High", [0xCE, 0x30, 0xFF]),
+];
+
+const DAQI_PM25: Table = Table {
+    bands: &[
+        band(0, 110, 1, 1, 1),
+        band(120, 230, 2, 2, 2),
+        band(240, 350, 3, 3, 3),
+        band(360, 410, 4, 4, 4),
+        band(420, 470, 5, 5, 5),
+        band(480, 530, 6, 6, 6),
+        band(540, 580, 7, 7, 7),
+        band(590, 640, 8, 8, 8),
+        band(650, 700, 9, 9, 9),
+        open(710, 10, 10),
+    ],
+    categories: DAQI_CATEGORIES,
+    step: 10,
+    ceil: false,
+};
+
+const DAQI_PM10: Table = Table {
+    bands: &[
+        band(0, 160, 1, 1, 1),
+        band(170, 330, 2, 2, 2),
+        band(340, 500, 3, 3, 3),
+        band(510, 580, 4, 4, 4),
+        band(590, 660, 5, 5, 5),
+        band(670, 750, 6, 6, 6),
+        band(760, 830, 7, 7, 7),
+        band(840, 910, 8, 8, 8),
+        band(920, 1000, 9, 9, 9),
+        open(1010, 10, 10),
+    ],
+    categories: DAQI_CATEGORIES,
+    step: 10,
+    ceil: false,
+};
+
+const NAQI_CATEGORIES: &[Category] = &[
+    category(1, "Good", [0x00, 0xB0, 0x50]),
+    category(2, "Satisfactory", [0x92, 0xD0, 0x50]),
+    category(3, "Moderately polluted", [0xFF, 0xFF, 0x00]),
+    category(4, "Poor", [0xFF, 0x99, 0x00]),
+    category(5, "Very poor", [0xFF, 0x00, 0x00]),
+    category(6, "Severe", [0xC0, 0x00, 0x00]),
+];
+
+const NAQI_PM25: Table = Table {
+    bands: &[
+        band(0, 300, 0, 50, 1),
+        band(310, 600, 51, 100, 2),
+        band(610, 900, 101, 200, 3),
+        band(910, 1200, 201, 300, 4),
+        band(1210, 2500, 301, 400, 5),
+        open(2510, 401, 6),
+    ],
+    categories: NAQI_CATEGORIES,
+    step: 10,
+    ceil: false,
+};
+
+const NAQI_PM10: Table = Table {
+    bands: &[
+        band(0, 500, 0, 50, 1),
+        band(510, 1000, 51, 100, 2),
+        band(1010, 2500, 101, 200, 3),
+        band(2510, 3500, 201, 300, 4),
+        band(3510, 4300, 301, 400, 5),
+        open(4310, 401, 6),
+    ],
+    categories: NAQI_CATEGORIES,
+    step: 10,
+    ceil: false,
+};
+
+const CHINA_CATEGORIES: &[Category] = &[
+    category(1, "Excellent", [0x00, 0xE4, 0x00]),
+    category(2, "Good", [0xFF, 0xFF, 0x00]),
+    category(3, "Lightly polluted", [0xFF, 0x7E, 0x00]),
+    category(4, "Moderately polluted", [0xFF, 0x00, 0x00]),
+    category(5, "Heavily polluted", [0x99, 0x00, 0x4C]),
+    category(6, "Severely polluted", [0x7E, 0x00, 0x23]),
+];
+
+const CHINA_PM25: Table = Table {
+    bands: &[
+        band(0, 350, 0, 50, 1),
+        band(350, 750, 50, 100, 2),
+        band(750, 1150, 100, 150, 3),
+        band(1150, 1500, 150, 200, 4),
+        band(1500, 2500, 200, 300, 5),
+        band(2500, 3500, 300, 400, 6),
+        band(3500, 5000, 400, 500, 6),
+    ],
+    categories: CHINA_CATEGORIES,
+    step: 10,
+    ceil: true,
+};
+
+const CHINA_PM10: Table = Table {
+    bands: &[
+        band(0, 500, 0, 50, 1),
+        band(500, 1500, 50, 100, 2),
+        band(1500, 2500, 100, 150, 3),
+        band(2500, 3500, 150, 200, 4),
+        band(3500, 4200, 200, 300, 5),
+        band(4200, 5000, 300, 400, 6),
+        band(5000, 6000, 400, 500, 6),
+    ],
+    categories: CHINA_CATEGORIES,
+    step: 10,
+    ceil: true,
+};
+
+impl Table {
+    fn index(&self, concentration: u16) -> (u16, &Band) {
+        let c = concentration - concentration % self.step;
+        // beyond the table, the highest band goes on
+        let pos = self
+            .bands
+            .iter()
+            .position(|b| b.high.is_none_or(|high| c <= high))
+            .unwrap_or(self.bands.len() - 1);
+        let band = &self.bands[pos];
+
+        // an open-ended band continues the slope of the band below
+        let (slope, high) = band.high.map_or_else(
+            || {
+                let below = &self.bands[pos - 1];
+                (below, below.high.unwrap_or(below.low))
+            },
+            |high| (band, high),
+        );
+
+        let rise = u32::from(slope.index_high - slope.index_low);
+        let run = u32::from(high - slope.low).max(1);
+        let over = u32::from(c.saturating_sub(band.low)) * rise;
+        let over = if self.ceil {
+            over.div_ceil(run)
+        } else {
+            (2 * over + run) / (2 * run)
+        };
+        let index = u32::from(band.index_low) + over;
+        (u16::try_from(index).unwrap_or(u16::MAX), band)
+    }
+}
+
+impl Scale {
+    const fn table(self, pollutant: Pollutant) -> &'static Table {
+        match (self, pollutant) {
+            (Self::UsEpa, Pollutant::Pm25) => &US_PM25,
+            (Self::UsEpa, Pollutant::Pm10) => &US_PM10,
+            (Self::CaqiHourly, Pollutant::Pm25) => &CAQI_HOURLY_PM25,
+            (Self::CaqiHourly, Pollutant::Pm10) => &CAQI_HOURLY_PM10,
+            (Self::CaqiDaily, Pollutant::Pm25) => &CAQI_DAILY_PM25,
+            (Self::CaqiDaily, Pollutant::Pm10) => &CAQI_DAILY_PM10,
+            (Self::Eaqi, Pollutant::Pm25) => &EAQI_PM25,
+            (Self::Eaqi, Pollutant::Pm10) => &EAQI_PM10,
+            (Self::UkDaqi, Pollutant::Pm25) => &DAQI_PM25,
+            (Self::UkDaqi, Pollutant::Pm10) => &DAQI_PM10,
+            (Self::IndiaNaqi, Pollutant::Pm25) => &NAQI_PM25,
+            (Self::IndiaNaqi, Pollutant::Pm10) => &NAQI_PM10,
+            (Self::ChinaAqi, Pollutant::Pm25) => &CHINA_PM25,
+            (Self::ChinaAqi, Pollutant::Pm10) => &CHINA_PM10,
+        }
+    }
+
+    /// The sub-index of a single pollutant's `concentration`,
+    /// in 0.1 µg/m3.
+    #[must_use]
+    pub fn sub_index(self, pollutant: Pollutant, concentration: u16) -> Aqi {
+        let table = self.table(pollutant);
+        let (index, band) = table.index(concentration);
+        let category = table.categories[usize::from(band.category - 1)];
+        Aqi {
+            scale: self,
+            pollutant,
+            index,
+            category,
+            band: *band,
+        }
+    }
+
+    /// The index of a measurement (or an average of measurements):
+    /// the higher one of its PM2.5 and PM10 sub-indices.
+    #[must_use]
+    pub fn index(self, measurement: &Measurement) -> Aqi {
+        let pm25 = self.sub_index(Pollutant::Pm25, measurement.pm25());
+        let pm10 = self.sub_index(Pollutant::Pm10, measurement.pm10());
+        if pm10.index > pm25.index { pm10 } else { pm25 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Pollutant, Scale};
+    use crate::Measurement;
+    use alloc::string::ToString;
+
+    /// Sub-index and category name of `concentration` in µg/m3.
+    fn aqi(scale: Scale, pollutant: Pollutant, concentration: f32) -> (u16, &'static str) {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let tenths = (concentration * 10.0).round() as u16;
+        let aqi = scale.sub_index(pollutant, tenths);
+        (aqi.index, aqi.category.name)
+    }
+
+    #[test]
+    fn us_epa() {
+        use Pollutant::{Pm10, Pm25};
+        let us = |p, c| aqi(Scale::UsEpa, p, c);
+
+        // the worked example of the EPA's technical assistance document
+        assert_eq!(us(Pm25, 35.9), (102, "Unhealthy for Sensitive Groups"));
+        assert_eq!(us(Pm25, 0.0), (0, "Good"));
+        assert_eq!(us(Pm25, 9.0), (50, "Good"));
+        assert_eq!(us(Pm25, 9.1), (51, "Moderate"));
+        assert_eq!(us(Pm25, 55.4), (150, "Unhealthy for Sensitive Groups"));
+        assert_eq!(us(Pm25, 55.5), (151, "Unhealthy"));
+        assert_eq!(us(Pm25, 225.4), (300, "Very Unhealthy"));
+        assert_eq!(us(Pm25, 325.4), (500, "Hazardous"));
+        // beyond the AQI
+        assert_eq!(us(Pm25, 425.4), (699, "Hazardous"));
+
+        // PM10 is truncated to whole µg/m3
+        assert_eq!(us(Pm10, 54.9), (50, "Good"));
+        assert_eq!(us(Pm10, 55.0), (51, "Moderate"));
+        assert_eq!(us(Pm10, 154.0), (100, "Moderate"));
+        assert_eq!(us(Pm10, 424.0), (300, "Very Unhealthy"));
+        assert_eq!(us(Pm10, 604.0), (500, "Hazardous"));
+
+        let overall = Scale::UsEpa.index(&Measurement::new(120, 1600));
+        assert_eq!((overall.index, overall.pollutant), (103, Pm10));
+        assert_eq!(overall.category.color.to_string(), "#FF7E00");
+        assert_eq!((overall.band.low, overall.band.high), (1550, Some(2540)));
+    }
+
+    #[test]
+    fn caqi() {
+        use Pollutant::{Pm10, Pm25};
+        let hourly = |p, c| aqi(Scale::CaqiHourly, p, c);
+        let daily = |p, c| aqi(Scale::CaqiDaily, p, c);
+
+        assert_eq!(hourly(Pm10, 25.0), (25, "Very low"));
+        assert_eq!(hourly(Pm10, 50.0), (50, "Low"));
+        assert_eq!(hourly(Pm10, 70.0), (63, "Medium"));
+        assert_eq!(hourly(Pm10, 180.0), (100, "High"));
+        assert_eq!(hourly(Pm10, 270.0), (125, "Very high"));
+        assert_eq!(hourly(Pm25, 15.0), (25, "Very low"));
+        assert_eq!(hourly(Pm25, 55.0), (75, "Medium"));
+        assert_eq!(hourly(Pm25, 110.0), (100, "High"));
+
+        assert_eq!(daily(Pm10, 15.0), (25, "Very low"));
+        assert_eq!(daily(Pm10, 100.0), (100, "High"));
+        assert_eq!(daily(Pm25, 20.0), (50, "Low"));
+        assert_eq!(daily(Pm25, 60.0), (100, "High"));
+        assert_eq!(daily(Pm25, 90.0), (125, "Very high"));
+    }
+
+    #[test]
+    fn eaqi() {
+        use Pollutant::{Pm10, Pm25};
+        let eaqi = |p, c| aqi(Scale::Eaqi, p, c);
+
+        assert_eq!(eaqi(Pm25, 10.0), (1, "Good"));
+        assert_eq!(eaqi(Pm25, 10.1), (2, "Fair"));
+        assert_eq!(eaqi(Pm25, 25.0), (3, "Moderate"));
+        assert_eq!(eaqi(Pm25, 50.0), (4, "Poor"));
+        assert_eq!(eaqi(Pm25, 75.0), (5, "Very poor"));
+        assert_eq!(eaqi(Pm25, 900.0), (6, "Extremely poor"));
+        assert_eq!(eaqi(Pm10, 40.0), (2, "Fair"));
+        assert_eq!(eaqi(Pm10, 100.1), (5, "Very poor"));
+        assert_eq!(eaqi(Pm10, 150.1), (6, "Extremely poor"));
+    }
+
+    #[test]
+    fn uk_daqi() {
+        use Pollutant::{Pm10, Pm25};
+        let daqi = |p, c| aqi(Scale::UkDaqi, p, c);
+
+        assert_eq!(daqi(Pm25, 11.9), (1, "Low"));
+        assert_eq!(daqi(Pm25, 12.0), (2, "Low"));
+        assert_eq!(daqi(Pm25, 36.0), (4, "Moderate"));
+        assert_eq!(daqi(Pm25, 58.0), (7, "High"));
+        assert_eq!(daqi(Pm25, 70.9), (9, "High"));
+        assert_eq!(daqi(Pm25, 71.0), (10, "Very High"));
+        assert_eq!(daqi(Pm25, 500.0), (10, "Very High"));
+        assert_eq!(daqi(Pm10, 16.0), (1, "Low"));
+        assert_eq!(daqi(Pm10, 75.0), (6, "Moderate"));
+        assert_eq!(daqi(Pm10, 101.0), (10, "Very High"));
+
+        let daqi = Scale::UkDaqi.sub_index(Pm25, 420);
+        assert_eq!(daqi.category.color.to_string(), "#FFCF00");
+    }
+
+    #[test]
+    fn india_naqi() {
+        use Pollutant::{Pm10, Pm25};
+        let naqi = |p, c| aqi(Scale::IndiaNaqi, p, c);
+
+        assert_eq!(naqi(Pm25, 30.0), (50, "Good"));
+        assert_eq!(naqi(Pm25, 31.0), (51, "Satisfactory"));
+        assert_eq!(naqi(Pm25, 90.0), (200, "Moderately polluted"));
+        assert_eq!(naqi(Pm25, 120.0), (300, "Poor"));
+        assert_eq!(naqi(Pm25, 250.0), (400, "Very poor"));
+        assert_eq!(naqi(Pm25, 251.0), (401, "Severe"));
+        assert_eq!(naqi(Pm10, 100.0), (100, "Satisfactory"));
+        assert_eq!(naqi(Pm10, 175.0), (150, "Moderately polluted"));
+        assert_eq!(naqi(Pm10, 430.0), (400, "Very poor"));
+        assert_eq!(naqi(Pm10, 431.0), (401, "Severe"));
+    }
+
+    #[test]
+    fn china_aqi() {
+        use Pollutant::{Pm10, Pm25};
+        let china = |p, c| aqi(Scale::ChinaAqi, p, c);
+
+        assert_eq!(china(Pm25, 35.0), (50, "Excellent"));
+        // sub-indices are rounded up
+        assert_eq!(china(Pm25, 36.0), (52, "Good"));
+        assert_eq!(china(Pm25, 75.0), (100, "Good"));
+        assert_eq!(china(Pm25, 150.0), (200, "Moderately polluted"));
+        assert_eq!(china(Pm25, 250.0), (300, "Heavily polluted"));
+        assert_eq!(china(Pm25, 500.0), (500, "Severely polluted"));
+        assert_eq!(china(Pm10, 150.0), (100, "Good"));
+        assert_eq!(china(Pm10, 420.0), (300, "Heavily polluted"));
+        assert_eq!(china(Pm10, 600.0), (500, "Severely polluted"));
+    }
+}
@@ -0,0 +1,12 @@
+//! The async driver, built on embedded-io-async and embedded-hal-async.
+//!
+//! Its types are also re-exported at the crate root. It can be used
+//! alongside the [blocking driver](crate::blocking) in the same build.
+
+use embedded_hal_async::delay::DelayNs;
+use embedded_io_async::{Read, Write};
+use maybe_async::must_be_async as maybe_async;
+
+include!("driver.rs");
+include!("supervised.rs");
+include!("dynamic.rs");
@@ -0,0 +1,229 @@
+//! Decode SDS011 serial traffic and print one annotated line per frame.
+//!
+//! Input is read from a file or stdin and may be
+//! * hex text, e.g. copied from a logic analyzer or the output of
+//!   `hexdump -C` / `xxd` (offsets and ASCII columns are skipped),
+//! * raw bytes, e.g. `cat /dev/ttyUSB0 > dump.bin`,
+//! * a capture written by `sds011::capture::Recorder`.
+//!
+//! Without a format flag, captures are recognized by their header,
+//! printable text is parsed as hex and anything else is taken as raw bytes.
+
+use sds011::capture::{Direction, Event, Records};
+use sds011::decode::{Chunk, Chunks};
+use std::io::{self, Read};
+use std::process::ExitCode;
+use std::{env, fs};
+
+const USAGE: &str = "usage: sds011-decode [--hex | --raw | --capture] [FILE]";
+
+enum Format {
+    Hex,
+    Raw,
+    Capture,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("sds011-decode: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut format = None;
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--hex" => format = Some(Format::Hex),
+            "--raw" => format = Some(Format::Raw),
+            "--capture" => format = Some(Format::Capture),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(format!("unexpected argument '{arg}'\n{USAGE}")),
+        }
+    }
+
+    let input = match path {
+        Some(path) => fs::read(&path).map_err(|e| format!("{path}: {e}"))?,
+        None => {
+            let mut buf = Vec::new();
+            io::stdin()
+                .read_to_end(&mut buf)
+                .map_err(|e| format!("stdin: {e}"))?;
+            buf
+        }
+    };
+
+    match format.unwrap_or_else(|| detect(&input)) {
+        Format::Hex => print_stream(&parse_hex(&input)?, ""),
+        Format::Raw => print_stream(&input, ""),
+        Format::Capture => print_capture(&input)?,
+    }
+    Ok(())
+}
+
+fn detect(input: &[u8]) -> Format {
+    if input.starts_with(b"SDSCAP") {
+        Format::Capture
+    } else if input
+        .iter()
+        .all(|b| b.is_ascii_graphic() || b.is_ascii_whitespace())
+    {
+        Format::Hex
+    } else {
+        Format::Raw
+    }
+}
+
+fn parse_hex(input: &[u8]) -> Result<Vec<u8>, String> {
+    let text = std::str::from_utf8(input).map_err(|_| "input is not text")?;
+    let mut out = Vec::new();
+    let mut dump = false;
+
+    for (n, line) in text.lines().enumerate() {
+        let mut tokens: Vec<&str> = line
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty())
+            .collect();
+
+        // hexdump -C: "00000010  aa c0 ... |....|", ending in a bare offset
+        if line.contains('|') {
+            dump = true;
+            let end = tokens.iter().position(|t| t.starts_with('|'));
+            tokens.truncate(end.unwrap_or(tokens.len()));
+            if !tokens.is_empty() {
+                tokens.remove(0);
+            }
+        } else if tokens.first().is_some_and(|t| t.ends_with(':')) {
+            // xxd: "00000010: aac0 d404 ...  ..", the ASCII column after two
+            // spaces (more on a short last line)
+            dump = true;
+            let hex = line.split_once(": ").map_or("", |(_, rest)| rest);
+            let hex = hex.split_once("  ").map_or(hex, |(hex, _)| hex);
+            tokens = hex.split_whitespace().collect();
+        } else if dump && tokens.len() == 1 {
+            continue;
+        }
+
+        for token in tokens {
+            let digits = token.trim_start_matches("0x").trim_start_matches("0X");
+            if digits.len() % 2 != 0 {
+                return Err(format!(
+                    "line {}: odd number of hex digits in '{token}'",
+                    n + 1
+                ));
+            }
+            for i in (0..digits.len()).step_by(2) {
+                let byte = digits
+                    .get(i..i + 2)
+                    .and_then(|d| u8::from_str_radix(d, 16).ok())
+                    .ok_or_else(|| format!("line {}: '{token}' is not hex", n + 1))?;
+                out.push(byte);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+fn print_stream(data: &[u8], prefix: &str) {
+    for chunk in Chunks::new(data) {
+        match chunk {
+            Chunk::Frame(frame) => println!("{prefix}{frame}"),
+            Chunk::Noise(bytes) => {
+                let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02X}")).collect();
+                println!(
+                    "{prefix}?? {} | {} bytes of noise",
+                    hex.join(" "),
+                    bytes.len()
+                );
+            }
+        }
+    }
+}
+
+fn print_capture(input: &[u8]) -> Result<(), String> {
+    let records = Records::new(input).map_err(|e| e.to_string())?;
+
+    // reads and writes may be split arbitrarily, so join them up per direction
+    let mut pending: Option<(Direction, u64)> = None;
+    let mut buf = Vec::new();
+    let flush = |buf: &mut Vec<u8>, at: Option<(Direction, u64)>| {
+        if let Some((_, time)) = at {
+            print_stream(buf, &timestamp(time));
+        }
+        buf.clear();
+    };
+
+    for record in records {
+        let record = record.map_err(|e| e.to_string())?;
+        match record.event {
+            Event::Data(data) => {
+                if pending.is_none_or(|(dir, _)| dir != record.direction) {
+                    flush(&mut buf, pending);
+                    pending = Some((record.direction, record.timestamp_us));
+                }
+                buf.extend_from_slice(data);
+            }
+            Event::Error(kind) => {
+                flush(&mut buf, pending.take());
+                let what = match record.direction {
+                    Direction::ToSensor => "-> write",
+                    Direction::FromSensor => "<- read",
+                };
+                println!("{}{what} error: {kind:?}", timestamp(record.timestamp_us));
+            }
+        }
+    }
+    flush(&mut buf, pending);
+
+    Ok(())
+}
+
+fn timestamp(us: u64) -> String {
+    format!("[{:>5}.{:06}] ", us / 1_000_000, us % 1_000_000)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_hex;
+
+    const FRAME: [u8; 10] = [0xAA, 0xC0, 0xD4, 0x04, 0x3A, 0x0A, 0xA1, 0x60, 0x1D, 0xAB];
+
+    #[test]
+    fn xxd() {
+        let dump = "\
+00000000: aac0 d404 3a0a a160 1dab aac0 d404 3a0a  ....:..`......:.
+00000010: a160 1dab                                .`..
+";
+        assert_eq!(parse_hex(dump.as_bytes()).unwrap(), [FRAME, FRAME].concat());
+
+        // a single short line, its ASCII column fitting 16 bytes of hex
+        let dump = "00000000: aac0 d404 3a0a a160 1dab            ....:..`..\n";
+        assert_eq!(parse_hex(dump.as_bytes()).unwrap(), FRAME);
+    }
+
+    #[test]
+    fn hexdump() {
+        let dump = "\
+00000000  aa c0 d4 04 3a 0a a1 60  1d ab aa c0 d4 04 3a 0a  |....:..`......:.|
+00000010  a1 60 1d ab                                       |.`..|
+00000014
+";
+        assert_eq!(parse_hex(dump.as_bytes()).unwrap(), [FRAME, FRAME].concat());
+    }
+
+    #[test]
+    fn plain() {
+        let text = "0xAA 0xC0 d4,04 3a0a\na1 60 1d ab\n";
+        assert_eq!(parse_hex(text.as_bytes()).unwrap(), FRAME);
+        assert!(parse_hex(b"aa c").is_err());
+    }
+}
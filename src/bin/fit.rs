@@ -0,0 +1,110 @@
+//! Fit a calibration from co-location data and report how well it fits.
+//!
+//! Input is a CSV file (or stdin) of time-aligned sensor readings and
+//! reference values, see `sds011::fit` for the columns. For each pollutant,
+//! one line reports the fitted correction, R², RMSE and bias.
+//!
+//! With `--out`, the calibration of the sensor given by `--id` (hex, as
+//! printed by the driver) is written to a file, ready for
+//! `sds011::calibration::Calibration::decode`. Humidity-aware fits cannot
+//! be stored that way, as the driver does not know the humidity; apply
+//! their coefficients with `sds011::humidity::Model::Linear` instead.
+
+use sds011::aqi::Pollutant;
+use sds011::calibration::{Calibration, Curve};
+use sds011::fit::{self, Dataset, Fit, Method, Model};
+use std::io::{self, Read};
+use std::process::ExitCode;
+use std::{env, fs};
+
+const USAGE: &str = "usage: sds011-fit [--method linear|theil-sen|humidity] \
+                     [--id ID --out FILE] [CSV]";
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("sds011-fit: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut method = Method::Linear;
+    let mut id = None;
+    let mut out = None;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--method" => {
+                method = match args.next().as_deref() {
+                    Some("linear") => Method::Linear,
+                    Some("theil-sen") => Method::TheilSen,
+                    Some("humidity") => Method::Humidity,
+                    _ => return Err(format!("invalid method\n{USAGE}")),
+                }
+            }
+            "--id" => {
+                let value = args.next().unwrap_or_default();
+                let hex = value.trim_start_matches("0x").trim_start_matches("0X");
+                id = Some(
+                    u16::from_str_radix(hex, 16)
+                        .map_err(|_| format!("invalid sensor ID '{value}'"))?,
+                );
+            }
+            "--out" => out = Some(args.next().ok_or(USAGE)?),
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                return Ok(());
+            }
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return Err(format!("unexpected argument '{arg}'\n{USAGE}")),
+        }
+    }
+
+    let input = match path {
+        Some(path) => fs::read_to_string(&path).map_err(|e| format!("{path}: {e}"))?,
+        None => {
+            let mut buf = String::new();
+            io::stdin()
+                .read_to_string(&mut buf)
+                .map_err(|e| format!("stdin: {e}"))?;
+            buf
+        }
+    };
+    let data = Dataset::from_csv(&input).map_err(|e| e.to_string())?;
+
+    let mut fits = Vec::new();
+    for (pollutant, name) in [(Pollutant::Pm25, "PM2.5"), (Pollutant::Pm10, "PM10")] {
+        let fit = fit::fit(data.samples(pollutant), method).map_err(|e| format!("{name}: {e}"))?;
+        println!("{name}: {}", describe(&fit));
+        fits.push(fit);
+    }
+
+    if let Some(out) = out {
+        let id = id.ok_or(format!("--out needs the sensor's --id\n{USAGE}"))?;
+        let calibration = fit::calibration(id, &fits[0], &fits[1])
+            .ok_or("humidity-aware fits cannot be stored as a calibration")?;
+        let mut buf = [0; Calibration::MAX_ENCODED_LEN];
+        let len = calibration.encode(&mut buf).map_err(|e| e.to_string())?;
+        fs::write(&out, &buf[..len]).map_err(|e| format!("{out}: {e}"))?;
+    }
+    Ok(())
+}
+
+fn describe(fit: &Fit) -> String {
+    let model = match fit.model {
+        Model::Curve(Curve::Linear { slope, offset }) => format!("{slope} * PM + {offset}"),
+        Model::Humidity(linear) => format!(
+            "{} * PM + {} * RH + {}",
+            linear.slope, linear.humidity, linear.offset
+        ),
+        Model::Curve(curve) => format!("{curve:?}"),
+    };
+    format!(
+        "{model} (R² {:.3}, RMSE {:.2} µg/m3, bias {:.2} µg/m3, {} samples)",
+        fit.r2, fit.rmse, fit.bias, fit.samples
+    )
+}
@@ -0,0 +1,11 @@
+//! The blocking driver, built on embedded-io and embedded-hal.
+//!
+//! It can be used alongside the [async driver](crate::asynch) in the same build.
+
+use embedded_hal::delay::DelayNs;
+use embedded_io::{Read, Write};
+use maybe_async::must_be_sync as maybe_async;
+
+include!("driver.rs");
+include!("supervised.rs");
+include!("dynamic.rs");
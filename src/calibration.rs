@@ -0,0 +1,479 @@
+//! Per-sensor calibration against a reference monitor.
+//!
+//! Sensors of the same model differ, so co-locating each one with a
+//! reference monitor and fitting a [`Curve`] from its readings to the
+//! reference pays off. A [`Calibration`] holds the curves of PM2.5 and PM10
+//! for the sensor with a given ID. Attached with
+//! [`Config::set_calibration()`](crate::Config::set_calibration), it is
+//! applied to every measurement of that sensor; it can also be applied
+//! to a [`Measurement`] directly.
+//!
+//! ```ignore
+//! let calibration = Calibration::new(
+//!     0xA160,
+//!     Curve::Linear { slope: 0.8, offset: -1.5 },
+//!     Curve::Quadratic { a: 0.001, b: 0.7, c: 0.0 },
+//! );
+//! let config = Config::default().set_calibration(calibration);
+//! ```
+//!
+//! # Storage format
+//! [`Calibration::encode()`] writes a calibration into a few bytes, for
+//! storage in flash or a file; concatenated calibrations can be read back
+//! one after the other with [`Calibration::decode()`]. An encoded
+//! calibration consists of
+//! * the magic bytes `SDSCAL` and a format version byte (currently 1),
+//! * the sensor ID (little endian),
+//! * the curves of PM2.5 and PM10, each a tag byte followed by its
+//!   coefficients as little-endian `f32`: 0 for linear (slope, offset),
+//!   1 for quadratic (a, b, c), and 2 for piecewise linear, followed by
+//!   the number of points and the points (raw, reference).
+
+use crate::Measurement;
+use crate::humidity::tenths;
+use thiserror::Error;
+
+const MAGIC: &[u8; 6] = b"SDSCAL";
+const VERSION: u8 = 1;
+
+const TAG_LINEAR: u8 = 0;
+const TAG_QUADRATIC: u8 = 1;
+const TAG_PIECEWISE: u8 = 2;
+
+/// Errors of calibrations and their storage format.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationError {
+    /// The points of a piecewise curve were too few, too many,
+    /// or not in ascending order.
+    #[error("a piecewise curve needs 2 to 8 points in ascending order")]
+    Points,
+    /// The buffer is too small to hold the encoded calibration.
+    #[error("buffer too small")]
+    BufferTooSmall,
+    /// The data does not start with a supported calibration header.
+    #[error("not a calibration, or unsupported format version")]
+    Header,
+    /// The encoded calibration is malformed or cut off.
+    #[error("calibration is corrupt or truncated")]
+    Corrupt,
+}
+
+/// A piecewise linear curve through up to 8 points.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Piecewise {
+    points: [(f32, f32); Self::MAX_POINTS],
+    len: u8,
+}
+
+impl Piecewise {
+    /// How many points a curve can have.
+    pub const MAX_POINTS: usize = 8;
+
+    /// A curve through `points` of (raw, reference) concentrations in
+    /// µg/m3, in ascending order of the raw concentration. Beyond the first
+    /// and last point, the curve continues the slope of the outer segments.
+    ///
+    /// # Errors
+    /// [`CalibrationError::Points`] if there are less than 2 or more than 8
+    /// points, or they are not in ascending order.
+    pub fn new(points: &[(f32, f32)]) -> Result<Self, CalibrationError> {
+        let ascending = points.windows(2).all(|w| w[0].0 < w[1].0);
+        if !(2..=Self::MAX_POINTS).contains(&points.len()) || !ascending {
+            return Err(CalibrationError::Points);
+        }
+        let mut curve = Self {
+            points: [(0.0, 0.0); Self::MAX_POINTS],
+            len: 0,
+        };
+        for (slot, point) in curve.points.iter_mut().zip(points) {
+            *slot = *point;
+            curve.len += 1;
+        }
+        Ok(curve)
+    }
+
+    /// The curve's points.
+    #[must_use]
+    pub fn points(&self) -> &[(f32, f32)] {
+        self.points.get(..usize::from(self.len)).unwrap_or_default()
+    }
+
+    fn apply(&self, x: f32) -> f32 {
+        let points = self.points();
+        // the segment containing x, or the outer one beyond the ends
+        let segment = points
+            .windows(2)
+            .find(|w| x <= w[1].0)
+            .or_else(|| points.windows(2).last());
+        let Some(&[(x0, y0), (x1, y1)]) = segment else {
+            return x;
+        };
+        y0 + (x - x0) * (y1 - y0) / (x1 - x0)
+    }
+}
+
+/// A correction curve from raw to reference concentrations, in µg/m3.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Curve {
+    /// `slope * x + offset`
+    Linear {
+        /// The slope.
+        slope: f32,
+        /// The offset, in µg/m3.
+        offset: f32,
+    },
+    /// `a * x² + b * x + c`
+    Quadratic {
+        /// The quadratic coefficient.
+        a: f32,
+        /// The linear coefficient.
+        b: f32,
+        /// The constant term, in µg/m3.
+        c: f32,
+    },
+    /// A piecewise linear curve.
+    Piecewise(Piecewise),
+}
+
+impl Curve {
+    /// The curve that changes nothing.
+    pub const IDENTITY: Self = Self::Linear {
+        slope: 1.0,
+        offset: 0.0,
+    };
+
+    /// Apply the curve to a concentration in 0.1 µg/m3,
+    /// clamping the result at zero.
+    #[must_use]
+    pub fn apply(&self, raw: u16) -> u16 {
+        let x = f32::from(raw) / 10.0;
+        tenths(match self {
+            Self::Linear { slope, offset } => slope * x + offset,
+            Self::Quadratic { a, b, c } => (a * x + b) * x + c,
+            Self::Piecewise(curve) => curve.apply(x),
+        })
+    }
+
+    fn encode(&self, out: &mut Writer<'_>) -> Result<(), CalibrationError> {
+        match self {
+            Self::Linear { slope, offset } => {
+                out.put(&[TAG_LINEAR])?;
+                out.floats(&[*slope, *offset])
+            }
+            Self::Quadratic { a, b, c } => {
+                out.put(&[TAG_QUADRATIC])?;
+                out.floats(&[*a, *b, *c])
+            }
+            Self::Piecewise(curve) => {
+                out.put(&[TAG_PIECEWISE, curve.len])?;
+                for (x, y) in curve.points() {
+                    out.floats(&[*x, *y])?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn decode(data: &mut Reader<'_>) -> Result<Self, CalibrationError> {
+        match data.byte()? {
+            TAG_LINEAR => Ok(Self::Linear {
+                slope: data.float()?,
+                offset: data.float()?,
+            }),
+            TAG_QUADRATIC => Ok(Self::Quadratic {
+                a: data.float()?,
+                b: data.float()?,
+                c: data.float()?,
+            }),
+            TAG_PIECEWISE => {
+                let len = usize::from(data.byte()?);
+                let mut points = [(0.0, 0.0); Piecewise::MAX_POINTS];
+                for point in points.iter_mut().take(len) {
+                    *point = (data.float()?, data.float()?);
+                }
+                let points = points.get(..len).ok_or(CalibrationError::Corrupt)?;
+                Piecewise::new(points)
+                    .map(Self::Piecewise)
+                    .map_err(|_| CalibrationError::Corrupt)
+            }
+            _ => Err(CalibrationError::Corrupt),
+        }
+    }
+}
+
+/// The calibration of one sensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Calibration {
+    sensor_id: u16,
+    pm25: Curve,
+    pm10: Curve,
+}
+
+impl Calibration {
+    /// The size of the largest encoded calibration, in bytes.
+    pub const MAX_ENCODED_LEN: usize = MAGIC.len() + 3 + 2 * (2 + 8 * Piecewise::MAX_POINTS);
+
+    /// The calibration of the sensor with ID `sensor_id`.
+    #[must_use]
+    pub const fn new(sensor_id: u16, pm25: Curve, pm10: Curve) -> Self {
+        Self {
+            sensor_id,
+            pm25,
+            pm10,
+        }
+    }
+
+    /// Find the calibration of the sensor with ID `sensor_id`.
+    #[must_use]
+    pub fn find(calibrations: &[Self], sensor_id: u16) -> Option<&Self> {
+        calibrations.iter().find(|c| c.sensor_id == sensor_id)
+    }
+
+    /// The ID of the calibrated sensor.
+    #[must_use]
+    pub const fn sensor_id(&self) -> u16 {
+        self.sensor_id
+    }
+
+    /// The curve of PM2.5.
+    #[must_use]
+    pub const fn pm25(&self) -> &Curve {
+        &self.pm25
+    }
+
+    /// The curve of PM10.
+    #[must_use]
+    pub const fn pm10(&self) -> &Curve {
+        &self.pm10
+    }
+
+    /// Calibrate a measurement of the sensor, marking it as
+    /// [calibrated](crate::Quality::calibrated).
+    /// This does not check where the measurement came from.
+    #[must_use]
+    pub fn apply(&self, measurement: &Measurement) -> Measurement {
+        let mut quality = measurement.quality();
+        quality.calibrated = true;
+        Measurement::new(
+            self.pm25.apply(measurement.pm25()),
+            self.pm10.apply(measurement.pm10()),
+        )
+        .with_quality(quality)
+    }
+
+    /// Write the calibration into `buf` (see the
+    /// [storage format](self#storage-format)), returning the number of bytes
+    /// written, at most [`Self::MAX_ENCODED_LEN`].
+    ///
+    /// # Errors
+    /// [`CalibrationError::BufferTooSmall`] if it does not fit into `buf`.
+    pub fn encode(&self, buf: &mut [u8]) -> Result<usize, CalibrationError> {
+        let mut out = Writer { buf, pos: 0 };
+        out.put(MAGIC)?;
+        out.put(&[VERSION])?;
+        out.put(&self.sensor_id.to_le_bytes())?;
+        self.pm25.encode(&mut out)?;
+        self.pm10.encode(&mut out)?;
+        Ok(out.pos)
+    }
+
+    /// Read a calibration from the start of `data`, returning it and the
+    /// number of bytes it took.
+    ///
+    /// # Errors
+    /// [`CalibrationError::Header`] if `data` does not start with a
+    /// supported header, [`CalibrationError::Corrupt`] if the rest is
+    /// malformed or cut off.
+    pub fn decode(data: &[u8]) -> Result<(Self, usize), CalibrationError> {
+        match data.split_at_checked(MAGIC.len()) {
+            Some((magic, [VERSION, ..])) if magic == MAGIC => {}
+            _ => return Err(CalibrationError::Header),
+        }
+        let mut data = Reader {
+            data,
+            pos: MAGIC.len() + 1,
+        };
+        let sensor_id = u16::from_le_bytes(data.take()?);
+        let pm25 = Curve::decode(&mut data)?;
+        let pm10 = Curve::decode(&mut data)?;
+        Ok((Self::new(sensor_id, pm25, pm10), data.pos))
+    }
+}
+
+struct Writer<'a> {
+    buf: &'a mut [u8],
+    pos: usize,
+}
+
+impl Writer<'_> {
+    fn put(&mut self, bytes: &[u8]) -> Result<(), CalibrationError> {
+        let end = self.pos + bytes.len();
+        let slot = self
+            .buf
+            .get_mut(self.pos..end)
+            .ok_or(CalibrationError::BufferTooSmall)?;
+        slot.copy_from_slice(bytes);
+        self.pos = end;
+        Ok(())
+    }
+
+    fn floats(&mut self, floats: &[f32]) -> Result<(), CalibrationError> {
+        floats.iter().try_for_each(|f| self.put(&f.to_le_bytes()))
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], CalibrationError> {
+        let (bytes, _) = self
+            .data
+            .get(self.pos..)
+            .and_then(<[u8]>::split_first_chunk)
+            .ok_or(CalibrationError::Corrupt)?;
+        self.pos += N;
+        Ok(*bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, CalibrationError> {
+        self.take().map(|[b]| b)
+    }
+
+    fn float(&mut self) -> Result<f32, CalibrationError> {
+        self.take().map(f32::from_le_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Calibration, CalibrationError, Curve, Piecewise};
+    use crate::mock::fixtures::{ID, SDS011, init_script, query, sleep_set};
+    use crate::mock::{Mock, MockDelay, SleepMode};
+    use crate::{Config, Measurement};
+
+    fn piecewise() -> Curve {
+        Curve::Piecewise(Piecewise::new(&[(0.0, 1.0), (10.0, 9.0), (50.0, 29.0)]).unwrap())
+    }
+
+    #[test]
+    fn curves() {
+        let linear = Curve::Linear {
+            slope: 0.8,
+            offset: -1.5,
+        };
+        assert_eq!(linear.apply(200), 145);
+        // clamped at zero
+        assert_eq!(linear.apply(10), 0);
+
+        let quadratic = Curve::Quadratic {
+            a: 0.01,
+            b: 0.5,
+            c: 1.0,
+        };
+        assert_eq!(quadratic.apply(200), 150);
+
+        let piecewise = piecewise();
+        assert_eq!(piecewise.apply(50), 50);
+        assert_eq!(piecewise.apply(300), 190);
+        // beyond the last point
+        assert_eq!(piecewise.apply(1_000), 540);
+        assert_eq!(Curve::IDENTITY.apply(1_234), 1_234);
+    }
+
+    #[test]
+    fn piecewise_points() {
+        assert_eq!(Piecewise::new(&[(0.0, 0.0)]), Err(CalibrationError::Points));
+        assert_eq!(
+            Piecewise::new(&[(1.0, 0.0), (1.0, 2.0)]),
+            Err(CalibrationError::Points)
+        );
+        assert_eq!(
+            Piecewise::new(&[(0.0, 0.0); 9]),
+            Err(CalibrationError::Points)
+        );
+    }
+
+    #[test]
+    fn storage() {
+        let calibrations = [
+            Calibration::new(ID, piecewise(), Curve::IDENTITY),
+            Calibration::new(
+                0x1234,
+                Curve::Quadratic {
+                    a: 0.01,
+                    b: 0.5,
+                    c: 1.0,
+                },
+                piecewise(),
+            ),
+        ];
+        let mut buf = [0u8; 2 * Calibration::MAX_ENCODED_LEN];
+        let first = calibrations[0].encode(&mut buf).unwrap();
+        let second = calibrations[1].encode(&mut buf[first..]).unwrap();
+        assert_eq!(first, 9 + 2 + 24 + 9);
+
+        let mut data = &buf[..first + second];
+        for calibration in &calibrations {
+            let (decoded, len) = Calibration::decode(data).unwrap();
+            assert_eq!(&decoded, calibration);
+            data = &data[len..];
+        }
+        assert!(data.is_empty());
+
+        let found = Calibration::find(&calibrations, 0x1234);
+        assert_eq!(found, Some(&calibrations[1]));
+        assert_eq!(Calibration::find(&calibrations, 0xFFFF), None);
+    }
+
+    #[test]
+    fn storage_errors() {
+        let calibration = Calibration::new(ID, piecewise(), piecewise());
+        let mut buf = [0u8; Calibration::MAX_ENCODED_LEN];
+        assert_eq!(
+            calibration.encode(&mut buf[..20]),
+            Err(CalibrationError::BufferTooSmall)
+        );
+        let len = calibration.encode(&mut buf).unwrap();
+
+        assert_eq!(
+            Calibration::decode(&buf[..len - 1]),
+            Err(CalibrationError::Corrupt)
+        );
+        assert_eq!(
+            Calibration::decode(b"SDSCAP\x01"),
+            Err(CalibrationError::Header)
+        );
+        // an unknown curve
+        buf[9] = 7;
+        assert_eq!(Calibration::decode(&buf), Err(CalibrationError::Corrupt));
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn calibrated_measurements() {
+        let curve = Curve::Linear {
+            slope: 0.5,
+            offset: 1.0,
+        };
+        for (id, expected) in [(ID, (628, 1_319)), (0x1234, (1_236, 2_618))] {
+            let mut script = init_script();
+            script.extend([
+                sleep_set(SleepMode::Work, Some(ID)),
+                query(1, 2),
+                query(1236, 2618),
+                sleep_set(SleepMode::Sleep, Some(ID)),
+            ]);
+            let mut serial = Mock::new(script);
+
+            let config = Config::default().set_calibration(Calibration::new(id, curve, curve));
+            let sensor = SDS011::new(&mut serial, config);
+            let mut sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+            let m = sensor.measure(&mut MockDelay::new()).await.unwrap();
+            assert_eq!(m, Measurement::new(expected.0, expected.1));
+            assert_eq!(m.quality().calibrated, id == ID);
+            serial.done();
+        }
+    }
+}
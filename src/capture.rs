@@ -0,0 +1,790 @@
+//! Recording and replaying serial traffic.
+//!
+//! [`Recorder`] wraps a serial interface and logs every byte sent and
+//! received, with timestamps, to a sink in a compact binary format.
+//! [`Replay`] turns such a capture back into a serial interface, so a session
+//! recorded in the field can be fed into [`SDS011`](crate::SDS011) in a test
+//! and fails (or succeeds) exactly the same way.
+//!
+//! # Capture format
+//! A capture starts with the magic bytes `SDSCAP` and a format version byte
+//! (currently 1). It is followed by records, each consisting of
+//! * a tag byte: 0 for data sent to the sensor, 1 for data received from it,
+//!   2 for a write error and 3 for a read error,
+//! * the time since the previous record (or since the recording started)
+//!   in microseconds, LEB128-encoded,
+//! * for data records, the length (LEB128) followed by the bytes;
+//!   for error records, a single byte identifying the [`ErrorKind`].
+
+use embedded_io::{ErrorKind, ErrorType};
+use thiserror::Error;
+
+const MAGIC: &[u8; 6] = b"SDSCAP";
+const VERSION: u8 = 1;
+const MAX_VARINT: usize = 10;
+
+const TAG_TO_SENSOR: u8 = 0;
+const TAG_FROM_SENSOR: u8 = 1;
+const TAG_WRITE_ERROR: u8 = 2;
+const TAG_READ_ERROR: u8 = 3;
+
+// the on-disk code of an ErrorKind is its index in this table
+const ERROR_KINDS: [ErrorKind; 18] = [
+    ErrorKind::Other,
+    ErrorKind::NotFound,
+    ErrorKind::PermissionDenied,
+    ErrorKind::ConnectionRefused,
+    ErrorKind::ConnectionReset,
+    ErrorKind::ConnectionAborted,
+    ErrorKind::NotConnected,
+    ErrorKind::AddrInUse,
+    ErrorKind::AddrNotAvailable,
+    ErrorKind::BrokenPipe,
+    ErrorKind::AlreadyExists,
+    ErrorKind::InvalidInput,
+    ErrorKind::InvalidData,
+    ErrorKind::TimedOut,
+    ErrorKind::Interrupted,
+    ErrorKind::Unsupported,
+    ErrorKind::OutOfMemory,
+    ErrorKind::WriteZero,
+];
+
+fn encode_kind(kind: ErrorKind) -> u8 {
+    ERROR_KINDS
+        .iter()
+        .position(|k| *k == kind)
+        .and_then(|i| u8::try_from(i).ok())
+        .unwrap_or(0)
+}
+
+fn decode_kind(code: u8) -> ErrorKind {
+    ERROR_KINDS
+        .get(usize::from(code))
+        .copied()
+        .unwrap_or(ErrorKind::Other)
+}
+
+const fn encode_varint(mut value: u64, out: &mut [u8; MAX_VARINT]) -> usize {
+    let mut n = 0;
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out[n] = byte;
+            return n + 1;
+        }
+        out[n] = byte | 0x80;
+        n += 1;
+    }
+}
+
+fn decode_varint(data: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in data.iter().take(MAX_VARINT).enumerate() {
+        value |= u64::from(byte & 0x7F) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// A monotonic time source used to timestamp captured traffic.
+pub trait Clock {
+    /// Microseconds elapsed since an arbitrary, fixed origin.
+    fn now_us(&mut self) -> u64;
+}
+
+/// A [`Clock`] based on [`std::time::Instant`].
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct StdClock(std::time::Instant);
+
+#[cfg(feature = "std")]
+impl StdClock {
+    /// Create a clock whose origin is now.
+    #[must_use]
+    pub fn new() -> Self {
+        Self(std::time::Instant::now())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now_us(&mut self) -> u64 {
+        u64::try_from(self.0.elapsed().as_micros()).unwrap_or(u64::MAX)
+    }
+}
+
+/// The direction of captured traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent by the host to the sensor.
+    ToSensor,
+    /// Received by the host from the sensor.
+    FromSensor,
+}
+
+/// What a captured record holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// Bytes that went over the line.
+    Data(&'a [u8]),
+    /// The serial interface returned an error.
+    Error(ErrorKind),
+}
+
+/// A single record of a capture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record<'a> {
+    /// Whether this was a write or a read.
+    pub direction: Direction,
+    /// Microseconds since the recording started.
+    pub timestamp_us: u64,
+    /// The bytes transferred, or the error that occurred.
+    pub event: Event<'a>,
+}
+
+/// A capture could not be decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum CaptureError {
+    /// The data does not start with a supported capture header.
+    #[error("not a capture, or unsupported format version")]
+    Header,
+    /// The record at the given byte offset is malformed or cut off.
+    #[error("capture is corrupt or truncated at offset {0}")]
+    Corrupt(usize),
+}
+
+/// Iterator over the [`Record`]s of a capture.
+#[derive(Debug, Clone)]
+pub struct Records<'a> {
+    data: &'a [u8],
+    pos: usize,
+    time: u64,
+}
+
+impl<'a> Records<'a> {
+    /// Start reading the given capture.
+    ///
+    /// # Errors
+    /// Returns [`CaptureError::Header`] if `capture` does not start with a
+    /// supported header.
+    pub fn new(capture: &'a [u8]) -> Result<Self, CaptureError> {
+        match capture.split_at_checked(MAGIC.len()) {
+            Some((magic, [VERSION, ..])) if magic == MAGIC => Ok(Self {
+                data: capture,
+                pos: MAGIC.len() + 1,
+                time: 0,
+            }),
+            _ => Err(CaptureError::Header),
+        }
+    }
+
+    /// The byte offset of the next record within the capture.
+    #[must_use]
+    pub const fn offset(&self) -> usize {
+        self.pos
+    }
+
+    fn parse(&mut self) -> Option<Record<'a>> {
+        let rest = self.data.get(self.pos..)?;
+        let (&tag, rest) = rest.split_first()?;
+        let (delta, n) = decode_varint(rest)?;
+        let rest = &rest[n..];
+        let mut len = 1 + n;
+
+        let (direction, event) = match tag {
+            TAG_TO_SENSOR | TAG_FROM_SENSOR => {
+                let (size, n) = decode_varint(rest)?;
+                let size = usize::try_from(size).ok()?;
+                let bytes = rest.get(n..n.checked_add(size)?)?;
+                len += n + size;
+                let direction = if tag == TAG_TO_SENSOR {
+                    Direction::ToSensor
+                } else {
+                    Direction::FromSensor
+                };
+                (direction, Event::Data(bytes))
+            }
+            TAG_WRITE_ERROR | TAG_READ_ERROR => {
+                let code = *rest.first()?;
+                len += 1;
+                let direction = if tag == TAG_WRITE_ERROR {
+                    Direction::ToSensor
+                } else {
+                    Direction::FromSensor
+                };
+                (direction, Event::Error(decode_kind(code)))
+            }
+            _ => return None,
+        };
+
+        self.pos += len;
+        self.time = self.time.saturating_add(delta);
+        Some(Record {
+            direction,
+            timestamp_us: self.time,
+            event,
+        })
+    }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<Record<'a>, CaptureError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+
+        let offset = self.pos;
+        let record = self.parse();
+        if record.is_none() {
+            // stop after reporting the corruption once
+            self.pos = self.data.len();
+        }
+        Some(record.ok_or(CaptureError::Corrupt(offset)))
+    }
+}
+
+/// Wraps a serial interface and logs all traffic to a capture sink.
+///
+/// The sink is any blocking [`embedded_io::Write`], e.g. a file or a buffer.
+/// Failing to write to or flush it never disturbs the wrapped interface;
+/// check [`Recorder::sink_ok`] afterwards instead.
+#[derive(Debug)]
+pub struct Recorder<T, S, C> {
+    inner: T,
+    sink: S,
+    clock: C,
+    last: u64,
+    sink_ok: bool,
+}
+
+impl<T, S, C> Recorder<T, S, C>
+where
+    S: embedded_io::Write,
+    C: Clock,
+{
+    /// Start recording the traffic of `inner` to `sink`,
+    /// beginning with the capture header.
+    pub fn new(inner: T, mut sink: S, mut clock: C) -> Self {
+        let sink_ok = sink.write_all(MAGIC).is_ok() && sink.write_all(&[VERSION]).is_ok();
+        let last = clock.now_us();
+
+        Self {
+            inner,
+            sink,
+            clock,
+            last,
+            sink_ok,
+        }
+    }
+
+    /// Whether every record so far made it into the sink,
+    /// and every flush of it succeeded.
+    pub const fn sink_ok(&self) -> bool {
+        self.sink_ok
+    }
+
+    /// Stop recording, returning the serial interface and the sink.
+    pub fn into_inner(self) -> (T, S) {
+        (self.inner, self.sink)
+    }
+
+    fn log(&mut self, tag: u8, payload: &[u8]) {
+        let now = self.clock.now_us();
+        let delta = now.saturating_sub(self.last);
+        self.last = now;
+
+        let mut varint = [0u8; MAX_VARINT];
+        let mut ok = self.sink.write_all(&[tag]).is_ok();
+        let n = encode_varint(delta, &mut varint);
+        ok &= self.sink.write_all(&varint[..n]).is_ok();
+        if matches!(tag, TAG_TO_SENSOR | TAG_FROM_SENSOR) {
+            let n = encode_varint(payload.len() as u64, &mut varint);
+            ok &= self.sink.write_all(&varint[..n]).is_ok();
+        }
+        ok &= self.sink.write_all(payload).is_ok();
+
+        self.sink_ok &= ok;
+    }
+
+    fn flush_sink(&mut self) {
+        self.sink_ok &= self.sink.flush().is_ok();
+    }
+
+    fn log_read<E: embedded_io::Error>(&mut self, buf: &[u8], res: &Result<usize, E>) {
+        match res {
+            Ok(n) if *n > 0 => self.log(TAG_FROM_SENSOR, &buf[..*n]),
+            Ok(_) => (),
+            Err(e) => self.log(TAG_READ_ERROR, &[encode_kind(e.kind())]),
+        }
+    }
+
+    fn log_write<E: embedded_io::Error>(&mut self, buf: &[u8], res: &Result<usize, E>) {
+        match res {
+            Ok(n) if *n > 0 => self.log(TAG_TO_SENSOR, &buf[..*n]),
+            Ok(_) => (),
+            Err(e) => self.log(TAG_WRITE_ERROR, &[encode_kind(e.kind())]),
+        }
+    }
+}
+
+impl<T: ErrorType, S, C> ErrorType for Recorder<T, S, C> {
+    type Error = T::Error;
+}
+
+impl<T, S, C> embedded_io::Read for Recorder<T, S, C>
+where
+    T: embedded_io::Read,
+    S: embedded_io::Write,
+    C: Clock,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let res = self.inner.read(buf);
+        self.log_read(buf, &res);
+        res
+    }
+}
+
+impl<T, S, C> embedded_io::ReadReady for Recorder<T, S, C>
+where
+    T: embedded_io::ReadReady,
+{
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.inner.read_ready()
+    }
+}
+
+impl<T, S, C> embedded_io::Write for Recorder<T, S, C>
+where
+    T: embedded_io::Write,
+    S: embedded_io::Write,
+    C: Clock,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let res = self.inner.write(buf);
+        self.log_write(buf, &res);
+        res
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush_sink();
+        self.inner.flush()
+    }
+}
+
+impl<T, S, C> embedded_io_async::Read for Recorder<T, S, C>
+where
+    T: embedded_io_async::Read,
+    S: embedded_io::Write,
+    C: Clock,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let res = self.inner.read(buf).await;
+        self.log_read(buf, &res);
+        res
+    }
+}
+
+impl<T, S, C> embedded_io_async::Write for Recorder<T, S, C>
+where
+    T: embedded_io_async::Write,
+    S: embedded_io::Write,
+    C: Clock,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        let res = self.inner.write(buf).await;
+        self.log_write(buf, &res);
+        res
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush_sink();
+        self.inner.flush().await
+    }
+}
+
+/// Error type of a [`Replay`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum ReplayError {
+    /// The driver did something other than what was recorded,
+    /// at the record starting at the given byte offset.
+    #[error("driver diverged from the capture at offset {0}")]
+    Diverged(usize),
+    /// The recorded session encountered this serial error here.
+    #[error("recorded serial error: {0:?}")]
+    Recorded(ErrorKind),
+    /// The capture itself is malformed.
+    #[error(transparent)]
+    Capture(#[from] CaptureError),
+}
+
+impl embedded_io::Error for ReplayError {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Recorded(kind) => *kind,
+            Self::Diverged(_) | Self::Capture(_) => ErrorKind::Other,
+        }
+    }
+}
+
+/// A serial interface that plays back a capture made by a [`Recorder`].
+///
+/// Writes must match the recorded outgoing bytes, and reads return the
+/// recorded incoming bytes and errors, in their original order.
+/// Timestamps are ignored, so the replay is fully deterministic.
+/// Reading past the end of the capture returns EOF.
+#[derive(Debug, Clone)]
+pub struct Replay<'a> {
+    records: Records<'a>,
+    current: Option<(usize, Record<'a>)>,
+}
+
+impl<'a> Replay<'a> {
+    /// Play back the given capture.
+    ///
+    /// # Errors
+    /// Returns [`CaptureError::Header`] if `capture` is not a capture.
+    pub fn new(capture: &'a [u8]) -> Result<Self, CaptureError> {
+        Ok(Self {
+            records: Records::new(capture)?,
+            current: None,
+        })
+    }
+
+    /// Whether every recorded byte and error has been replayed.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.current.is_none() && self.records.clone().next().is_none()
+    }
+
+    /// The record currently being replayed, with its offset in the capture.
+    fn current(&mut self) -> Result<Option<(usize, Record<'a>)>, ReplayError> {
+        if self.current.is_none() {
+            let offset = self.records.offset();
+            self.current = self.records.next().transpose()?.map(|r| (offset, r));
+        }
+        Ok(self.current)
+    }
+
+    fn on_read(&mut self, buf: &mut [u8]) -> Result<usize, ReplayError> {
+        let Some((offset, record)) = self.current()? else {
+            return Ok(0);
+        };
+
+        match (record.direction, record.event) {
+            (Direction::ToSensor, _) => Err(ReplayError::Diverged(offset)),
+            (Direction::FromSensor, Event::Error(kind)) => {
+                self.current = None;
+                Err(ReplayError::Recorded(kind))
+            }
+            (Direction::FromSensor, Event::Data(data)) => {
+                let n = buf.len().min(data.len());
+                buf[..n].copy_from_slice(&data[..n]);
+                self.consume(offset, record, &data[n..]);
+                Ok(n)
+            }
+        }
+    }
+
+    fn on_write(&mut self, buf: &[u8]) -> Result<usize, ReplayError> {
+        let Some((offset, record)) = self.current()? else {
+            return Err(ReplayError::Diverged(self.records.offset()));
+        };
+
+        match (record.direction, record.event) {
+            (Direction::FromSensor, _) => Err(ReplayError::Diverged(offset)),
+            (Direction::ToSensor, Event::Error(kind)) => {
+                self.current = None;
+                Err(ReplayError::Recorded(kind))
+            }
+            (Direction::ToSensor, Event::Data(data)) => {
+                let n = buf.len().min(data.len());
+                if buf[..n] != data[..n] {
+                    return Err(ReplayError::Diverged(offset));
+                }
+                self.consume(offset, record, &data[n..]);
+                Ok(n)
+            }
+        }
+    }
+
+    const fn consume(&mut self, offset: usize, record: Record<'a>, rest: &'a [u8]) {
+        self.current = if rest.is_empty() {
+            None
+        } else {
+            Some((
+                offset,
+                Record {
+                    event: Event::Data(rest),
+                    ..record
+                },
+            ))
+        };
+    }
+}
+
+impl ErrorType for Replay<'_> {
+    type Error = ReplayError;
+}
+
+impl embedded_io::Read for Replay<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.on_read(buf)
+    }
+}
+
+impl embedded_io::ReadReady for Replay<'_> {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        // at the end of the capture, reads return EOF right away
+        Ok(self
+            .current()?
+            .is_none_or(|(_, record)| matches!(record.direction, Direction::FromSensor)))
+    }
+}
+
+impl embedded_io::Write for Replay<'_> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.on_write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl embedded_io_async::Read for Replay<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.on_read(buf)
+    }
+}
+
+impl embedded_io_async::Write for Replay<'_> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.on_write(buf)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CaptureError, Clock, Direction, Event, Recorder, Records, Replay, ReplayError};
+    use crate::mock::fixtures::{ID, SDS011, init_script, query, sleep_set};
+    use crate::mock::{Mock, MockDelay, SleepMode};
+    use crate::{Config, SDS011Error};
+    use alloc::vec::Vec;
+    #[cfg(not(feature = "sync"))]
+    use core::task::{Context, Poll, Waker};
+    use embedded_io::ErrorKind;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    struct Ticks(u64);
+
+    impl Clock for Ticks {
+        fn now_us(&mut self) -> u64 {
+            self.0 += 1_500;
+            self.0
+        }
+    }
+
+    #[cfg_attr(feature = "sync", maybe_async::must_be_sync)]
+    async fn record_session() -> Vec<u8> {
+        let mut script = init_script();
+        script.extend([
+            sleep_set(SleepMode::Work, Some(ID)),
+            query(1, 2),
+            query(1236, 2618),
+            sleep_set(SleepMode::Sleep, Some(ID)),
+        ]);
+        let mut serial = Mock::new(script);
+        let mut recorder = Recorder::new(&mut serial, Vec::new(), Ticks(0));
+
+        let sensor = SDS011::new(&mut recorder, Config::default());
+        let mut sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        sensor.measure(&mut MockDelay::new()).await.unwrap();
+
+        assert!(recorder.sink_ok());
+        let (_, capture) = recorder.into_inner();
+        serial.done();
+        capture
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn record_and_replay() {
+        let capture = record_session().await;
+
+        let records: Vec<_> = Records::new(&capture)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records[0].direction, Direction::ToSensor);
+        assert_eq!(records[0].timestamp_us, 1_500);
+        assert!(matches!(records[0].event, Event::Data(d) if d.len() == 19));
+        assert_eq!(records[1].direction, Direction::FromSensor);
+        assert_eq!(records[1].timestamp_us, 3_000);
+
+        let mut replay = Replay::new(&capture).unwrap();
+        let sensor = SDS011::new(&mut replay, Config::default());
+        let mut sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        assert_eq!(sensor.id(), ID);
+        let m = sensor.measure(&mut MockDelay::new()).await.unwrap();
+        assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+        assert!(replay.is_finished());
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn replay_detects_divergence() {
+        let capture = record_session().await;
+
+        // the recorded session measured instead of switching to periodic mode
+        let mut replay = Replay::new(&capture).unwrap();
+        let sensor = SDS011::new(&mut replay, Config::default());
+        let sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        let res = sensor.make_periodic(&mut MockDelay::new(), 1).await;
+        assert!(matches!(
+            res,
+            Err(SDS011Error::WriteError(ReplayError::Diverged(_)))
+        ));
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn replay_recorded_error() {
+        const WAKE: [u8; 19] = [
+            0xAA, 0xB4, 0x06, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0xFF, 0xFF, 0x06, 0xAB,
+        ];
+        let mut capture = Vec::from(*b"SDSCAP\x01");
+        capture.extend([0, 0, 19]);
+        capture.extend(WAKE);
+        // read error after one second: TimedOut
+        capture.extend([3, 0xC0, 0x84, 0x3D, 13]);
+
+        let records: Vec<_> = Records::new(&capture)
+            .unwrap()
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(records[1].timestamp_us, 1_000_000);
+        assert_eq!(records[1].event, Event::Error(ErrorKind::TimedOut));
+
+        let mut replay = Replay::new(&capture).unwrap();
+        let sensor = SDS011::new(&mut replay, Config::default());
+        let res = sensor.init(&mut MockDelay::new()).await;
+        assert!(matches!(
+            res,
+            Err(SDS011Error::ReadError(ReplayError::Recorded(
+                ErrorKind::TimedOut
+            )))
+        ));
+    }
+
+    /// A sink that takes every byte but cannot flush them, like a full disk.
+    struct Unflushable;
+
+    impl embedded_io::ErrorType for Unflushable {
+        type Error = ErrorKind;
+    }
+
+    impl embedded_io::Write for Unflushable {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            Err(ErrorKind::OutOfMemory)
+        }
+    }
+
+    #[test]
+    fn sink_flush_error() {
+        let mut serial = Mock::new([sleep_set(SleepMode::Work, None)]);
+        let mut recorder = Recorder::new(&mut serial, Unflushable, Ticks(0));
+        assert!(recorder.sink_ok());
+        embedded_io::Write::flush(&mut recorder).unwrap();
+        assert!(!recorder.sink_ok());
+    }
+
+    #[test]
+    fn corrupt_captures() {
+        assert_eq!(Replay::new(b"PCAP").unwrap_err(), CaptureError::Header);
+        assert_eq!(
+            Records::new(b"SDSCAP\x02").unwrap_err(),
+            CaptureError::Header
+        );
+
+        let mut records = Records::new(b"SDSCAP\x01\x00\x00\x05\xAA").unwrap();
+        assert_eq!(records.next(), Some(Err(CaptureError::Corrupt(7))));
+        assert_eq!(records.next(), None);
+    }
+
+    #[cfg(not(feature = "sync"))]
+    fn block_on<F: Future>(future: F) -> F::Output {
+        // replays never have to wait
+        let mut future = core::pin::pin!(future);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(out) = future.as_mut().poll(&mut cx) {
+                return out;
+            }
+        }
+    }
+
+    #[cfg_attr(feature = "sync", maybe_async::must_be_sync)]
+    async fn init_with_reply(garbage: &[u8]) -> bool {
+        const WAKE: [u8; 19] = [
+            0xAA, 0xB4, 0x06, 0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0xFF, 0xFF, 0x06, 0xAB,
+        ];
+        let mut capture = Vec::from(*b"SDSCAP\x01");
+        capture.extend([0, 0, 19]);
+        capture.extend(WAKE);
+        capture.extend([1, 0, u8::try_from(garbage.len()).unwrap()]);
+        capture.extend(garbage);
+
+        let mut replay = Replay::new(&capture).unwrap();
+        let sensor = SDS011::new(&mut replay, Config::default());
+        sensor.init(&mut MockDelay::new()).await.is_ok()
+    }
+
+    proptest! {
+        #[test]
+        fn read_any_capture(data in vec(any::<u8>(), 0..64)) {
+            let mut capture = Vec::from(*b"SDSCAP\x01");
+            capture.extend(&data);
+
+            let mut records = Records::new(&capture).unwrap();
+            while let Some(record) = records.next() {
+                if record.is_err() {
+                    prop_assert_eq!(records.next(), None);
+                }
+            }
+
+            let mut replay = Replay::new(&capture).unwrap();
+            let mut buf = [0u8; 8];
+            while embedded_io::Read::read(&mut replay, &mut buf).is_ok_and(|n| n > 0) {}
+        }
+
+        #[test]
+        fn garbage_replies(reply in vec(any::<u8>(), 0..40)) {
+            // the script ends after the first reply, so init can never succeed
+            #[cfg(feature = "sync")]
+            prop_assert!(!init_with_reply(&reply));
+            #[cfg(not(feature = "sync"))]
+            prop_assert!(!block_on(init_with_reply(&reply)));
+        }
+    }
+}
@@ -0,0 +1,259 @@
+//! Finding and annotating frames in raw serial traffic.
+//!
+//! This is the basis of the `sds011-decode` tool, but works just as well
+//! on a device: [`Chunks`] splits a byte stream (e.g. from a logic analyzer
+//! or a [capture](crate::capture)) into frames and the noise between them,
+//! and every [`Frame`] displays as an annotated line:
+//!
+//! ```text
+//! -> AA B4 06 01 00 00 00 00 00 00 00 00 00 00 00 A1 60 08 AB | set sleep mode: sleep | to A160 | checksum ok
+//! <- AA C5 06 01 00 00 A1 60 08 AB | sleep mode is sleep (set) | from A160 | checksum ok
+//! ```
+
+use crate::capture::Direction;
+use crate::message::{Message, RECV_BUF_SIZE, SEND_BUF_SIZE, checksum};
+use core::fmt::{Display, Formatter};
+
+const HEAD: u8 = 0xAA;
+const TAIL: u8 = 0xAB;
+
+/// A complete frame, sent to or received from the sensor.
+///
+/// Frames are recognized by their head and command bytes only,
+/// so they may still carry a wrong checksum, tail or payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a>(&'a [u8]);
+
+impl<'a> Frame<'a> {
+    /// Recognize a frame at the start of `data`, returning it
+    /// if `data` is long enough to hold it.
+    #[must_use]
+    pub fn at_start(data: &'a [u8]) -> Option<Self> {
+        let len = match data {
+            [HEAD, 0xB4, ..] => SEND_BUF_SIZE,
+            [HEAD, 0xC0 | 0xC5, ..] => RECV_BUF_SIZE,
+            _ => return None,
+        };
+        data.get(..len).map(Self)
+    }
+
+    /// The raw bytes of this frame.
+    #[must_use]
+    pub const fn bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Whether the frame was sent by the host or the sensor,
+    /// as indicated by its command byte.
+    #[must_use]
+    pub const fn direction(&self) -> Direction {
+        if self.0.len() == SEND_BUF_SIZE {
+            Direction::ToSensor
+        } else {
+            Direction::FromSensor
+        }
+    }
+
+    /// The checksum computed over the frame's data bytes.
+    #[must_use]
+    pub fn checksum(&self) -> u8 {
+        let len = self.0.len();
+        checksum(&self.0[2..len - 2])
+    }
+
+    /// Whether the checksum byte matches the frame's data.
+    #[must_use]
+    pub fn checksum_ok(&self) -> bool {
+        self.checksum() == self.0[self.0.len() - 2]
+    }
+}
+
+impl Display for Frame<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        let from_sensor = self.direction() == Direction::FromSensor;
+        f.write_str(if from_sensor { "<-" } else { "->" })?;
+        for b in self.0 {
+            write!(f, " {b:02X}")?;
+        }
+
+        f.write_str(" | ")?;
+        match Message::inspect(self.0) {
+            Ok(msg) => {
+                msg.describe(f, from_sensor)?;
+                match (msg.sensor_id, from_sensor) {
+                    (Some(id), true) => write!(f, " | from {id:04X}")?,
+                    (Some(id), false) => write!(f, " | to {id:04X}")?,
+                    (None, _) => f.write_str(" | to all sensors")?,
+                }
+            }
+            Err(e) => write!(f, "invalid: {e}")?,
+        }
+
+        let len = self.0.len();
+        if self.checksum_ok() {
+            f.write_str(" | checksum ok")?;
+        } else {
+            write!(
+                f,
+                " | checksum BAD (computed {:02X}, frame has {:02X})",
+                self.checksum(),
+                self.0[len - 2]
+            )?;
+        }
+        if self.0[len - 1] != TAIL {
+            write!(f, " | tail {:02X}", self.0[len - 1])?;
+        }
+        Ok(())
+    }
+}
+
+/// A piece of a byte stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chunk<'a> {
+    /// A complete frame.
+    Frame(Frame<'a>),
+    /// Bytes that do not belong to any complete frame.
+    Noise(&'a [u8]),
+}
+
+/// Splits a byte stream into [`Chunk`]s.
+#[derive(Debug, Clone)]
+pub struct Chunks<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Chunks<'a> {
+    /// Split the given stream.
+    #[must_use]
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Chunk<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.data.is_empty() {
+            return None;
+        }
+
+        let start = (0..self.data.len())
+            .find(|&i| Frame::at_start(&self.data[i..]).is_some())
+            .unwrap_or(self.data.len());
+
+        if start > 0 {
+            let (noise, rest) = self.data.split_at(start);
+            self.data = rest;
+            return Some(Chunk::Noise(noise));
+        }
+
+        let frame = Frame::at_start(self.data)?;
+        self.data = &self.data[frame.0.len()..];
+        Some(Chunk::Frame(frame))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Chunk, Chunks, Frame};
+    use crate::capture::Direction;
+    use alloc::string::ToString;
+    use alloc::vec::Vec;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    const SLEEP: [u8; 19] = [
+        0xAA, 0xB4, 0x06, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xA1, 0x60, 0x08, 0xAB,
+    ];
+    const DATA: [u8; 10] = [0xAA, 0xC0, 0xD4, 0x04, 0x3A, 0x0A, 0xA1, 0x60, 0x1D, 0xAB];
+
+    #[test]
+    fn split_stream() {
+        let mut stream = Vec::from([0x00, 0xAA]);
+        stream.extend(SLEEP);
+        stream.extend(DATA);
+        stream.extend(&DATA[..4]);
+
+        let chunks: Vec<_> = Chunks::new(&stream).collect();
+        assert_eq!(
+            chunks,
+            [
+                Chunk::Noise(&[0x00, 0xAA]),
+                Chunk::Frame(Frame(&SLEEP)),
+                Chunk::Frame(Frame(&DATA)),
+                Chunk::Noise(&DATA[..4]),
+            ]
+        );
+    }
+
+    #[test]
+    fn annotate_query() {
+        let frame = Frame::at_start(&SLEEP).unwrap();
+        assert_eq!(frame.direction(), Direction::ToSensor);
+        assert_eq!(
+            frame.to_string(),
+            "-> AA B4 06 01 00 00 00 00 00 00 00 00 00 00 00 A1 60 08 AB \
+             | set sleep mode: sleep | to A160 | checksum ok"
+        );
+    }
+
+    #[test]
+    fn annotate_reply() {
+        let frame = Frame::at_start(&DATA).unwrap();
+        assert_eq!(frame.direction(), Direction::FromSensor);
+        assert_eq!(
+            frame.to_string(),
+            "<- AA C0 D4 04 3A 0A A1 60 1D AB \
+             | measurement: PM2.5: 123.6 µg/m3, PM10: 261.8 µg/m3 | from A160 | checksum ok"
+        );
+    }
+
+    #[test]
+    fn annotate_damage() {
+        // sleep reply quirk (tail 0xFF) with a flipped bit in the checksum
+        let data = [0xAA, 0xC5, 0x06, 0x01, 0x00, 0x00, 0xA1, 0x60, 0x09, 0xFF];
+        let frame = Frame::at_start(&data).unwrap();
+        assert!(!frame.checksum_ok());
+        assert_eq!(
+            frame.to_string(),
+            "<- AA C5 06 01 00 00 A1 60 09 FF | sleep mode is sleep (set) | from A160 \
+             | checksum BAD (computed 08, frame has 09) | tail FF"
+        );
+
+        let data = [0xAA, 0xC5, 0x03, 0x00, 0x00, 0x00, 0xA1, 0x60, 0x04, 0xAB];
+        assert!(
+            Frame::at_start(&data)
+                .unwrap()
+                .to_string()
+                .contains("| invalid: 3 is an unknown subcommand |")
+        );
+    }
+
+    proptest! {
+        #[test]
+        // bias towards frame starts so that frames actually show up
+        fn split_anything(
+            data in vec(prop_oneof![Just(0xAA), Just(0xB4), Just(0xC0), any::<u8>()], 0..64)
+        ) {
+            let mut joined = Vec::<u8>::new();
+            let mut noise = false;
+            for chunk in Chunks::new(&data) {
+                match chunk {
+                    Chunk::Frame(frame) => {
+                        prop_assert!(!frame.to_string().is_empty());
+                        joined.extend(frame.bytes());
+                        noise = false;
+                    }
+                    Chunk::Noise(bytes) => {
+                        prop_assert!(!noise, "noise is never split");
+                        joined.extend(bytes);
+                        noise = true;
+                    }
+                }
+            }
+            prop_assert_eq!(joined, data);
+        }
+    }
+}
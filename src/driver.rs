@@ -0,0 +1,879 @@
+// The driver, shared by the `blocking` and `asynch` modules.
+// Both include this file after importing their flavor of the embedded-io
+// and embedded-hal traits and of the `maybe_async` attribute.
+
+use crate::message::{Message, RECV_BUF_SIZE, SEND_BUF_SIZE};
+use crate::observer::{NoObserver, Observer, Transition};
+use crate::sensor_state::{Periodic, Polling, SensorState, Uninitialized};
+use crate::split::Split;
+use crate::{
+    Config, FirmwareVersion, Kind, Measurement, NoPower, Quirks, Reporting, ReportingMode,
+    SDS011Error, Sleep, SleepMode, Source, State, WorkingPeriod,
+};
+use core::marker::PhantomData;
+use embedded_hal::digital::{OutputPin, PinState};
+use embedded_io::ReadReady;
+
+/// What the driver was in the middle of, should its future be dropped.
+#[derive(Clone, Copy)]
+enum InFlight {
+    Idle,
+    /// `sent` bytes of `frame`, encoding `command`, are on the wire.
+    Command {
+        command: Kind,
+        frame: [u8; SEND_BUF_SIZE],
+        sent: usize,
+    },
+    /// The sensor still owes (the rest of) its reply to a command.
+    Reply(Kind),
+}
+
+/// The main struct.
+/// Wraps around a serial interface that implements embedded-io(-async).
+///
+/// Calling `new()` will give you an uninitialized struct
+/// (or `new_split()`, if your HAL hands out separate RX and TX halves).
+/// You need to call `init()` on it to get a sensor that can be polled.
+///
+/// Created with `new_with_power()`, the driver also controls the sensor's
+/// supply through a power switch `P`, e.g. a MOSFET gate.
+/// An [`Observer`] `O` attached with `set_observer()` is told what the
+/// driver does.
+pub struct SDS011<RW, S: SensorState, P = NoPower, O = NoObserver> {
+    serial: RW,
+    config: Config,
+    power: Option<P>,
+    observer: O,
+    /// failed measurements in a row
+    failures: u8,
+    /// as configured (0xFFFF: all sensors) until initialized
+    sensor_id: u16,
+    /// all zeros until initialized
+    firmware: FirmwareVersion,
+    /// deviations from the protocol tolerated so far
+    quirks: Quirks,
+    in_flight: InFlight,
+    /// the command whose reply was cut short, which may still arrive
+    resync: Option<Kind>,
+    /// a polled measurement is under way (the sensor is awake)
+    measuring: bool,
+    /// the working period in minutes as last set (0: continuous)
+    period: u8,
+    _state: PhantomData<S>,
+}
+
+impl<RW, S, P, O> SDS011<RW, S, P, O>
+where
+    RW: Read + Write,
+    S: SensorState,
+    P: OutputPin,
+    O: Observer,
+{
+    /// Attach `observer`, replacing the current one (if any).
+    /// Pass `&mut observer` to keep access to it.
+    pub fn set_observer<T: Observer>(self, observer: T) -> SDS011<RW, S, P, T> {
+        SDS011 {
+            serial: self.serial,
+            config: self.config,
+            power: self.power,
+            observer,
+            failures: self.failures,
+            sensor_id: self.sensor_id,
+            firmware: self.firmware,
+            quirks: self.quirks,
+            in_flight: self.in_flight,
+            resync: self.resync,
+            measuring: self.measuring,
+            period: self.period,
+            _state: PhantomData,
+        }
+    }
+
+    /// The attached observer.
+    pub const fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Deviations from the protocol this sensor has shown so far,
+    /// within the tolerated [`Quirks`] of its [`Config`].
+    /// A non-empty set hints at a clone (except for
+    /// [`Quirk::SleepReplyTail`](crate::Quirk::SleepReplyTail), which genuine sensors show as well).
+    pub const fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    /// Receive the reply to `command`. After a reply was cut short, what is
+    /// left of it is skipped first: the bytes up to the next frame head,
+    /// frames that cannot be decoded, and frames that do not answer
+    /// `command`, such as the late reply itself.
+    #[maybe_async]
+    async fn get_reply(&mut self, command: Kind) -> Result<Message, SDS011Error<RW::Error>> {
+        let mut buf = [0u8; RECV_BUF_SIZE];
+        // bytes at the start of `buf` kept from a frame that could not be decoded
+        let mut kept = 0;
+        loop {
+            if self.resync.is_some() {
+                while kept == 0 {
+                    self.receive(&mut buf[..1], command).await?;
+                    kept = usize::from(buf[0] == 0xAA);
+                }
+            }
+            self.receive(&mut buf[kept..], command).await?;
+            kept = 0;
+            self.observer.frame_received(&buf);
+
+            let expected_id = (self.sensor_id != 0xFFFF).then_some(self.sensor_id);
+            match (
+                Message::parse_reply_with(&buf, expected_id, self.config.quirks),
+                self.resync,
+            ) {
+                (Ok((msg, _)), Some(cut)) if !msg.kind.answers(&command) => {
+                    // once the late reply is skipped, the line is in sync again
+                    if msg.kind.answers(&cut) {
+                        self.resync = None;
+                    }
+                }
+                (Ok((msg, seen)), cut) => {
+                    // the late reply looks the same and may still follow
+                    self.resync = cut.filter(|cut| msg.kind.answers(cut));
+                    self.quirks = self.quirks.union(seen);
+                    return Ok(msg);
+                }
+                (Err(_), Some(_)) => {
+                    // a frame head among the bytes left of the cut reply,
+                    // scan on from the next one
+                    if let Some(next) = buf[1..].iter().position(|&b| b == 0xAA) {
+                        buf.copy_within(next + 1.., 0);
+                        kept = RECV_BUF_SIZE - next - 1;
+                    }
+                }
+                (Err(source), None) => {
+                    self.observer.parse_error(&source, &buf);
+                    return Err(SDS011Error::ParseError {
+                        source,
+                        raw: buf,
+                        command,
+                        sensor_id: self.sensor_id,
+                    });
+                }
+            }
+        }
+    }
+
+    /// Fill `buf`, keeping track of whether the reply to `command` is under way.
+    #[maybe_async]
+    async fn receive(
+        &mut self,
+        buf: &mut [u8],
+        command: Kind,
+    ) -> Result<(), SDS011Error<RW::Error>> {
+        let mut filled = 0;
+        while let Some(rest @ [_, ..]) = buf.get_mut(filled..) {
+            match self.serial.read(rest).await {
+                Ok(0) => {
+                    self.in_flight = InFlight::Idle;
+                    return Err(SDS011Error::UnexpectedEof);
+                }
+                Ok(n) => {
+                    filled += n;
+                    self.in_flight = InFlight::Reply(command);
+                }
+                Err(e) => {
+                    self.in_flight = InFlight::Idle;
+                    return Err(SDS011Error::ReadError(e));
+                }
+            }
+        }
+        self.in_flight = InFlight::Idle;
+        Ok(())
+    }
+
+    #[maybe_async]
+    async fn send_message(&mut self, kind: Kind) -> Result<(), SDS011Error<RW::Error>> {
+        let msg = Message::new(kind, Some(self.sensor_id));
+        self.send_frame(kind, msg.create_query(), 0).await
+    }
+
+    /// Send `frame`, encoding `command`, from byte `sent` on,
+    /// keeping track of the progress.
+    #[maybe_async]
+    async fn send_frame(
+        &mut self,
+        command: Kind,
+        frame: [u8; SEND_BUF_SIZE],
+        mut sent: usize,
+    ) -> Result<(), SDS011Error<RW::Error>> {
+        self.in_flight = InFlight::Command {
+            command,
+            frame,
+            sent,
+        };
+        while let Some(rest @ [_, ..]) = frame.get(sent..) {
+            match self.serial.write(rest).await {
+                // the serial interface broke its contract
+                Ok(0) => {
+                    self.in_flight = InFlight::Idle;
+                    return Err(SDS011Error::UnexpectedEof);
+                }
+                Ok(n) => {
+                    sent += n;
+                    self.in_flight = InFlight::Command {
+                        command,
+                        frame,
+                        sent,
+                    };
+                }
+                Err(e) => {
+                    self.in_flight = InFlight::Idle;
+                    return Err(SDS011Error::WriteError(e));
+                }
+            }
+        }
+
+        // make sure the command is on the wire before awaiting the reply
+        if let Err(e) = self.serial.flush().await {
+            self.in_flight = InFlight::Idle;
+            return Err(SDS011Error::WriteError(e));
+        }
+        self.observer.frame_sent(&frame);
+        self.in_flight = InFlight::Reply(command);
+        Ok(())
+    }
+
+    /// Clean up after an operation whose future was dropped, e.g. by a
+    /// timeout: finish sending its command, give up on its reply,
+    /// and put the sensor back to rest if it was measuring.
+    #[maybe_async]
+    async fn settle(&mut self) -> Result<(), SDS011Error<RW::Error>> {
+        self.abandon().await?;
+        if self.measuring {
+            self.rest().await?;
+            self.measuring = false;
+        }
+        Ok(())
+    }
+
+    /// Finish sending a command cut short, without waiting for the sensor.
+    /// Whatever it still sends of the reply is skipped by `get_reply()`;
+    /// the sensor may never send it, e.g. because it hung.
+    #[maybe_async]
+    async fn abandon(&mut self) -> Result<(), SDS011Error<RW::Error>> {
+        if let InFlight::Command {
+            command,
+            frame,
+            sent,
+        } = self.in_flight
+        {
+            self.send_frame(command, frame, sent).await?;
+        }
+        if let InFlight::Reply(command) = self.in_flight {
+            self.in_flight = InFlight::Idle;
+            self.resync = Some(command);
+        }
+        Ok(())
+    }
+
+    /// Whether an operation was cut short, see `settle()`.
+    const fn interrupted(&self) -> bool {
+        self.measuring || !matches!(self.in_flight, InFlight::Idle)
+    }
+
+    /// Send `command` and return the kind of the sensor's reply.
+    #[maybe_async]
+    async fn transact(&mut self, command: Kind) -> Result<Kind, SDS011Error<RW::Error>> {
+        self.send_message(command).await?;
+        Ok(self.get_reply(command).await?.kind)
+    }
+
+    const fn unexpected(&self, expected: Kind, received: Kind) -> SDS011Error<RW::Error> {
+        SDS011Error::UnexpectedType {
+            expected,
+            received,
+            sensor_id: self.sensor_id,
+        }
+    }
+
+    const fn refused(&self, command: Kind, received: Kind) -> SDS011Error<RW::Error> {
+        SDS011Error::OperationFailed {
+            command,
+            received,
+            sensor_id: self.sensor_id,
+        }
+    }
+
+    #[maybe_async]
+    async fn read_sensor(&mut self, query: bool) -> Result<Measurement, SDS011Error<RW::Error>> {
+        // in periodic mode, data arrives without being asked for
+        let command = Kind::Query(None);
+        let reply = if query {
+            self.transact(command).await?
+        } else {
+            self.get_reply(command).await?.kind
+        };
+
+        match reply {
+            Kind::Query(Some(data)) => {
+                let mut quality = data.quality();
+                quality.source = Some(if query {
+                    Source::Query
+                } else {
+                    Source::Periodic
+                });
+                Ok(data.with_quality(quality))
+            }
+            reply => Err(self.unexpected(command, reply)),
+        }
+    }
+
+    #[maybe_async]
+    async fn get_firmware(&mut self) -> Result<(u16, FirmwareVersion), SDS011Error<RW::Error>> {
+        let command = Kind::FWVersion(None);
+        self.send_message(command).await?;
+
+        let reply = self.get_reply(command).await?;
+        match (reply.kind, reply.sensor_id) {
+            (Kind::FWVersion(Some(data)), Some(id)) => Ok((id, data)),
+            (reply, _) => Err(self.unexpected(command, reply)),
+        }
+    }
+
+    #[maybe_async]
+    async fn _get_runmode(&mut self) -> Result<ReportingMode, SDS011Error<RW::Error>> {
+        let command = Kind::ReportingMode(Reporting::new_query());
+
+        match self.transact(command).await? {
+            Kind::ReportingMode(data) => Ok(data.mode()),
+            reply => Err(self.unexpected(command, reply)),
+        }
+    }
+
+    #[maybe_async]
+    async fn set_runmode(&mut self, mode: ReportingMode) -> Result<(), SDS011Error<RW::Error>> {
+        let command = Kind::ReportingMode(Reporting::new_set(mode));
+
+        match self.transact(command).await? {
+            Kind::ReportingMode(r) if r.mode() == mode => {
+                self.observer.transition(Transition::Reporting(mode));
+                Ok(())
+            }
+            reply @ Kind::ReportingMode(_) => Err(self.refused(command, reply)),
+            reply => Err(self.unexpected(command, reply)),
+        }
+    }
+
+    #[maybe_async]
+    async fn _get_period(&mut self) -> Result<u8, SDS011Error<RW::Error>> {
+        let command = Kind::WorkingPeriod(WorkingPeriod::new_query());
+
+        match self.transact(command).await? {
+            Kind::WorkingPeriod(data) => Ok(data.period()),
+            reply => Err(self.unexpected(command, reply)),
+        }
+    }
+
+    #[maybe_async]
+    async fn set_period(&mut self, minutes: u8) -> Result<(), SDS011Error<RW::Error>> {
+        let command = Kind::WorkingPeriod(WorkingPeriod::new_set(minutes));
+
+        match self.transact(command).await? {
+            Kind::WorkingPeriod(data) if data.period() == minutes => {
+                self.period = minutes;
+                self.observer.transition(Transition::Period(minutes));
+                Ok(())
+            }
+            reply @ Kind::WorkingPeriod(_) => Err(self.refused(command, reply)),
+            reply => Err(self.unexpected(command, reply)),
+        }
+    }
+
+    #[maybe_async]
+    async fn _get_sleep(&mut self) -> Result<SleepMode, SDS011Error<RW::Error>> {
+        let command = Kind::Sleep(Sleep::new_query());
+
+        match self.transact(command).await? {
+            Kind::Sleep(data) => Ok(data.sleep_mode()),
+            reply => Err(self.unexpected(command, reply)),
+        }
+    }
+
+    #[maybe_async]
+    async fn set_sleep(&mut self, mode: SleepMode) -> Result<(), SDS011Error<RW::Error>> {
+        let command = Kind::Sleep(Sleep::new_set(mode));
+
+        match self.transact(command).await? {
+            Kind::Sleep(s) if s.sleep_mode() == mode => {
+                self.observer.transition(match mode {
+                    SleepMode::Sleep => Transition::Slept,
+                    SleepMode::Work => Transition::Woke,
+                });
+                Ok(())
+            }
+            reply @ Kind::Sleep(_) => Err(self.refused(command, reply)),
+            reply => Err(self.unexpected(command, reply)),
+        }
+    }
+
+    #[maybe_async]
+    async fn sleep(&mut self) -> Result<(), SDS011Error<RW::Error>> {
+        self.set_sleep(SleepMode::Sleep).await
+    }
+
+    #[maybe_async]
+    async fn wake(&mut self) -> Result<(), SDS011Error<RW::Error>> {
+        self.set_sleep(SleepMode::Work).await
+    }
+
+    const fn powered_off_when_idle(&self) -> bool {
+        self.power.is_some() && self.config.power_off_idle
+    }
+
+    fn set_power(&mut self, state: PinState) -> Result<(), SDS011Error<RW::Error>> {
+        let Some(pin) = self.power.as_mut() else {
+            return Ok(());
+        };
+        pin.set_state(state).map_err(|_| SDS011Error::PowerError)?;
+        self.observer.transition(match state {
+            PinState::Low => Transition::PoweredOff,
+            PinState::High => Transition::PoweredOn,
+        });
+        Ok(())
+    }
+
+    /// Switch the sensor on and wait for it to boot.
+    #[maybe_async]
+    async fn power_on<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), SDS011Error<RW::Error>> {
+        self.set_power(PinState::High)?;
+        delay.delay_ms(self.config.power_delay).await;
+        Ok(())
+    }
+
+    /// Put the sensor to rest: switched off or sent to sleep, as configured.
+    #[maybe_async]
+    async fn rest(&mut self) -> Result<(), SDS011Error<RW::Error>> {
+        if self.powered_off_when_idle() {
+            self.set_power(PinState::Low)
+        } else {
+            self.sleep().await
+        }
+    }
+
+    /// Leave the state this sensor is in, e.g. after a transition.
+    fn retype<T: SensorState>(self) -> SDS011<RW, T, P, O> {
+        SDS011 {
+            serial: self.serial,
+            config: self.config,
+            power: self.power,
+            observer: self.observer,
+            failures: self.failures,
+            sensor_id: self.sensor_id,
+            firmware: self.firmware,
+            quirks: self.quirks,
+            in_flight: self.in_flight,
+            resync: self.resync,
+            measuring: self.measuring,
+            period: self.period,
+            _state: PhantomData,
+        }
+    }
+
+    /// Bring the sensor into polling mode and send it to rest.
+    #[maybe_async]
+    async fn initialize<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<(), SDS011Error<RW::Error>> {
+        self.settle().await?;
+        self.set_up(delay).await
+    }
+
+    /// `initialize()` a sensor not in the middle of anything.
+    #[maybe_async]
+    async fn set_up<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), SDS011Error<RW::Error>> {
+        if self.power.is_some() {
+            self.power_on(delay).await?;
+        }
+        // sleep a short moment to make sure the sensor is ready
+        delay.delay_ms(self.config.sleep_delay).await;
+        self.wake().await?;
+
+        self.set_runmode(ReportingMode::Query).await?;
+
+        // while we're at it, read the firmware version once
+        let (id, firmware) = self.get_firmware().await?;
+        self.rest().await?;
+
+        self.sensor_id = id;
+        self.firmware = firmware;
+        self.observer.transition(Transition::State(State::Polling));
+        Ok(())
+    }
+
+    /// Take a measurement in polling mode.
+    #[maybe_async]
+    async fn poll<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<Measurement, SDS011Error<RW::Error>> {
+        if self.interrupted() {
+            // cut short, e.g. by a timeout: how a sensor that stopped answering
+            // looks to the caller
+            self.failures = self.failures.saturating_add(1);
+        }
+        let res = self.poll_or_power_cycle(delay).await;
+        self.measuring = false;
+        if res.is_err() && self.powered_off_when_idle() {
+            // the failure matters more than whether switching off worked
+            _ = self.set_power(PinState::Low);
+        }
+        let data = res?;
+        self.observer.raw_measurement(&data);
+        let data = self.calibrate(data);
+        self.observer.measurement(&data);
+        Ok(data)
+    }
+
+    /// Wait for the next measurement in periodic mode.
+    #[maybe_async]
+    async fn report(&mut self) -> Result<Measurement, SDS011Error<RW::Error>> {
+        self.settle().await?;
+        let data = self.read_sensor(false).await?;
+        // with a working period, the fan runs for 30 s before each report
+        let mut quality = data.quality();
+        quality.warm_up = (self.period > 0).then_some(30_000);
+        let data = data.with_quality(quality);
+        self.observer.raw_measurement(&data);
+        let data = self.calibrate(data);
+        self.observer.measurement(&data);
+        Ok(data)
+    }
+
+    /// Apply the configured calibration, if it is this sensor's.
+    fn calibrate(&self, data: Measurement) -> Measurement {
+        match &self.config.calibration {
+            Some(calibration) if calibration.sensor_id() == self.sensor_id => {
+                calibration.apply(&data)
+            }
+            _ => data,
+        }
+    }
+
+    #[maybe_async]
+    async fn poll_or_power_cycle<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<Measurement, SDS011Error<RW::Error>> {
+        if !self.power_cycle_due() {
+            let err = match self.try_poll(delay).await {
+                Ok(res) => {
+                    self.failures = 0;
+                    return Ok(res);
+                }
+                Err(e) => e,
+            };
+            self.failures = self.failures.saturating_add(1);
+            if !self.power_cycle_due() {
+                return Err(err);
+            }
+        }
+        // without waiting for a sensor that may have hung
+        self.abandon().await?;
+        self.measuring = true;
+        self.power_cycle(delay).await?;
+        let res = self.try_measure(delay).await;
+        self.failures = u8::from(res.is_err());
+        res
+    }
+
+    /// Whether enough measurements failed in a row to power cycle the sensor.
+    const fn power_cycle_due(&self) -> bool {
+        let threshold = self.config.power_cycle_after;
+        self.power.is_some() && threshold > 0 && self.failures >= threshold
+    }
+
+    /// Clean up after an interrupted operation, then measure.
+    #[maybe_async]
+    async fn try_poll<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<Measurement, SDS011Error<RW::Error>> {
+        self.settle().await?;
+        self.measuring = true;
+        self.try_measure(delay).await
+    }
+
+    #[maybe_async]
+    async fn try_measure<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<Measurement, SDS011Error<RW::Error>> {
+        if self.powered_off_when_idle() {
+            self.power_on(delay).await?;
+            // a sensor that was switched off may have forgotten its mode
+            self.set_runmode(ReportingMode::Query).await?;
+        }
+        // sleep a short moment to make sure the sensor is ready
+        delay.delay_ms(self.config.sleep_delay).await;
+        self.wake().await?;
+
+        // do a dummy measurement, spin for a few secs, then do real measurement
+        _ = self.read_sensor(true).await?;
+        delay.delay_ms(self.config.measure_delay).await;
+        let res = self.read_sensor(true).await?;
+        self.rest().await?;
+
+        let mut quality = res.quality();
+        quality.warm_up = Some(self.config.measure_delay);
+        Ok(res.with_quality(quality))
+    }
+
+    /// Switch the sensor off and on again and re-initialize it like `init()`.
+    #[maybe_async]
+    async fn power_cycle<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<(), SDS011Error<RW::Error>> {
+        self.set_power(PinState::Low)?;
+        delay.delay_ms(self.config.power_delay).await;
+        self.set_up(delay).await
+    }
+
+    /// Switch a sensor in polling mode to periodic mode.
+    #[maybe_async]
+    async fn start_periodic<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        minutes: u8,
+    ) -> Result<(), SDS011Error<RW::Error>> {
+        if minutes > 30 {
+            return Err(SDS011Error::Invalid);
+        }
+
+        let res = self.switch_to_periodic(delay, minutes).await;
+        if res.is_err() {
+            // don't leave the fan running while still polling;
+            // the failure matters more than whether this worked
+            _ = self.rest().await;
+        }
+        res
+    }
+
+    /// `start_periodic()`, without cleaning up after a failure.
+    #[maybe_async]
+    async fn switch_to_periodic<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        minutes: u8,
+    ) -> Result<(), SDS011Error<RW::Error>> {
+        if self.powered_off_when_idle() {
+            self.power_on(delay).await?;
+        }
+        // sleep a short moment to make sure the sensor is ready
+        delay.delay_ms(self.config.sleep_delay).await;
+        self.wake().await?;
+
+        self.set_period(minutes).await?;
+        self.set_runmode(ReportingMode::Active).await?;
+
+        self.observer.transition(Transition::State(State::Periodic));
+        Ok(())
+    }
+}
+
+impl<RW, S, P, O> SDS011<RW, S, P, O>
+where
+    RW: Read + Write + ReadReady,
+    S: SensorState,
+    P: OutputPin,
+    O: Observer,
+{
+    /// Discard whatever arrived without being read, e.g. the rest of a
+    /// garbled reply, returning how many bytes that were.
+    #[maybe_async]
+    async fn drain(&mut self) -> Result<usize, SDS011Error<RW::Error>> {
+        let mut buf = [0u8; RECV_BUF_SIZE];
+        let mut discarded = 0;
+
+        while self.serial.read_ready().map_err(SDS011Error::ReadError)? {
+            match self.serial.read(&mut buf).await {
+                Ok(0) => break,
+                Ok(n) => discarded += n,
+                Err(e) => return Err(SDS011Error::ReadError(e)),
+            }
+        }
+        Ok(discarded)
+    }
+}
+
+impl<RW> SDS011<RW, Uninitialized>
+where
+    RW: Read + Write,
+{
+    /// Create a new sensor instance, consuming the serial interface.
+    /// The returned instance needs to be initialized before use.
+    pub const fn new(serial: RW, config: Config) -> Self {
+        Self::with_power(serial, None, config)
+    }
+}
+
+impl<RW, P> SDS011<RW, Uninitialized, P>
+where
+    RW: Read + Write,
+    P: OutputPin,
+{
+    /// Create a new sensor instance whose supply is switched by `power`
+    /// (high = on), consuming the serial interface and the pin.
+    /// With a power switch, the driver power cycles a sensor that stopped
+    /// answering and can switch it off between measurements (see [`Config`]).
+    /// The returned instance needs to be initialized before use.
+    pub const fn new_with_power(serial: RW, power: P, config: Config) -> Self {
+        Self::with_power(serial, Some(power), config)
+    }
+
+    const fn with_power(serial: RW, power: Option<P>, config: Config) -> Self {
+        let sensor_id = config.sensor_id;
+        Self {
+            serial,
+            config,
+            power,
+            observer: NoObserver,
+            failures: 0,
+            sensor_id,
+            firmware: FirmwareVersion::new(0, 0, 0),
+            quirks: Quirks::STRICT,
+            in_flight: InFlight::Idle,
+            resync: None,
+            measuring: false,
+            period: 0,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl<RW, P, O> SDS011<RW, Uninitialized, P, O>
+where
+    RW: Read + Write,
+    P: OutputPin,
+    O: Observer,
+{
+    /// Put the sensor in a well-defined state (sleeping in polling mode).
+    ///
+    /// # Errors
+    /// This communicates with the sensor over serial and may fail with any
+    /// [`SDS011Error`].
+    #[maybe_async]
+    pub async fn init<D: DelayNs>(
+        mut self,
+        delay: &mut D,
+    ) -> Result<SDS011<RW, Polling, P, O>, SDS011Error<RW::Error>> {
+        self.initialize(delay).await?;
+        Ok(self.retype())
+    }
+}
+
+impl<R, W> SDS011<Split<R, W>, Uninitialized>
+where
+    R: Read,
+    W: Write,
+{
+    /// Create a new sensor instance from separate receive and transmit
+    /// halves of a serial interface, e.g. a HAL's `UartRx` and `UartTx`.
+    /// The returned instance needs to be initialized before use.
+    pub const fn new_split(rx: R, tx: W, config: Config) -> Self {
+        Self::new(Split::new(rx, tx), config)
+    }
+}
+
+impl<RW, P, O> SDS011<RW, Periodic, P, O>
+where
+    RW: Read + Write,
+    P: OutputPin,
+    O: Observer,
+{
+    /// In this state, the sensor will wake up periodically (as configured),
+    /// wait 30 seconds, send a measurement over serial, and go back to sleep.
+    /// This method waits until data is available before returning.
+    ///
+    /// # Errors
+    /// This communicates with the sensor over serial and may fail with any
+    /// [`SDS011Error`].
+    ///
+    /// # Cancel Safety
+    /// If the future is dropped before it completes, the next call skips
+    /// whatever arrives of the partially received measurement.
+    #[maybe_async]
+    pub async fn measure(&mut self) -> Result<Measurement, SDS011Error<RW::Error>> {
+        self.report().await
+    }
+
+    /// Get the sensor's ID.
+    pub const fn id(&self) -> u16 {
+        self.sensor_id
+    }
+
+    /// Get the sensor's firmware version.
+    pub const fn version(&self) -> FirmwareVersion {
+        self.firmware
+    }
+}
+
+impl<RW, P, O> SDS011<RW, Polling, P, O>
+where
+    RW: Read + Write,
+    P: OutputPin,
+    O: Observer,
+{
+    /// In this state, measurements are triggered by calling this function.
+    /// The sensor is woken up and the fan spins for the configured delay time,
+    /// after which we send the measurement query and put it back to sleep.
+    ///
+    /// With a power switch, the sensor is switched on and off instead if
+    /// configured so, and after repeated failures it is power cycled and
+    /// re-initialized before one more attempt.
+    ///
+    /// # Cancel Safety
+    /// If the future is dropped before it completes, e.g. by a timeout while
+    /// the fan spins, the next call finishes sending an interrupted command
+    /// and puts the sensor back to sleep before measuring again. It does not
+    /// wait for the rest of an interrupted reply, which a hung sensor never
+    /// sends, but skips whatever arrives of it.
+    ///
+    /// # Errors
+    /// This communicates with the sensor over serial and may fail with any
+    /// [`SDS011Error`].
+    #[maybe_async]
+    pub async fn measure<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<Measurement, SDS011Error<RW::Error>> {
+        self.poll(delay).await
+    }
+
+    /// Set the sensor into periodic measurement mode, in which it performs
+    /// a measurement every 0-30 `minutes`.
+    /// If > 0, the sensor will go to sleep between measurements.
+    /// Should this fail, the sensor is put back to rest as far as it still
+    /// answers.
+    ///
+    /// # Errors
+    /// This communicates with the sensor over serial and may fail with any
+    /// [`SDS011Error`].
+    #[maybe_async]
+    pub async fn make_periodic<D: DelayNs>(
+        mut self,
+        delay: &mut D,
+        minutes: u8,
+    ) -> Result<SDS011<RW, Periodic, P, O>, SDS011Error<RW::Error>> {
+        self.start_periodic(delay, minutes).await?;
+        Ok(self.retype())
+    }
+
+    /// Get the sensor's ID.
+    pub const fn id(&self) -> u16 {
+        self.sensor_id
+    }
+
+    /// Get the sensor's firmware version.
+    pub const fn version(&self) -> FirmwareVersion {
+        self.firmware
+    }
+}
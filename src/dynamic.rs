@@ -0,0 +1,138 @@
+// The state-erased driver, shared by the `blocking` and `asynch` modules
+// like the typed driver it wraps.
+
+/// A sensor whose [state](State) is checked at runtime instead of encoded
+/// in its type, e.g. to keep sensors in different modes in one collection.
+///
+/// Offers the operations of the typed [`SDS011`] on `&mut self`; calling one
+/// the current state does not allow fails with
+/// [`SDS011Error::InvalidState`]. A failed transition keeps the old state,
+/// putting the sensor back to rest as far as it still answers.
+/// Convert from a typed sensor with `From`, and back with `TryFrom`,
+/// which hands the sensor back if it is in another state.
+pub struct DynSDS011<RW, P = NoPower, O = NoObserver> {
+    // the sensor's actual state is tracked by `state`
+    sensor: SDS011<RW, Uninitialized, P, O>,
+    state: State,
+}
+
+impl<RW, P, O> DynSDS011<RW, P, O>
+where
+    RW: Read + Write,
+    P: OutputPin,
+    O: Observer,
+{
+    /// The sensor's current state.
+    pub const fn state(&self) -> State {
+        self.state
+    }
+
+    fn require(&self, state: State) -> Result<(), SDS011Error<RW::Error>> {
+        if self.state == state {
+            Ok(())
+        } else {
+            Err(SDS011Error::InvalidState(self.state))
+        }
+    }
+
+    /// Put an uninitialized sensor in polling mode, see `SDS011::init()`.
+    ///
+    /// # Errors
+    /// [`SDS011Error::InvalidState`] if the sensor is not uninitialized,
+    /// or any other [`SDS011Error`] while communicating with it.
+    #[maybe_async]
+    pub async fn init<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), SDS011Error<RW::Error>> {
+        self.require(State::Uninitialized)?;
+        self.sensor.initialize(delay).await?;
+        self.state = State::Polling;
+        Ok(())
+    }
+
+    /// Switch a polling sensor to periodic mode,
+    /// see `SDS011::make_periodic()`.
+    ///
+    /// # Errors
+    /// [`SDS011Error::InvalidState`] if the sensor is not polling,
+    /// or any other [`SDS011Error`] while communicating with it.
+    #[maybe_async]
+    pub async fn make_periodic<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+        minutes: u8,
+    ) -> Result<(), SDS011Error<RW::Error>> {
+        self.require(State::Polling)?;
+        self.sensor.start_periodic(delay, minutes).await?;
+        self.state = State::Periodic;
+        Ok(())
+    }
+
+    /// Take a measurement: trigger one if polling,
+    /// or wait for the next one if periodic (then `delay` is not used).
+    ///
+    /// # Cancel Safety
+    /// Like the typed `measure()` methods.
+    ///
+    /// # Errors
+    /// [`SDS011Error::InvalidState`] if the sensor is uninitialized,
+    /// or any other [`SDS011Error`] while communicating with it.
+    #[maybe_async]
+    pub async fn measure<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<Measurement, SDS011Error<RW::Error>> {
+        match self.state {
+            State::Uninitialized => Err(SDS011Error::InvalidState(self.state)),
+            State::Polling => self.sensor.poll(delay).await,
+            State::Periodic => self.sensor.report().await,
+        }
+    }
+
+    /// Get the sensor's ID (0xFFFF, or as configured, until initialized).
+    pub const fn id(&self) -> u16 {
+        self.sensor.sensor_id
+    }
+
+    /// Get the sensor's firmware version (all zeros until initialized).
+    pub const fn version(&self) -> FirmwareVersion {
+        self.sensor.firmware
+    }
+
+    /// Deviations from the protocol this sensor has shown so far,
+    /// see `SDS011::quirks()`.
+    pub const fn quirks(&self) -> Quirks {
+        self.sensor.quirks
+    }
+}
+
+impl<RW, S, P, O> From<SDS011<RW, S, P, O>> for DynSDS011<RW, P, O>
+where
+    RW: Read + Write,
+    S: SensorState,
+    P: OutputPin,
+    O: Observer,
+{
+    fn from(sensor: SDS011<RW, S, P, O>) -> Self {
+        Self {
+            sensor: sensor.retype(),
+            state: S::STATE,
+        }
+    }
+}
+
+impl<RW, S, P, O> TryFrom<DynSDS011<RW, P, O>> for SDS011<RW, S, P, O>
+where
+    RW: Read + Write,
+    S: SensorState,
+    P: OutputPin,
+    O: Observer,
+{
+    type Error = DynSDS011<RW, P, O>;
+
+    fn try_from(sensor: DynSDS011<RW, P, O>) -> Result<Self, Self::Error> {
+        if sensor.state == S::STATE {
+            Ok(sensor.sensor.retype())
+        } else {
+            Err(sensor)
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Smoothing measurements.
+//!
+//! The sensor's readings are jumpy, especially at low concentrations.
+//! A [`Filter`] takes each new measurement and returns a smoothed one;
+//! it works with any source of measurements, such as periodic reports
+//! or repeated polling:
+//!
+//! ```ignore
+//! let mut filter = (Median::<5>::new(), Ema::new(64));
+//! loop {
+//!     let smooth = filter.update(sensor.measure().await?);
+//!     println!("{smooth}");
+//! }
+//! ```
+//!
+//! * [`Ema`], an exponential moving average, follows trends with a lag,
+//! * [`Median`], a sliding median, removes single spikes without blurring
+//!   steps,
+//! * [`Kalman`], a one-dimensional Kalman filter, trades responsiveness
+//!   for smoothness given how noisy the readings and the air are.
+//!
+//! A pair of filters is a filter that applies both in turn. [`Ema`] and
+//! [`Median`] use integer math only. Filtered measurements keep the quality
+//! of their input, marked as [filtered](crate::Quality::filtered) if a filter
+//! changed their values. Whether they are
+//! [at the limit](crate::Quality::at_limit) is judged by the new values.
+
+use crate::Measurement;
+use crate::humidity::tenths;
+use crate::stats::{self, Ring};
+
+/// A smoothing filter for measurements.
+pub trait Filter {
+    /// Add `measurement` and return the filtered measurement.
+    fn update(&mut self, measurement: Measurement) -> Measurement;
+
+    /// Forget earlier measurements, e.g. after the sensor slept.
+    fn reset(&mut self);
+}
+
+/// The output of a filter for `input`.
+pub(crate) const fn output(input: &Measurement, pm25: u16, pm10: u16) -> Measurement {
+    let output = Measurement::new(pm25, pm10);
+    let mut quality = input.quality();
+    quality.at_limit = output.quality().at_limit;
+    quality.filtered |= pm25 != input.pm25() || pm10 != input.pm10();
+    output.with_quality(quality)
+}
+
+impl<T: Filter + ?Sized> Filter for &mut T {
+    fn update(&mut self, measurement: Measurement) -> Measurement {
+        (**self).update(measurement)
+    }
+
+    fn reset(&mut self) {
+        (**self).reset();
+    }
+}
+
+impl<A: Filter, B: Filter> Filter for (A, B) {
+    fn update(&mut self, measurement: Measurement) -> Measurement {
+        let measurement = self.0.update(measurement);
+        self.1.update(measurement)
+    }
+
+    fn reset(&mut self) {
+        self.0.reset();
+        self.1.reset();
+    }
+}
+
+/// An exponential moving average.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ema {
+    weight: u8,
+    /// both values in 1/256 of 0.1 µg/m3
+    state: Option<[u32; 2]>,
+}
+
+impl Ema {
+    /// Give each new measurement a weight of `weight / 256`.
+    /// Averaging over about `n` measurements takes a weight of `512 / (n + 1)`,
+    /// e.g. 64 for the last 7. A weight of 0 is taken as 1.
+    #[must_use]
+    pub const fn new(weight: u8) -> Self {
+        Self {
+            weight: if weight == 0 { 1 } else { weight },
+            state: None,
+        }
+    }
+}
+
+impl Filter for Ema {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn update(&mut self, measurement: Measurement) -> Measurement {
+        let values = [measurement.pm25(), measurement.pm10()].map(|v| u32::from(v) << 8);
+        let state = self.state.map_or(values, |state| {
+            let weight = i64::from(self.weight);
+            [0, 1].map(|i| {
+                let delta = i64::from(values[i]) - i64::from(state[i]);
+                // the result lies between the old state and the new value
+                (i64::from(state[i]) + ((delta * weight) >> 8)) as u32
+            })
+        });
+        self.state = Some(state);
+        // between u16 values as well
+        let [pm25, pm10] = state.map(|s| ((s + 128) >> 8) as u16);
+        output(&measurement, pm25, pm10)
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+/// The median of the last `N` measurements, of each pollutant separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Median<const N: usize> {
+    /// PM2.5 and PM10 of each measurement
+    ring: Ring<[u16; 2], N>,
+}
+
+impl<const N: usize> Default for Median<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Median<N> {
+    /// Start without any measurements.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { ring: Ring::new() }
+    }
+
+    fn median(&self, pollutant: usize) -> Option<u16> {
+        let mut values = [0; N];
+        let mut len = 0;
+        for (slot, m) in values.iter_mut().zip(self.ring.iter()) {
+            *slot = m[pollutant];
+            len += 1;
+        }
+        stats::median(values.get_mut(..len)?)
+    }
+}
+
+impl<const N: usize> Filter for Median<N> {
+    fn update(&mut self, measurement: Measurement) -> Measurement {
+        self.ring.push([measurement.pm25(), measurement.pm10()]);
+        match (self.median(0), self.median(1)) {
+            (Some(pm25), Some(pm10)) => output(&measurement, pm25, pm10),
+            // N == 0
+            _ => measurement,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+/// A one-dimensional Kalman filter of each pollutant,
+/// modelling the concentration as a random walk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Kalman {
+    process_noise: f32,
+    measurement_noise: f32,
+    /// estimates in µg/m3 and their variances
+    state: Option<[(f32, f32); 2]>,
+}
+
+impl Kalman {
+    /// Start with how much the concentration varies between measurements
+    /// (`process_noise`) and how noisy the readings are
+    /// (`measurement_noise`), both as variances in (µg/m3)².
+    /// The smaller their ratio, the smoother and slower the output.
+    #[must_use]
+    pub const fn new(process_noise: f32, measurement_noise: f32) -> Self {
+        Self {
+            process_noise,
+            measurement_noise,
+            state: None,
+        }
+    }
+
+    /// The variances of the current PM2.5 and PM10 estimates, in (µg/m3)².
+    #[must_use]
+    pub fn variance(&self) -> Option<(f32, f32)> {
+        self.state.map(|[pm25, pm10]| (pm25.1, pm10.1))
+    }
+}
+
+impl Filter for Kalman {
+    fn update(&mut self, measurement: Measurement) -> Measurement {
+        let values = [measurement.pm25(), measurement.pm10()].map(|v| f32::from(v) / 10.0);
+        let state = self.state.map_or_else(
+            || values.map(|z| (z, self.measurement_noise)),
+            |state| {
+                [0, 1].map(|i| {
+                    let (x, p) = state[i];
+                    let p = p + self.process_noise;
+                    let gain = p / (p + self.measurement_noise);
+                    (x + gain * (values[i] - x), p * (1.0 - gain))
+                })
+            },
+        );
+        self.state = Some(state);
+        output(&measurement, tenths(state[0].0), tenths(state[1].0))
+    }
+
+    fn reset(&mut self) {
+        self.state = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ema, Filter, Kalman, Median};
+    use crate::Measurement;
+
+    const fn m(pm: u16) -> Measurement {
+        Measurement::new(pm, pm)
+    }
+
+    fn run(filter: &mut impl Filter, input: &[u16]) -> alloc::vec::Vec<u16> {
+        input
+            .iter()
+            .map(|&pm| filter.update(m(pm)).pm25())
+            .collect()
+    }
+
+    #[test]
+    fn ema() {
+        let mut ema = Ema::new(128);
+        assert_eq!(
+            run(&mut ema, &[100, 200, 200, 200, 0]),
+            [100, 150, 175, 188, 94]
+        );
+        ema.reset();
+        assert_eq!(run(&mut ema, &[10]), [10]);
+
+        // small weights still converge
+        let mut slow = Ema::new(0);
+        slow.update(m(0));
+        let out = run(&mut slow, &[65_535; 3_000]);
+        assert!(out.windows(2).all(|w| w[0] <= w[1]));
+        assert!(*out.last().unwrap() > 65_000);
+    }
+
+    #[test]
+    fn median() {
+        let mut median = Median::<3>::new();
+        // a spike disappears, a step stays
+        assert_eq!(
+            run(&mut median, &[10, 12, 500, 11, 40, 40, 40]),
+            [10, 11, 12, 12, 40, 40, 40]
+        );
+        // pollutants separately
+        let mixed = median.update(Measurement::new(0, 100));
+        assert_eq!(mixed, Measurement::new(40, 40));
+        assert!(mixed.quality().filtered && !mixed.quality().at_limit);
+        // unchanged
+        let same = median.update(Measurement::new(40, 40));
+        assert!(!same.quality().filtered);
+
+        median.reset();
+        assert_eq!(run(&mut median, &[7]), [7]);
+        let mut none = Median::<0>::new();
+        assert_eq!(run(&mut none, &[7]), [7]);
+    }
+
+    #[test]
+    fn kalman() {
+        let mut kalman = Kalman::new(0.1, 4.0);
+        assert_eq!(kalman.variance(), None);
+        let out = run(&mut kalman, &[100, 140, 60, 140, 60, 140, 60]);
+        assert_eq!(out[0], 100);
+        assert!(out.iter().all(|pm| (80..=120).contains(pm)));
+        let (pm25, _) = kalman.variance().unwrap();
+        assert!(pm25 < 4.0);
+
+        // trusting the readings, the filter follows them
+        let mut fast = Kalman::new(100.0, 0.01);
+        assert_eq!(run(&mut fast, &[100, 300]), [100, 300]);
+        fast.reset();
+        assert_eq!(fast.variance(), None);
+    }
+
+    #[test]
+    fn chain() {
+        let mut filter = (Median::<3>::new(), Ema::new(128));
+        assert_eq!(
+            run(&mut filter, &[100, 100, 1_000, 100]),
+            [100, 100, 100, 100]
+        );
+        let mut by_ref = &mut filter;
+        by_ref.reset();
+        assert_eq!(run(&mut by_ref, &[20]), [20]);
+    }
+}
@@ -0,0 +1,500 @@
+//! Fitting calibrations from co-location data (requires `std`).
+//!
+//! This is the basis of the `sds011-fit` tool. Given time-aligned readings
+//! of a sensor and a reference monitor, [`fit()`] finds the correction of one
+//! pollutant with one of several [`Method`]s and reports how well it fits;
+//! [`calibration()`] turns the fits of both pollutants into a
+//! [`Calibration`] the driver can load.
+//!
+//! # CSV format
+//! [`Dataset::from_csv()`] reads comma-separated values with a header row.
+//! The columns `pm25`, `pm10` (the sensor's readings), `ref_pm25` and
+//! `ref_pm10` (the reference's) are required, `rh` (relative humidity in
+//! percent) is optional, and other columns, such as timestamps, are ignored.
+//! Concentrations are in µg/m3. Empty cells mark missing values; rows
+//! missing a value of a pollutant are left out of that pollutant's fit.
+//!
+//! ```text
+//! time,pm25,pm10,ref_pm25,ref_pm10,rh
+//! 2024-03-01T10:00,12.3,20.1,9.8,17.5,64
+//! 2024-03-01T11:00,14.0,22.9,,19.0,66
+//! ```
+
+use crate::aqi::Pollutant;
+use crate::calibration::{Calibration, Curve};
+use crate::humidity;
+use crate::stats::median;
+use std::vec::Vec;
+use thiserror::Error;
+
+/// Errors while reading or fitting co-location data.
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum FitError {
+    /// The CSV data lacks a required column.
+    #[error("missing column '{0}'")]
+    MissingColumn(&'static str),
+    /// A value in the CSV data is not a finite number.
+    #[error("invalid value in line {0}")]
+    Value(usize),
+    /// There are too few samples to fit the model
+    /// (for the humidity-aware fit: samples with humidity).
+    #[error("too few samples")]
+    TooFewSamples,
+    /// The samples do not determine the model, e.g. because the
+    /// sensor's readings are all the same.
+    #[error("the samples do not determine the model")]
+    Degenerate,
+}
+
+/// A reading of one pollutant and the reference's value at the same time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// The sensor's reading, in µg/m3.
+    pub raw: f64,
+    /// The reference's value, in µg/m3.
+    pub reference: f64,
+    /// The relative humidity, in percent, if known.
+    pub humidity: Option<f64>,
+}
+
+/// The samples of both pollutants.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Dataset {
+    pm25: Vec<Sample>,
+    pm10: Vec<Sample>,
+}
+
+impl Dataset {
+    /// Read co-location data in the [CSV format](self#csv-format).
+    ///
+    /// # Errors
+    /// [`FitError::MissingColumn`] or [`FitError::Value`]
+    /// if the data does not follow the format.
+    pub fn from_csv(text: &str) -> Result<Self, FitError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty());
+        let header: Vec<&str> = lines
+            .next()
+            .map(|(_, l)| l.split(',').map(str::trim).collect())
+            .unwrap_or_default();
+        let column = |name| {
+            header
+                .iter()
+                .position(|h| *h == name)
+                .ok_or(FitError::MissingColumn(name))
+        };
+        let columns = [
+            column("pm25")?,
+            column("ref_pm25")?,
+            column("pm10")?,
+            column("ref_pm10")?,
+        ];
+        let rh = column("rh").ok();
+
+        let mut data = Self::default();
+        for (i, line) in lines {
+            let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+            let value = |col: usize| match cells.get(col).copied().unwrap_or_default() {
+                "" => Ok(None),
+                cell => match cell.parse::<f64>() {
+                    // NaN or infinity would spoil the whole fit
+                    Ok(value) if value.is_finite() => Ok(Some(value)),
+                    _ => Err(FitError::Value(i + 1)),
+                },
+            };
+            let [pm25, ref_pm25, pm10, ref_pm10] = columns.map(value);
+            let humidity = rh.map(value).transpose()?.flatten();
+
+            for (samples, raw, reference) in [
+                (&mut data.pm25, pm25?, ref_pm25?),
+                (&mut data.pm10, pm10?, ref_pm10?),
+            ] {
+                if let (Some(raw), Some(reference)) = (raw, reference) {
+                    samples.push(Sample {
+                        raw,
+                        reference,
+                        humidity,
+                    });
+                }
+            }
+        }
+        Ok(data)
+    }
+
+    /// The samples of `pollutant`.
+    #[must_use]
+    pub fn samples(&self, pollutant: Pollutant) -> &[Sample] {
+        match pollutant {
+            Pollutant::Pm25 => &self.pm25,
+            Pollutant::Pm10 => &self.pm10,
+        }
+    }
+}
+
+/// How to fit a correction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Ordinary least squares: `reference = slope * raw + offset`.
+    Linear,
+    /// The same line, as the median of the slopes between all pairs of
+    /// samples (Theil-Sen), which is robust against outliers.
+    /// Takes time and memory quadratic in the number of samples.
+    TheilSen,
+    /// Least squares including the relative humidity:
+    /// `reference = slope * raw + humidity * rh + offset`.
+    /// Only uses samples with humidity.
+    Humidity,
+}
+
+/// A fitted correction.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Model {
+    /// A correction of the reading alone.
+    Curve(Curve),
+    /// A correction that needs the relative humidity,
+    /// see [`humidity::Model::Linear`].
+    Humidity(humidity::Linear),
+}
+
+impl Model {
+    /// The corrected value of `sample`, in µg/m3.
+    #[must_use]
+    pub fn predict(&self, sample: &Sample) -> f64 {
+        match self {
+            Self::Curve(Curve::Linear { slope, offset }) => {
+                f64::from(*slope) * sample.raw + f64::from(*offset)
+            }
+            Self::Curve(Curve::Quadratic { a, b, c }) => {
+                (f64::from(*a) * sample.raw + f64::from(*b)) * sample.raw + f64::from(*c)
+            }
+            Self::Curve(curve @ Curve::Piecewise(_)) => {
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                let raw = (sample.raw * 10.0).round().clamp(0.0, f64::from(u16::MAX)) as u16;
+                f64::from(curve.apply(raw)) / 10.0
+            }
+            Self::Humidity(linear) => {
+                f64::from(linear.slope) * sample.raw
+                    + f64::from(linear.humidity) * sample.humidity.unwrap_or_default()
+                    + f64::from(linear.offset)
+            }
+        }
+    }
+}
+
+/// A fitted correction and how well it fits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct Fit {
+    /// The correction.
+    pub model: Model,
+    /// The coefficient of determination of the corrected values.
+    pub r2: f64,
+    /// The root mean square error of the corrected values, in µg/m3.
+    pub rmse: f64,
+    /// The mean error of the corrected values, in µg/m3.
+    pub bias: f64,
+    /// How many samples the fit is based on.
+    pub samples: usize,
+}
+
+/// Fit a correction of one pollutant's `samples`.
+///
+/// # Errors
+/// [`FitError::TooFewSamples`] if there are less than 2 samples
+/// (3 with humidity for [`Method::Humidity`]), and
+/// [`FitError::Degenerate`] if they do not determine the correction.
+#[allow(clippy::cast_possible_truncation)]
+pub fn fit(samples: &[Sample], method: Method) -> Result<Fit, FitError> {
+    let samples: Vec<Sample> = match method {
+        Method::Humidity => samples
+            .iter()
+            .filter(|s| s.humidity.is_some())
+            .copied()
+            .collect(),
+        Method::Linear | Method::TheilSen => samples.to_vec(),
+    };
+    let needed = if method == Method::Humidity { 3 } else { 2 };
+    if samples.len() < needed {
+        return Err(FitError::TooFewSamples);
+    }
+
+    let model = match method {
+        Method::Linear => {
+            let (slope, offset) = least_squares(&samples)?;
+            Model::Curve(Curve::Linear {
+                slope: slope as f32,
+                offset: offset as f32,
+            })
+        }
+        Method::TheilSen => {
+            let (slope, offset) = theil_sen(&samples)?;
+            Model::Curve(Curve::Linear {
+                slope: slope as f32,
+                offset: offset as f32,
+            })
+        }
+        Method::Humidity => {
+            let [slope, humidity, offset] = with_humidity(&samples)?;
+            Model::Humidity(humidity::Linear {
+                slope: slope as f32,
+                humidity: humidity as f32,
+                temperature: 0.0,
+                offset: offset as f32,
+            })
+        }
+    };
+    Ok(evaluate(model, &samples))
+}
+
+/// The calibration of sensor `sensor_id` from the fits of both pollutants,
+/// or `None` if one of them needs the humidity.
+#[must_use]
+pub const fn calibration(sensor_id: u16, pm25: &Fit, pm10: &Fit) -> Option<Calibration> {
+    match (pm25.model, pm10.model) {
+        (Model::Curve(pm25), Model::Curve(pm10)) => Some(Calibration::new(sensor_id, pm25, pm10)),
+        _ => None,
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn mean(values: impl ExactSizeIterator<Item = f64>) -> f64 {
+    let n = values.len() as f64;
+    values.sum::<f64>() / n
+}
+
+fn least_squares(samples: &[Sample]) -> Result<(f64, f64), FitError> {
+    let x = mean(samples.iter().map(|s| s.raw));
+    let y = mean(samples.iter().map(|s| s.reference));
+    let sxx: f64 = samples.iter().map(|s| (s.raw - x).powi(2)).sum();
+    let sxy: f64 = samples
+        .iter()
+        .map(|s| (s.raw - x) * (s.reference - y))
+        .sum();
+    if sxx == 0.0 {
+        return Err(FitError::Degenerate);
+    }
+    let slope = sxy / sxx;
+    Ok((slope, y - slope * x))
+}
+
+fn theil_sen(samples: &[Sample]) -> Result<(f64, f64), FitError> {
+    let mut slopes: Vec<f64> = samples
+        .iter()
+        .enumerate()
+        .flat_map(|(i, a)| samples.iter().skip(i + 1).map(move |b| (a, b)))
+        .filter(|(a, b)| a.raw.total_cmp(&b.raw).is_ne())
+        .map(|(a, b)| (b.reference - a.reference) / (b.raw - a.raw))
+        .collect();
+    let slope = median(&mut slopes).ok_or(FitError::Degenerate)?;
+    let mut offsets: Vec<f64> = samples
+        .iter()
+        .map(|s| s.reference - slope * s.raw)
+        .collect();
+    let offset = median(&mut offsets).ok_or(FitError::Degenerate)?;
+    Ok((slope, offset))
+}
+
+/// Least squares of `reference` on `raw`, `humidity` and 1,
+/// solving the normal equations by Gaussian elimination.
+fn with_humidity(samples: &[Sample]) -> Result<[f64; 3], FitError> {
+    let mut m = [[0.0; 4]; 3];
+    for s in samples {
+        let row = [s.raw, s.humidity.unwrap_or_default(), 1.0];
+        for (i, a) in row.iter().enumerate() {
+            for (j, b) in row.iter().enumerate() {
+                m[i][j] += a * b;
+            }
+            m[i][3] += a * s.reference;
+        }
+    }
+
+    for col in 0..3 {
+        let pivot = (col..3)
+            .max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))
+            .unwrap_or(col);
+        m.swap(col, pivot);
+        if m[col][col].abs() < 1e-9 {
+            return Err(FitError::Degenerate);
+        }
+        let pivot = m[col];
+        for (i, row) in m.iter_mut().enumerate() {
+            if i != col {
+                let factor = row[col] / pivot[col];
+                for (x, p) in row.iter_mut().zip(pivot) {
+                    *x -= factor * p;
+                }
+            }
+        }
+    }
+    Ok([0, 1, 2].map(|i| m[i][3] / m[i][i]))
+}
+
+fn evaluate(model: Model, samples: &[Sample]) -> Fit {
+    let errors = || samples.iter().map(move |s| model.predict(s) - s.reference);
+    let reference = mean(samples.iter().map(|s| s.reference));
+    let total: f64 = samples
+        .iter()
+        .map(|s| (s.reference - reference).powi(2))
+        .sum();
+    let residual: f64 = errors().map(|e| e * e).sum();
+
+    Fit {
+        model,
+        r2: if total == 0.0 {
+            0.0
+        } else {
+            1.0 - residual / total
+        },
+        rmse: mean(errors().map(|e| e * e)).sqrt(),
+        bias: mean(errors()),
+        samples: samples.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Dataset, FitError, Method, Model, Sample, calibration, fit};
+    use crate::aqi::Pollutant;
+    use crate::calibration::Curve;
+    use std::vec::Vec;
+
+    fn samples(f: impl Fn(f64, f64) -> f64) -> Vec<Sample> {
+        (0..20)
+            .map(|i| {
+                let (raw, rh) = (f64::from(i) * 2.5, f64::from(i * 7 % 60) + 30.0);
+                Sample {
+                    raw,
+                    reference: f(raw, rh),
+                    humidity: Some(rh),
+                }
+            })
+            .collect()
+    }
+
+    fn line(model: Model) -> (f32, f32) {
+        match model {
+            Model::Curve(Curve::Linear { slope, offset }) => (slope, offset),
+            _ => panic!("not a line: {model:?}"),
+        }
+    }
+
+    #[test]
+    fn linear() {
+        let data = samples(|raw, _| 0.8 * raw - 1.5);
+        let f = fit(&data, Method::Linear).unwrap();
+        let (slope, offset) = line(f.model);
+        assert!((slope - 0.8).abs() < 1e-5 && (offset + 1.5).abs() < 1e-5);
+        assert!((f.r2 - 1.0).abs() < 1e-9 && f.rmse < 1e-5 && f.bias.abs() < 1e-5);
+        assert_eq!(f.samples, 20);
+
+        // alternating errors of ±1
+        let mut noisy = data;
+        for (i, s) in noisy.iter_mut().enumerate() {
+            s.reference += if i % 2 == 0 { 1.0 } else { -1.0 };
+        }
+        let f = fit(&noisy, Method::Linear).unwrap();
+        assert!(f.r2 < 1.0 && f.r2 > 0.9);
+        assert!((f.rmse - 1.0).abs() < 0.05 && f.bias.abs() < 1e-5);
+    }
+
+    #[test]
+    fn theil_sen() {
+        let mut data = samples(|raw, _| 0.5 * raw + 2.0);
+        data[3].reference = 500.0;
+        data[11].reference = -40.0;
+
+        let (slope, offset) = line(fit(&data, Method::TheilSen).unwrap().model);
+        assert!((slope - 0.5).abs() < 1e-5 && (offset - 2.0).abs() < 1e-5);
+        // least squares is pulled off by the outliers
+        let (slope, _) = line(fit(&data, Method::Linear).unwrap().model);
+        assert!((slope - 0.5).abs() > 0.1);
+    }
+
+    #[test]
+    fn humidity() {
+        let mut data = samples(|raw, rh| 0.6 * raw - 0.1 * rh + 4.0);
+        data.push(Sample {
+            raw: 1_000.0,
+            reference: 0.0,
+            humidity: None,
+        });
+
+        let f = fit(&data, Method::Humidity).unwrap();
+        let Model::Humidity(linear) = f.model else {
+            panic!("not a humidity model: {:?}", f.model);
+        };
+        assert!((linear.slope - 0.6).abs() < 1e-4);
+        assert!((linear.humidity + 0.1).abs() < 1e-4);
+        assert!((linear.offset - 4.0).abs() < 1e-3);
+        assert_eq!(f.samples, 20);
+        assert!(calibration(0xA160, &f, &f).is_none());
+    }
+
+    #[test]
+    fn errors() {
+        let data = samples(|raw, _| raw);
+        assert_eq!(
+            fit(&data[..1], Method::Linear),
+            Err(FitError::TooFewSamples)
+        );
+        assert_eq!(
+            fit(&data[..2], Method::Humidity),
+            Err(FitError::TooFewSamples)
+        );
+
+        let flat = [data[4]; 5];
+        for method in [Method::Linear, Method::TheilSen, Method::Humidity] {
+            assert_eq!(fit(&flat, method), Err(FitError::Degenerate));
+        }
+    }
+
+    #[test]
+    fn csv() {
+        let text = "time, pm25,pm10,ref_pm10,ref_pm25,rh\n\
+                    10:00,12.5,20,18,10,64\n\
+                    \n\
+                    11:00,14,22,19,,\n\
+                    12:00,16,24,21,13.5,70\n";
+        let data = Dataset::from_csv(text).unwrap();
+        let pm25 = data.samples(Pollutant::Pm25);
+        assert_eq!(pm25.len(), 2);
+        assert_eq!(
+            pm25[0],
+            Sample {
+                raw: 12.5,
+                reference: 10.0,
+                humidity: Some(64.0)
+            }
+        );
+        let pm10 = data.samples(Pollutant::Pm10);
+        assert_eq!(pm10.len(), 3);
+        assert_eq!(pm10[1].humidity, None);
+
+        let f25 = fit(pm25, Method::Linear).unwrap();
+        let f10 = fit(pm10, Method::TheilSen).unwrap();
+        let cal = calibration(0xA160, &f25, &f10).unwrap();
+        assert_eq!(cal.sensor_id(), 0xA160);
+        assert_eq!(
+            cal.pm10(),
+            &Curve::Linear {
+                slope: 0.75,
+                offset: 3.0
+            }
+        );
+
+        assert_eq!(
+            Dataset::from_csv("pm25,pm10,ref_pm25\n"),
+            Err(FitError::MissingColumn("ref_pm10"))
+        );
+        assert_eq!(
+            Dataset::from_csv("pm25,pm10,ref_pm25,ref_pm10\n1,2,3,4\n1,x,3,4\n"),
+            Err(FitError::Value(3))
+        );
+        for cell in ["NaN", "inf", "-inf"] {
+            let text = std::format!("pm25,pm10,ref_pm25,ref_pm10\n1,2,3,4\n1,2,{cell},4\n");
+            assert_eq!(Dataset::from_csv(&text), Err(FitError::Value(3)));
+        }
+    }
+}
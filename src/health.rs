@@ -0,0 +1,511 @@
+//! Spotting failing sensors.
+//!
+//! A [`Monitor`] watches the readings and reply times of a sensor for signs
+//! of hardware problems: readings stuck at one value or at zero, readings
+//! saturated at the upper end of the range, PM2.5 above PM10, and replies
+//! that take ever longer. Installed as the driver's
+//! [`Observer`], it times every reply and judges the measurements the driver
+//! returns as the sensor reported them, before any calibration:
+//!
+//! ```ignore
+//! let mut sensor = SDS011::new(serial, config).set_observer(Monitor::new(clock));
+//! // ...
+//! let report = sensor.observer().report();
+//! if !report.is_healthy() {
+//!     alert(&report);
+//! }
+//! ```
+//!
+//! Measurements from other sources can be checked with [`Monitor::check()`].
+
+use crate::Measurement;
+use crate::capture::Clock;
+use crate::message::MAX_VALUE;
+use crate::observer::Observer;
+use core::fmt::{self, Display, Formatter};
+use core::time::Duration;
+
+/// How many replies make up the latency baseline.
+const BASELINE: u64 = 8;
+/// Latency growth below this is never reported, in microseconds.
+const LATENCY_MARGIN: u64 = 10_000;
+
+/// A likely hardware problem.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Problem {
+    /// The readings have not changed for `duration`,
+    /// e.g. because the fan or laser stopped.
+    Stuck {
+        /// The reading repeated.
+        measurement: Measurement,
+        /// How long it has been repeated.
+        duration: Duration,
+    },
+    /// The readings have been zero for `duration`,
+    /// e.g. because the laser or photodiode failed.
+    Zero {
+        /// How long the readings have been zero.
+        duration: Duration,
+    },
+    /// The last `readings` readings were at the upper end of the range,
+    /// e.g. because the optics are dirty.
+    Saturated {
+        /// How many readings in a row.
+        readings: u32,
+    },
+    /// In the last `readings` readings, PM2.5 exceeded PM10,
+    /// which the sensor does not report when intact.
+    Inverted {
+        /// How many readings in a row.
+        readings: u32,
+    },
+    /// The sensor replies much slower than it used to.
+    SlowReplies {
+        /// The mean latency of the first replies.
+        baseline: Duration,
+        /// The recent (smoothed) latency.
+        recent: Duration,
+    },
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Stuck {
+                measurement,
+                duration,
+            } => write!(
+                f,
+                "readings stuck at {measurement} for {} s",
+                duration.as_secs()
+            ),
+            Self::Zero { duration } => {
+                write!(f, "readings zero for {} s", duration.as_secs())
+            }
+            Self::Saturated { readings } => {
+                write!(f, "{readings} readings in a row at 999.9 µg/m3")
+            }
+            Self::Inverted { readings } => {
+                write!(f, "{readings} readings in a row with PM2.5 above PM10")
+            }
+            Self::SlowReplies { baseline, recent } => write!(
+                f,
+                "replies take {} ms, up from {} ms",
+                recent.as_millis(),
+                baseline.as_millis()
+            ),
+        }
+    }
+}
+
+/// The problems found by a [`Monitor`], at most one of each kind.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Report {
+    problems: [Option<Problem>; 4],
+}
+
+impl Report {
+    /// Whether no problems were found.
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.problems.iter().all(Option::is_none)
+    }
+
+    /// The problems found.
+    pub fn problems(&self) -> impl Iterator<Item = &Problem> {
+        self.problems.iter().flatten()
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.is_healthy() {
+            return f.write_str("healthy");
+        }
+        for (i, problem) in self.problems().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{problem}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Watches a sensor for hardware problems.
+#[derive(Debug, Clone)]
+pub struct Monitor<C> {
+    clock: C,
+    stuck_after: u64,
+    zero_after: u64,
+    run_length: u32,
+    latency_factor: u8,
+    /// the last reading, since when it repeats, and the time of the latest
+    last: Option<(Measurement, u64, u64)>,
+    saturated: u32,
+    inverted: u32,
+    sent_at: Option<u64>,
+    replies: u64,
+    /// the mean of the first replies, in µs
+    baseline: u64,
+    /// the smoothed latency of recent replies, in µs
+    recent: u64,
+}
+
+impl<C: Clock> Monitor<C> {
+    /// Start watching, taking reply times from `clock`.
+    #[must_use]
+    pub const fn new(clock: C) -> Self {
+        Self {
+            clock,
+            stuck_after: 3_600_000_000,
+            zero_after: 3_600_000_000,
+            run_length: 3,
+            latency_factor: 3,
+            last: None,
+            saturated: 0,
+            inverted: 0,
+            sent_at: None,
+            replies: 0,
+            baseline: 0,
+            recent: 0,
+        }
+    }
+
+    /// Report unchanged readings after `duration` (default: 1 hour).
+    /// In clean, steady air, readings may legitimately repeat for a while.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn set_stuck_after(mut self, duration: Duration) -> Self {
+        self.stuck_after = duration.as_micros() as u64;
+        self
+    }
+
+    /// Report zero readings after `duration` (default: 1 hour).
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn set_zero_after(mut self, duration: Duration) -> Self {
+        self.zero_after = duration.as_micros() as u64;
+        self
+    }
+
+    /// Report saturated or inverted readings once `readings` come
+    /// in a row (default: 3).
+    #[must_use]
+    pub const fn set_run_length(mut self, readings: u32) -> Self {
+        self.run_length = readings;
+        self
+    }
+
+    /// Report slow replies once they take `factor` times as long as the
+    /// first replies (default: 3). Growth below 10 ms is never reported.
+    #[must_use]
+    pub const fn set_latency_factor(mut self, factor: u8) -> Self {
+        self.latency_factor = factor;
+        self
+    }
+
+    /// Check a reading taken at `now_us`, in microseconds of the clock.
+    /// Only its values count, not its [`Quality`](crate::Quality).
+    pub fn check(&mut self, now_us: u64, measurement: &Measurement) {
+        let (pm25, pm10) = (measurement.pm25(), measurement.pm10());
+        self.last = match self.last {
+            Some((last, since, _)) if (last.pm25(), last.pm10()) == (pm25, pm10) => {
+                Some((last, since, now_us))
+            }
+            _ => Some((*measurement, now_us, now_us)),
+        };
+        let saturated = pm25 >= MAX_VALUE || pm10 >= MAX_VALUE;
+        self.saturated = if saturated {
+            self.saturated.saturating_add(1)
+        } else {
+            0
+        };
+        let inverted = pm25 > pm10;
+        self.inverted = if inverted {
+            self.inverted.saturating_add(1)
+        } else {
+            0
+        };
+    }
+
+    /// The problems found so far.
+    #[must_use]
+    pub fn report(&self) -> Report {
+        let repeated = self.last.and_then(|(measurement, since, latest)| {
+            let duration = latest.saturating_sub(since);
+            if measurement.pm25() == 0 && measurement.pm10() == 0 {
+                (duration >= self.zero_after).then_some(Problem::Zero {
+                    duration: Duration::from_micros(duration),
+                })
+            } else {
+                (duration >= self.stuck_after).then_some(Problem::Stuck {
+                    measurement,
+                    duration: Duration::from_micros(duration),
+                })
+            }
+        });
+        let run = |readings| (readings >= self.run_length.max(1)).then_some(readings);
+        let slow = self.replies >= BASELINE
+            && self.recent >= self.baseline * u64::from(self.latency_factor)
+            && self.recent >= self.baseline + LATENCY_MARGIN;
+
+        Report {
+            problems: [
+                repeated,
+                run(self.saturated).map(|readings| Problem::Saturated { readings }),
+                run(self.inverted).map(|readings| Problem::Inverted { readings }),
+                slow.then_some(Problem::SlowReplies {
+                    baseline: Duration::from_micros(self.baseline),
+                    recent: Duration::from_micros(self.recent),
+                }),
+            ],
+        }
+    }
+
+    /// Forget everything seen, e.g. after replacing the sensor.
+    pub const fn reset(&mut self) {
+        self.last = None;
+        self.saturated = 0;
+        self.inverted = 0;
+        self.sent_at = None;
+        self.replies = 0;
+        self.baseline = 0;
+        self.recent = 0;
+    }
+
+    const fn latency(&mut self, latency: u64) {
+        self.replies += 1;
+        if self.replies <= BASELINE {
+            // running mean
+            self.baseline = (self.baseline * (self.replies - 1) + latency) / self.replies;
+            self.recent = self.baseline;
+        } else {
+            self.recent = (self.recent * 7 + latency) / 8;
+        }
+    }
+}
+
+impl<C: Clock> Observer for Monitor<C> {
+    fn frame_sent(&mut self, _frame: &[u8]) {
+        self.sent_at = Some(self.clock.now_us());
+    }
+
+    fn frame_received(&mut self, _frame: &[u8]) {
+        if let Some(sent_at) = self.sent_at.take() {
+            let latency = self.clock.now_us().saturating_sub(sent_at);
+            self.latency(latency);
+        }
+    }
+
+    fn raw_measurement(&mut self, measurement: &Measurement) {
+        let now = self.clock.now_us();
+        self.check(now, measurement);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Monitor, Problem};
+    use crate::calibration::{Calibration, Curve};
+    use crate::capture::Clock;
+    use crate::mock::fixtures::{ID, SDS011, init_script, measurement, query, sleep_set};
+    use crate::mock::{Mock, MockDelay, SleepMode};
+    use crate::observer::Observer;
+    use crate::{Config, Measurement, Quality, Source};
+    use alloc::string::ToString;
+    use core::cell::Cell;
+    use core::time::Duration;
+
+    struct Manual<'a>(&'a Cell<u64>);
+
+    impl Clock for Manual<'_> {
+        fn now_us(&mut self) -> u64 {
+            self.0.get()
+        }
+    }
+
+    const MINUTE: u64 = 60_000_000;
+
+    fn problems(monitor: &Monitor<Manual<'_>>) -> alloc::vec::Vec<Problem> {
+        monitor.report().problems().copied().collect()
+    }
+
+    #[test]
+    fn stuck() {
+        let now = Cell::new(0);
+        let mut monitor = Monitor::new(Manual(&now)).set_stuck_after(Duration::from_mins(10));
+        let m = Measurement::new(123, 456);
+        for minute in 0..10 {
+            monitor.check(minute * MINUTE, &m);
+        }
+        assert!(monitor.report().is_healthy());
+        assert_eq!(monitor.report().to_string(), "healthy");
+
+        monitor.check(10 * MINUTE, &m);
+        let stuck = Problem::Stuck {
+            measurement: m,
+            duration: Duration::from_mins(10),
+        };
+        assert_eq!(problems(&monitor), [stuck]);
+
+        // a change starts over
+        monitor.check(11 * MINUTE, &Measurement::new(124, 456));
+        assert!(monitor.report().is_healthy());
+    }
+
+    #[test]
+    fn zero() {
+        let now = Cell::new(0);
+        let mut monitor = Monitor::new(Manual(&now));
+        for minute in 0..=60 {
+            monitor.check(minute * MINUTE, &Measurement::new(0, 0));
+        }
+        assert_eq!(
+            problems(&monitor),
+            [Problem::Zero {
+                duration: Duration::from_hours(1)
+            }]
+        );
+        assert_eq!(monitor.report().to_string(), "readings zero for 3600 s");
+    }
+
+    #[test]
+    fn values_only() {
+        let now = Cell::new(0);
+        let mut monitor = Monitor::new(Manual(&now))
+            .set_zero_after(Duration::from_mins(1))
+            .set_stuck_after(Duration::from_mins(1));
+        // as the driver returns them, warming up for a varying time
+        let reading = |pm25, warm_up| {
+            Measurement::new(pm25, 0).with_quality(Quality {
+                warm_up: Some(warm_up),
+                source: Some(Source::Query),
+                sensor_id: Some(0xA160),
+                ..Quality::default()
+            })
+        };
+        monitor.check(0, &reading(0, 30_000));
+        monitor.check(MINUTE, &reading(0, 31_000));
+        assert_eq!(
+            problems(&monitor),
+            [Problem::Zero {
+                duration: Duration::from_mins(1)
+            }]
+        );
+
+        monitor.check(2 * MINUTE, &reading(5, 30_000));
+        monitor.check(3 * MINUTE, &reading(5, 31_000));
+        let [Problem::Stuck { duration, .. }] = problems(&monitor)[..] else {
+            panic!("{}", monitor.report());
+        };
+        assert_eq!(duration, Duration::from_mins(1));
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn skips_discarded_query() {
+        let mut script = init_script();
+        script.extend([
+            sleep_set(SleepMode::Work, Some(ID)),
+            query(9_999, 9_999),
+            query(9_999, 9_999),
+            sleep_set(SleepMode::Sleep, Some(ID)),
+        ]);
+        let mut serial = Mock::new(script);
+        let now = Cell::new(0);
+        let mut monitor = Monitor::new(Manual(&now)).set_run_length(2);
+
+        let sensor = SDS011::new(&mut serial, Config::default()).set_observer(&mut monitor);
+        let mut sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        sensor.measure(&mut MockDelay::new()).await.unwrap();
+        serial.done();
+
+        // one saturated reading, not two
+        assert!(monitor.report().is_healthy());
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn raw_readings() {
+        let mut script = init_script();
+        script.extend(measurement(10, 10));
+        let mut serial = Mock::new(script);
+        let now = Cell::new(0);
+        let mut monitor = Monitor::new(Manual(&now)).set_zero_after(Duration::ZERO);
+
+        // clamps clean air to zero
+        let curve = Curve::Linear {
+            slope: 1.0,
+            offset: -1.5,
+        };
+        let config = Config::default().set_calibration(Calibration::new(ID, curve, curve));
+        let sensor = SDS011::new(&mut serial, config).set_observer(&mut monitor);
+        let mut sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        let m = sensor.measure(&mut MockDelay::new()).await.unwrap();
+        assert_eq!(m, Measurement::new(0, 0));
+        serial.done();
+
+        assert!(monitor.report().is_healthy());
+    }
+
+    #[test]
+    fn runs() {
+        let now = Cell::new(0);
+        let mut monitor = Monitor::new(Manual(&now));
+        monitor.check(0, &Measurement::new(9_999, 9_999));
+        monitor.check(1, &Measurement::new(200, 100));
+        monitor.check(2, &Measurement::new(200, 9_999));
+        monitor.check(3, &Measurement::new(200, 9_999));
+        assert!(monitor.report().is_healthy());
+
+        monitor.check(4, &Measurement::new(9_999, 9_999));
+        assert_eq!(problems(&monitor), [Problem::Saturated { readings: 3 }]);
+        for (t, pm10) in [(5, 245), (6, 244), (7, 243)] {
+            monitor.check(t, &Measurement::new(300, pm10));
+        }
+        assert_eq!(problems(&monitor), [Problem::Inverted { readings: 3 }]);
+        assert_eq!(
+            monitor.report().to_string(),
+            "3 readings in a row with PM2.5 above PM10"
+        );
+        monitor.reset();
+        assert!(monitor.report().is_healthy());
+    }
+
+    /// A query answered after `latency_ms`, a minute after the last.
+    fn exchange(
+        monitor: &mut Monitor<Manual<'_>>,
+        now: &Cell<u64>,
+        latency_ms: u64,
+        m: Measurement,
+    ) {
+        monitor.frame_sent(&[0xAA, 0xB4]);
+        now.set(now.get() + latency_ms * 1_000);
+        monitor.frame_received(&[0xAA, 0xC0]);
+        monitor.raw_measurement(&m);
+        now.set(now.get() + MINUTE);
+    }
+
+    #[test]
+    fn observed() {
+        let now = Cell::new(0);
+        let mut monitor = Monitor::new(Manual(&now)).set_run_length(2);
+        for _ in 0..8 {
+            exchange(&mut monitor, &now, 10, Measurement::new(100, 200));
+        }
+        exchange(&mut monitor, &now, 10, Measurement::new(300, 200));
+        exchange(&mut monitor, &now, 10, Measurement::new(300, 200));
+        assert_eq!(problems(&monitor), [Problem::Inverted { readings: 2 }]);
+
+        // replies slow down to 100 ms
+        for _ in 0..20 {
+            exchange(&mut monitor, &now, 100, Measurement::new(100, 200));
+        }
+        let [Problem::SlowReplies { baseline, recent }] = problems(&monitor)[..] else {
+            panic!("{}", monitor.report());
+        };
+        assert_eq!(baseline, Duration::from_millis(10));
+        assert!(recent > Duration::from_millis(80));
+        assert!(monitor.report().to_string().starts_with("replies take"));
+    }
+}
@@ -0,0 +1,269 @@
+//! Correcting readings for humidity.
+//!
+//! Particles take up water in humid air and grow, so the sensor's optical
+//! measurement overestimates their dry mass, heavily so above about 70%
+//! relative humidity. A [`Model`] corrects a [`Measurement`] given the
+//! relative humidity (and, for some models, the temperature) measured by
+//! another sensor nearby:
+//!
+//! ```ignore
+//! let model = Model::Kohler { kappa: 0.4 };
+//! let corrected = model.correct(&measurement, rh, None)?;
+//! println!("{corrected}");
+//! ```
+//!
+//! The result is a [`Corrected`] value instead of a `Measurement`,
+//! so corrected and raw readings cannot be mixed up by accident.
+
+use crate::Measurement;
+use core::fmt::{self, Display, Formatter};
+use thiserror::Error;
+
+/// A linear correction `slope * PM + humidity * RH + temperature * T + offset`,
+/// with PM in µg/m3, RH in percent and T in °C.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Linear {
+    /// Factor of the raw concentration.
+    pub slope: f32,
+    /// Factor of the relative humidity.
+    pub humidity: f32,
+    /// Factor of the temperature.
+    pub temperature: f32,
+    /// Constant term, in µg/m3.
+    pub offset: f32,
+}
+
+impl Linear {
+    /// The US-wide correction of the EPA for PM2.5 (Barkjohn et al., 2021).
+    pub const EPA_US: Self = Self {
+        slope: 0.524,
+        humidity: -0.0862,
+        temperature: 0.0,
+        offset: 5.75,
+    };
+
+    fn apply(&self, pm: u16, rh: f32, temperature: Option<f32>) -> Result<u16, CorrectionError> {
+        let temperature = match temperature {
+            Some(t) => t,
+            None if self.temperature == 0.0 => 0.0,
+            None => return Err(CorrectionError::MissingTemperature),
+        };
+        let pm = self.slope * f32::from(pm) / 10.0
+            + self.humidity * rh
+            + self.temperature * temperature
+            + self.offset;
+        Ok(tenths(pm))
+    }
+}
+
+/// A humidity correction model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Model {
+    /// Hygroscopic growth after κ-Köhler theory (Crilley et al., 2018):
+    /// both concentrations are divided by `1 + (κ / 1.65) / (100 / RH - 1)`.
+    /// `kappa` depends on the aerosol; 0.4 is typical for urban air,
+    /// sea salt goes up to about 1.
+    Kohler {
+        /// The hygroscopicity parameter κ.
+        kappa: f32,
+    },
+    /// The EPA's US-wide correction, see [`Linear::EPA_US`].
+    /// It was fitted for Plantower sensors rather than the SDS011,
+    /// and only corrects PM2.5.
+    EpaUs,
+    /// A linear correction of PM2.5, and of PM10 if given,
+    /// e.g. fitted against a reference instrument.
+    Linear {
+        /// The correction of PM2.5.
+        pm25: Linear,
+        /// The correction of PM10, if any.
+        pm10: Option<Linear>,
+    },
+}
+
+/// Why a correction could not be applied.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+pub enum CorrectionError {
+    /// The relative humidity was not between 0 and 100%.
+    #[error("relative humidity out of range")]
+    InvalidHumidity,
+    /// The model depends on the temperature, which was not given.
+    #[error("the correction needs the temperature")]
+    MissingTemperature,
+}
+
+/// A measurement corrected for humidity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Corrected {
+    pm25: u16,
+    pm10: Option<u16>,
+    raw: Measurement,
+    rh: f32,
+}
+
+impl Corrected {
+    /// The corrected PM2.5 value. Divide by ten to get µg/m3.
+    #[must_use]
+    pub const fn pm25(&self) -> u16 {
+        self.pm25
+    }
+
+    /// The corrected PM10 value, or `None` if the model does not correct
+    /// PM10. Divide by ten to get µg/m3.
+    #[must_use]
+    pub const fn pm10(&self) -> Option<u16> {
+        self.pm10
+    }
+
+    /// The measurement before the correction.
+    #[must_use]
+    pub const fn raw(&self) -> Measurement {
+        self.raw
+    }
+
+    /// The relative humidity the correction was based on, in percent.
+    #[must_use]
+    pub const fn humidity(&self) -> f32 {
+        self.rh
+    }
+}
+
+impl Display for Corrected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let pm25: f32 = self.pm25.into();
+        write!(f, "PM2.5: {} µg/m3", pm25 / 10.0)?;
+        if let Some(pm10) = self.pm10 {
+            let pm10: f32 = pm10.into();
+            write!(f, ", PM10: {} µg/m3", pm10 / 10.0)?;
+        }
+        write!(f, " (corrected for {}% RH)", self.rh)
+    }
+}
+
+impl Model {
+    /// Correct `measurement` for the relative humidity `rh` (in percent)
+    /// and, if known, the `temperature` (in °C).
+    /// The κ-Köhler model treats humidity above 99% as 99%.
+    ///
+    /// # Errors
+    /// [`CorrectionError::InvalidHumidity`] if `rh` is out of range, and
+    /// [`CorrectionError::MissingTemperature`] if the model needs the
+    /// temperature.
+    pub fn correct(
+        &self,
+        measurement: &Measurement,
+        rh: f32,
+        temperature: Option<f32>,
+    ) -> Result<Corrected, CorrectionError> {
+        if !(0.0..=100.0).contains(&rh) {
+            return Err(CorrectionError::InvalidHumidity);
+        }
+        let (pm25, pm10) = match self {
+            Self::Kohler { kappa } => {
+                let growth = 1.0 + (kappa / 1.65) / (100.0 / rh.min(99.0) - 1.0);
+                let dry = |pm: u16| tenths(f32::from(pm) / 10.0 / growth);
+                (dry(measurement.pm25()), Some(dry(measurement.pm10())))
+            }
+            Self::EpaUs => (
+                Linear::EPA_US.apply(measurement.pm25(), rh, temperature)?,
+                None,
+            ),
+            Self::Linear { pm25, pm10 } => (
+                pm25.apply(measurement.pm25(), rh, temperature)?,
+                pm10.map(|pm10| pm10.apply(measurement.pm10(), rh, temperature))
+                    .transpose()?,
+            ),
+        };
+        Ok(Corrected {
+            pm25,
+            pm10,
+            raw: *measurement,
+            rh,
+        })
+    }
+}
+
+/// `pm` in µg/m3 as a (raw-like) value in 0.1 µg/m3, rounded,
+/// and clamped at zero.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+pub(crate) fn tenths(pm: f32) -> u16 {
+    (pm * 10.0 + 0.5).clamp(0.0, f32::from(u16::MAX)) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CorrectionError, Linear, Model};
+    use crate::Measurement;
+
+    #[test]
+    fn kohler() {
+        let model = Model::Kohler { kappa: 0.4 };
+        let m = Measurement::new(1_000, 2_000);
+
+        let dry = model.correct(&m, 0.0, None).unwrap();
+        assert_eq!((dry.pm25(), dry.pm10()), (1_000, Some(2_000)));
+        // growth by 1 + 0.4 / 1.65
+        let humid = model.correct(&m, 50.0, None).unwrap();
+        assert_eq!((humid.pm25(), humid.pm10()), (805, Some(1_610)));
+        let foggy = model.correct(&m, 90.0, None).unwrap();
+        assert_eq!((foggy.pm25(), foggy.pm10()), (314, Some(629)));
+        assert_eq!(foggy.raw(), m);
+
+        let saturated = model.correct(&m, 100.0, None).unwrap();
+        let almost = model.correct(&m, 99.0, None).unwrap();
+        assert_eq!(saturated.pm25(), almost.pm25());
+    }
+
+    #[test]
+    fn epa() {
+        let corrected = Model::EpaUs
+            .correct(&Measurement::new(200, 300), 50.0, None)
+            .unwrap();
+        // 0.524 * 20 - 0.0862 * 50 + 5.75
+        assert_eq!((corrected.pm25(), corrected.pm10()), (119, None));
+
+        // clean, humid air
+        let corrected = Model::EpaUs
+            .correct(&Measurement::new(0, 0), 90.0, None)
+            .unwrap();
+        assert_eq!(corrected.pm25(), 0);
+    }
+
+    #[test]
+    fn linear() {
+        let pm25 = Linear {
+            slope: 1.0,
+            humidity: -0.1,
+            temperature: 0.2,
+            offset: 1.0,
+        };
+        let model = Model::Linear { pm25, pm10: None };
+        let m = Measurement::new(100, 100);
+
+        let corrected = model.correct(&m, 50.0, Some(20.0)).unwrap();
+        assert_eq!((corrected.pm25(), corrected.pm10()), (100, None));
+        assert_eq!(
+            model.correct(&m, 50.0, None),
+            Err(CorrectionError::MissingTemperature)
+        );
+
+        let model = Model::Linear {
+            pm25: Linear::EPA_US,
+            pm10: Some(Linear::EPA_US),
+        };
+        let corrected = model.correct(&Measurement::new(200, 200), 50.0, None);
+        assert_eq!(corrected.unwrap().pm10(), Some(119));
+    }
+
+    #[test]
+    fn invalid_humidity() {
+        let m = Measurement::new(100, 100);
+        for rh in [-1.0, 100.1, f32::NAN] {
+            assert_eq!(
+                Model::EpaUs.correct(&m, rh, None),
+                Err(CorrectionError::InvalidHumidity)
+            );
+        }
+    }
+}
@@ -4,15 +4,28 @@
 //! systems as well as embedded devices.
 //!
 //! # Features
-//! * `sync`: To use the synchronous interface, enable this feature.
-//!   By default, this library exposes an async API.
+//! * `sync`: No longer has any effect. `SDS011` at the crate root always
+//!   refers to the async driver, [`asynch::SDS011`]; the blocking driver is
+//!   [`blocking::SDS011`]. Both are available in every build, so features
+//!   unified across the dependency graph cannot change either API.
+//! * `mock`: Exposes the [`mock`] module, a scripted serial port and delay
+//!   for unit testing code built on this driver (requires `alloc`).
+//! * `std`: Adds [`capture::StdClock`] for timestamping recorded traffic,
+//!   and the [`fit`] module and `sds011-fit` tool for fitting calibrations.
+//! * `log`, `defmt`: Add [`observer::LogObserver`] and
+//!   [`observer::DefmtObserver`], which write what the driver does to the
+//!   respective logger.
 //!
 //! # Examples
 //! The crate ships with two small CLI examples that utilize the library:
-//! * [`cli.rs`](examples/cli.rs) uses the synchronous interface (embedded-io),
-//! * [`cli_async.rs`](examples/cli_async.rs) uses the asynchronous interface
+//! * [`cli.rs`](examples/cli.rs) uses the blocking driver (embedded-io),
+//! * [`cli_async.rs`](examples/cli_async.rs) uses the async driver
 //!   (embedded-io-async).
 //!
+//! For debugging, the `sds011-decode` tool annotates every frame in a hex
+//! dump, raw byte stream or [capture] with its direction, command,
+//! fields, sensor ID and checksum validity.
+//!
 //! The example below demonstrates how to use the sensor with an ESP32,
 //! showcasing the strength of the embedded-hal abstractions.
 //!
@@ -88,16 +101,65 @@
 //!   This puts the sensor in charge of sleeping and waking up.
 //!   Since it will continuously produce data, make sure to call `measure()`
 //!   in time so the serial output buffer does not overflow.
+//! * If the state is only known at runtime, e.g. for sensors kept in a
+//!   collection, convert the sensor into a [`DynSDS011`]. It offers the same
+//!   operations, but checks the state at runtime and fails with
+//!   [`SDS011Error::InvalidState`] instead.
+//!
+//! # Clone Sensors
+//! Some sensors deviate from the control protocol, e.g. by ending replies
+//! in the wrong byte or echoing their ID byte-swapped. Known deviations are
+//! listed in [`Quirk`]; which ones the driver tolerates is set with
+//! [`Config::set_quirks()`] (`Quirks::STRICT`, `Quirks::LENIENT`, or any
+//! combination). [`SDS011::quirks()`] reports the ones a sensor has shown,
+//! which helps to tell genuine sensors from clones.
+//!
+//! # RS485 Buses
+//! By default, commands are broadcast to all sensors on a serial port.
+//! To share a half-duplex RS485 bus between several sensors, wrap the port in
+//! [`rs485::Rs485`], which drives the transceiver's direction-control pin,
+//! and address each sensor with [`Config::set_sensor_id()`].
+//!
+//! # Power Switch
+//! If the sensor's supply is switched, e.g. by a MOSFET, hand the gate pin to
+//! `new_with_power()`. The driver then power cycles and re-initializes a sensor
+//! that failed several measurements in a row, and it can switch the sensor off
+//! between measurements, which saves more energy than its sleep mode
+//! (see [`Config::set_power_off_when_idle()`]).
+//!
+//! # Air Quality
+//! Every [`Measurement`] carries its [`Quality`]: the sensor it came from,
+//! whether it was queried or reported periodically, how long the fan ran,
+//! whether it is at the end of the range, and whether it was calibrated or
+//! filtered.
+//!
+//! The [`aqi`] module converts measurements, or better their averages over
+//! the right period, into the US EPA AQI, the European CAQI and EAQI, the UK
+//! DAQI, the Indian NAQI or the Chinese AQI, with categories and colors;
+//! [`nowcast::NowCast`] averages hourly measurements for a current US AQI.
+//! The [`stats`] module provides the averages: running statistics and moving
+//! averages over the last readings or a time span, without allocating.
+//! The [`filter`] module smooths jumpy readings with an exponential moving
+//! average, a sliding median or a Kalman filter, and the [`outlier`] module
+//! flags single-reading spikes from insects, steam or cooking.
+//! Since the sensor overestimates in humid air, the [`humidity`] module corrects
+//! measurements for the relative humidity measured by another sensor.
+//! Per-sensor corrections from a co-location with a reference monitor go into
+//! a [`calibration::Calibration`], applied to every measurement by
+//! [`Config::set_calibration()`]. The `sds011-fit` tool fits them from a CSV
+//! file of readings and reference values and reports R², RMSE and bias.
 //!
 //! # Limitations
-//! This abstraction does not yet support sending commands only to a specific
-//! sensor id (it effectively uses broadcast mode all the time).
-//! This feature seemed irrelevant, but the backend code for it is completely
-//! implemented, so this may change in a future version if there is demand.
-//! Also, putting sensors into periodic mode can have the side effect of missing
-//! package boundaries. The current version cannot recover from this; it will
-//! return an error. Close the serial port and retry, or probably better,
-//! just don't use periodic mode.
+//! Putting sensors into periodic mode can have the side effect of missing
+//! package boundaries. The driver itself cannot recover from this; it will
+//! return an error. A [`Supervisor`] drains the line and re-initializes the
+//! sensor after such errors, but probably better, just don't use periodic mode.
+//!
+//! Flaky links may also need some quiet time between commands: [`pacing::Paced`]
+//! waits a fixed gap before each command, then discards stale input.
+//!
+//! Sensors wear out over time, and a [`health::Monitor`] flags the ones that
+//! look broken: stuck or saturated readings, PM2.5 above PM10, slow replies.
 //!
 //! # Acknowledgements
 //! Thank you to Tim Orme, who implemented sds011lib in Python
@@ -112,28 +174,64 @@
 #![warn(clippy::pedantic)]
 #![warn(clippy::cargo)]
 #![warn(clippy::nursery)]
-
+#![cfg_attr(
+    not(test),
+    warn(
+        clippy::expect_used,
+        clippy::unwrap_used,
+        clippy::panic,
+        clippy::unimplemented,
+        clippy::todo,
+        clippy::unreachable
+    )
+)]
+
+#[cfg(any(test, feature = "mock"))]
+extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
+
+use calibration::Calibration;
+use core::convert::Infallible;
 use core::fmt::Debug;
-use core::marker::PhantomData;
-#[cfg(feature = "sync")]
-use embedded_hal::delay::DelayNs;
-#[cfg(not(feature = "sync"))]
-use embedded_hal_async::delay::DelayNs;
-#[cfg(feature = "sync")]
-use embedded_io::{Read, ReadExactError, Write};
-#[cfg(not(feature = "sync"))]
-use embedded_io_async::{Read, ReadExactError, Write};
-use maybe_async::maybe_async;
-pub use message::{FirmwareVersion, Measurement};
-use message::{
-    Kind, Message, ParseError, RECV_BUF_SIZE, Reporting, ReportingMode, Sleep, SleepMode,
-    WorkingPeriod,
+use embedded_hal::digital;
+use message::RECV_BUF_SIZE;
+pub use message::{
+    FirmwareVersion, Kind, Measurement, NewDeviceID, ParseError, Quality, Reporting, ReportingMode,
+    Sleep, SleepMode, Source, WorkingPeriod,
 };
+pub use quirks::{Quirk, Quirks};
 use thiserror::Error;
 
+pub mod aqi;
+pub mod asynch;
+pub mod blocking;
+pub mod calibration;
+pub mod capture;
+pub mod decode;
+pub mod filter;
+#[cfg(feature = "std")]
+pub mod fit;
+pub mod health;
+pub mod humidity;
 mod message;
-
-/// Sensor configuration, specifically delay times.
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+pub mod nowcast;
+pub mod observer;
+pub mod outlier;
+pub mod pacing;
+mod quirks;
+pub mod rs485;
+pub mod split;
+pub mod stats;
+pub mod supervisor;
+#[cfg(test)]
+mod tests;
+
+pub use asynch::{DynSDS011, SDS011, Supervisor};
+
+/// Sensor configuration, specifically delay times and the parsing policy.
 ///
 /// Delays are necessary between waking up the sensor
 /// and reading its value to stabilize the measurement.
@@ -141,6 +239,12 @@ mod message;
 pub struct Config {
     sleep_delay: u32,
     measure_delay: u32,
+    quirks: Quirks,
+    sensor_id: u16,
+    power_delay: u32,
+    power_off_idle: bool,
+    power_cycle_after: u8,
+    calibration: Option<Calibration>,
 }
 
 impl Default for Config {
@@ -148,6 +252,12 @@ impl Default for Config {
         Self {
             sleep_delay: 500,
             measure_delay: 30_000,
+            quirks: Quirks::default(),
+            sensor_id: 0xFFFF,
+            power_delay: 1_000,
+            power_off_idle: false,
+            power_cycle_after: 3,
+            calibration: None,
         }
     }
 }
@@ -169,32 +279,157 @@ impl Config {
         self.sleep_delay = sleep_delay;
         self
     }
+
+    /// Which deviations from the protocol to tolerate in replies, e.g.
+    /// [`Quirks::STRICT`] to reject all of them or [`Quirks::LENIENT`]
+    /// for clone sensors. Replies showing any other quirk fail with
+    /// a [`SDS011Error::ParseError`].
+    #[must_use]
+    pub const fn set_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    /// Address only the sensor with this ID, e.g. one of several on an
+    /// RS485 bus (see [`rs485`]). By default, commands are broadcast to all
+    /// sensors (0xFFFF), which only works with a single sensor per port.
+    #[must_use]
+    pub const fn set_sensor_id(mut self, sensor_id: u16) -> Self {
+        self.sensor_id = sensor_id;
+        self
+    }
+
+    /// How many milliseconds to leave the sensor switched off when power
+    /// cycling it, and to let it boot after switching it on; defaults to 1000.
+    /// Only used with a power switch, see `SDS011::new_with_power()`.
+    #[must_use]
+    pub const fn set_power_delay(mut self, power_delay: u32) -> Self {
+        self.power_delay = power_delay;
+        self
+    }
+
+    /// Switch the sensor off between measurements instead of sending it to
+    /// sleep, which saves more energy; defaults to false.
+    /// The sensor is switched off after failed measurements as well.
+    /// Only used with a power switch, see `SDS011::new_with_power()`.
+    #[must_use]
+    pub const fn set_power_off_when_idle(mut self, power_off_idle: bool) -> Self {
+        self.power_off_idle = power_off_idle;
+        self
+    }
+
+    /// After how many failed measurements in a row to power cycle and
+    /// re-initialize the sensor before trying once more; defaults to 3,
+    /// 0 never power cycles. Measurements cut short, e.g. by a timeout,
+    /// count as failed. Only used with a power switch,
+    /// see `SDS011::new_with_power()`.
+    #[must_use]
+    pub const fn set_power_cycle_after(mut self, failures: u8) -> Self {
+        self.power_cycle_after = failures;
+        self
+    }
+
+    /// Calibrate the measurements of the sensor with the calibration's ID;
+    /// measurements of other sensors (or before `init()`, if the ID is not
+    /// configured) are left alone. By default, nothing is calibrated.
+    #[must_use]
+    pub const fn set_calibration(mut self, calibration: Calibration) -> Self {
+        self.calibration = Some(calibration);
+        self
+    }
+}
+
+/// Stands in for the power switch of a sensor created without one.
+///
+/// It only fills the power switch parameter of [`SDS011`] when the sensor
+/// is created with `new()`; switching it does nothing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoPower;
+
+impl digital::ErrorType for NoPower {
+    type Error = Infallible;
+}
+
+impl digital::OutputPin for NoPower {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
 
 /// Error type for operations on the SDS011 sensor.
+///
+/// Errors about a reply name the command in flight (a measurement query
+/// while waiting for periodic data) and the ID of the sensor it was sent to,
+/// which is 0xFFFF (all sensors) before initialization.
 #[derive(Debug, Error)]
 pub enum SDS011Error<E> {
     /// A received message could not be decoded.
-    #[error("message could not be decoded: {0}")]
-    ParseError(#[from] ParseError),
+    #[error(
+        "reply to \"{}\" could not be decoded: {source} (frame {raw:02X?})",
+        command.describe_query()
+    )]
+    ParseError {
+        /// Why the reply was rejected.
+        source: ParseError,
+        /// The received frame.
+        raw: [u8; RECV_BUF_SIZE],
+        /// The command in flight.
+        command: Kind,
+        /// The sensor the command was sent to.
+        sensor_id: u16,
+    },
     /// The serial interface returned an error while reading.
     #[error("serial read error: {0}")]
     ReadError(E),
     /// The serial interface returned an error while writing.
     #[error("serial write error: {0}")]
     WriteError(E),
-    /// Encountered an EOF while reading.
+    /// Encountered an EOF while reading,
+    /// or the serial interface accepted no data while writing.
     #[error("unexpected EOF")]
     UnexpectedEof,
     /// The received message was not expected in the current sensor state.
-    #[error("unexpected message type")]
-    UnexpectedType,
-    /// The requested operation failed.
-    #[error("requested operation failed")]
-    OperationFailed,
+    #[error(
+        "expected a reply to \"{}\" from sensor {sensor_id:04X}, received \"{}\"",
+        expected.describe_query(),
+        received.describe_reply()
+    )]
+    UnexpectedType {
+        /// The command in flight.
+        expected: Kind,
+        /// The reply that arrived instead.
+        received: Kind,
+        /// The sensor the command was sent to.
+        sensor_id: u16,
+    },
+    /// The sensor did not carry out the requested operation.
+    #[error(
+        "sensor {sensor_id:04X} refused \"{}\", replying \"{}\"",
+        command.describe_query(),
+        received.describe_reply()
+    )]
+    OperationFailed {
+        /// The command the sensor refused.
+        command: Kind,
+        /// The sensor's reply.
+        received: Kind,
+        /// The sensor the command was sent to.
+        sensor_id: u16,
+    },
     /// The given parameters were invalid.
     #[error("given parameters were invalid")]
     Invalid,
+    /// The power switch could not be set.
+    #[error("could not switch the sensor's power")]
+    PowerError,
+    /// The operation is not possible in the sensor's current state
+    /// (only returned by `DynSDS011`).
+    #[error("operation not possible while the sensor is {0}")]
+    InvalidState(State),
 }
 
 pub mod sensor_state {
@@ -206,339 +441,52 @@ pub mod sensor_state {
     /// as explained in the [technical overview](crate#technical-overview).
     ///
     /// This trait is sealed to prevent external implementations.
-    pub trait SensorState: private::Sealed {}
+    pub trait SensorState: private::Sealed {
+        /// The state, as checked at runtime by `DynSDS011`.
+        const STATE: State;
+    }
 
     /// Sensor reports periodically
     pub struct Periodic;
     impl private::Sealed for Periodic {}
-    impl SensorState for Periodic {}
+    impl SensorState for Periodic {
+        const STATE: State = State::Periodic;
+    }
 
     /// Sensor sleeps until polled
     pub struct Polling;
     impl private::Sealed for Polling {}
-    impl SensorState for Polling {}
+    impl SensorState for Polling {
+        const STATE: State = State::Polling;
+    }
 
     /// Sensor not yet initialized
     pub struct Uninitialized;
     impl private::Sealed for Uninitialized {}
-    impl SensorState for Uninitialized {}
-}
-
-pub use sensor_state::SensorState;
-use sensor_state::{Periodic, Polling, Uninitialized};
-
-/// The main struct.
-/// Wraps around a serial interface that implements embedded-io(-async).
-///
-/// Calling `new()` will give you an uninitialized struct.
-/// You need to call `init()` on it to get a sensor that can be polled.
-pub struct SDS011<RW, S: SensorState> {
-    serial: RW,
-    config: Config,
-    sensor_id: Option<u16>,
-    firmware: Option<FirmwareVersion>,
-    _state: PhantomData<S>,
-}
-
-impl<RW, S> SDS011<RW, S>
-where
-    RW: Read + Write,
-    S: SensorState,
-{
-    #[maybe_async]
-    async fn get_reply(&mut self) -> Result<Message, SDS011Error<RW::Error>> {
-        let mut buf = [0u8; RECV_BUF_SIZE];
-
-        match self.serial.read_exact(&mut buf).await {
-            Ok(()) => Ok(Message::parse_reply(&buf)?),
-            Err(ReadExactError::UnexpectedEof) => Err(SDS011Error::UnexpectedEof),
-            Err(ReadExactError::Other(e)) => Err(SDS011Error::ReadError(e)),
-        }
-    }
-
-    #[maybe_async]
-    async fn send_message(&mut self, kind: Kind) -> Result<(), SDS011Error<RW::Error>> {
-        let msg = Message::new(kind, self.sensor_id);
-        let out_buf = msg.create_query();
-
-        self.serial
-            .write_all(&out_buf)
-            .await
-            .map_err(SDS011Error::WriteError)
+    impl SensorState for Uninitialized {
+        const STATE: State = State::Uninitialized;
     }
 
-    #[maybe_async]
-    async fn read_sensor(&mut self, query: bool) -> Result<Measurement, SDS011Error<RW::Error>> {
-        if query {
-            self.send_message(Kind::Query(None)).await?;
-        }
-
-        match self.get_reply().await?.kind {
-            Kind::Query(data) => Ok(data.expect("replies always contain data")),
-            _ => Err(SDS011Error::UnexpectedType),
-        }
+    /// The states of [`SensorState`], for when they are only known at runtime.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum State {
+        /// See [`Uninitialized`].
+        Uninitialized,
+        /// See [`Polling`].
+        Polling,
+        /// See [`Periodic`].
+        Periodic,
     }
 
-    #[maybe_async]
-    async fn get_firmware(&mut self) -> Result<(u16, FirmwareVersion), SDS011Error<RW::Error>> {
-        self.send_message(Kind::FWVersion(None)).await?;
-
-        let reply = self.get_reply().await?;
-        let id = reply.sensor_id.expect("replies always contain data");
-        match reply.kind {
-            Kind::FWVersion(data) => Ok((id, data.expect("replies always contain data"))),
-            _ => Err(SDS011Error::UnexpectedType),
+    impl core::fmt::Display for State {
+        fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+            f.write_str(match self {
+                Self::Uninitialized => "uninitialized",
+                Self::Polling => "polling",
+                Self::Periodic => "periodic",
+            })
         }
     }
-
-    #[maybe_async]
-    async fn _get_runmode(&mut self) -> Result<ReportingMode, SDS011Error<RW::Error>> {
-        let r = Reporting::new_query();
-        self.send_message(Kind::ReportingMode(r)).await?;
-
-        match self.get_reply().await?.kind {
-            Kind::ReportingMode(data) => Ok(data.mode()),
-            _ => Err(SDS011Error::UnexpectedType),
-        }
-    }
-
-    #[maybe_async]
-    async fn set_runmode_query(&mut self) -> Result<(), SDS011Error<RW::Error>> {
-        let r = Reporting::new_set(ReportingMode::Query);
-        self.send_message(Kind::ReportingMode(r)).await?;
-
-        match self.get_reply().await?.kind {
-            Kind::ReportingMode(r) => match r.mode() {
-                ReportingMode::Query => Ok(()),
-                ReportingMode::Active => Err(SDS011Error::OperationFailed),
-            },
-            _ => Err(SDS011Error::UnexpectedType),
-        }
-    }
-
-    #[maybe_async]
-    async fn set_runmode_active(&mut self) -> Result<(), SDS011Error<RW::Error>> {
-        let r = Reporting::new_set(ReportingMode::Active);
-        self.send_message(Kind::ReportingMode(r)).await?;
-
-        match self.get_reply().await?.kind {
-            Kind::ReportingMode(r) => match r.mode() {
-                ReportingMode::Active => Ok(()),
-                ReportingMode::Query => Err(SDS011Error::OperationFailed),
-            },
-            _ => Err(SDS011Error::UnexpectedType),
-        }
-    }
-
-    #[maybe_async]
-    async fn _get_period(&mut self) -> Result<u8, SDS011Error<RW::Error>> {
-        let w = WorkingPeriod::new_query();
-        self.send_message(Kind::WorkingPeriod(w)).await?;
-
-        match self.get_reply().await?.kind {
-            Kind::WorkingPeriod(data) => Ok(data.period()),
-            _ => Err(SDS011Error::UnexpectedType),
-        }
-    }
-
-    #[maybe_async]
-    async fn set_period(&mut self, minutes: u8) -> Result<(), SDS011Error<RW::Error>> {
-        let w = WorkingPeriod::new_set(minutes);
-        self.send_message(Kind::WorkingPeriod(w)).await?;
-
-        match self.get_reply().await?.kind {
-            Kind::WorkingPeriod(data) if data.period() == minutes => Ok(()),
-            Kind::WorkingPeriod(_) => Err(SDS011Error::OperationFailed),
-            _ => Err(SDS011Error::UnexpectedType),
-        }
-    }
-
-    #[maybe_async]
-    async fn _get_sleep(&mut self) -> Result<SleepMode, SDS011Error<RW::Error>> {
-        let s = Sleep::new_query();
-        self.send_message(Kind::Sleep(s)).await?;
-
-        match self.get_reply().await?.kind {
-            Kind::Sleep(data) => Ok(data.sleep_mode()),
-            _ => Err(SDS011Error::UnexpectedType),
-        }
-    }
-
-    #[maybe_async]
-    async fn sleep(&mut self) -> Result<(), SDS011Error<RW::Error>> {
-        let s = Sleep::new_set(SleepMode::Sleep);
-        self.send_message(Kind::Sleep(s)).await?;
-
-        match self.get_reply().await?.kind {
-            Kind::Sleep(s) => match s.sleep_mode() {
-                SleepMode::Sleep => Ok(()),
-                SleepMode::Work => Err(SDS011Error::OperationFailed),
-            },
-            _ => Err(SDS011Error::UnexpectedType),
-        }
-    }
-
-    #[maybe_async]
-    async fn wake(&mut self) -> Result<(), SDS011Error<RW::Error>> {
-        let s = Sleep::new_set(SleepMode::Work);
-        self.send_message(Kind::Sleep(s)).await?;
-
-        match self.get_reply().await?.kind {
-            Kind::Sleep(s) => match s.sleep_mode() {
-                SleepMode::Work => Ok(()),
-                SleepMode::Sleep => Err(SDS011Error::OperationFailed),
-            },
-            _ => Err(SDS011Error::UnexpectedType),
-        }
-    }
-}
-
-impl<RW> SDS011<RW, Uninitialized>
-where
-    RW: Read + Write,
-{
-    /// Create a new sensor instance, consuming the serial interface.
-    /// The returned instance needs to be initialized before use.
-    pub const fn new(serial: RW, config: Config) -> Self {
-        Self {
-            serial,
-            config,
-            sensor_id: None,
-            firmware: None,
-            _state: PhantomData,
-        }
-    }
-
-    /// Put the sensor in a well-defined state (sleeping in polling mode).
-    ///
-    /// # Errors
-    /// This communicates with the sensor over serial and may fail with any
-    /// [`SDS011Error`].
-    #[maybe_async]
-    pub async fn init<D: DelayNs>(
-        mut self,
-        delay: &mut D,
-    ) -> Result<SDS011<RW, Polling>, SDS011Error<RW::Error>> {
-        // sleep a short moment to make sure the sensor is ready
-        delay.delay_ms(self.config.sleep_delay).await;
-        self.wake().await?;
-
-        self.set_runmode_query().await?;
-
-        // while we're at it, read the firmware version once
-        let (id, firmware) = self.get_firmware().await?;
-        self.sleep().await?;
-
-        Ok(SDS011::<RW, Polling> {
-            serial: self.serial,
-            config: self.config,
-            sensor_id: Some(id),
-            firmware: Some(firmware),
-            _state: PhantomData,
-        })
-    }
-}
-
-impl<RW> SDS011<RW, Periodic>
-where
-    RW: Read + Write,
-{
-    /// In this state, the sensor will wake up periodically (as configured),
-    /// wait 30 seconds, send a measurement over serial, and go back to sleep.
-    /// This method waits until data is available before returning.
-    ///
-    /// # Errors
-    /// This communicates with the sensor over serial and may fail with any
-    /// [`SDS011Error`].
-    #[maybe_async]
-    pub async fn measure(&mut self) -> Result<Measurement, SDS011Error<RW::Error>> {
-        self.read_sensor(false).await
-    }
-
-    /// Get the sensor's ID.
-    #[expect(clippy::missing_panics_doc, reason = "should never panic")]
-    pub const fn id(&self) -> u16 {
-        self.sensor_id.expect("sensor is initialized")
-    }
-
-    /// Get the sensor's firmware version.
-    #[expect(clippy::missing_panics_doc, reason = "should never panic")]
-    pub fn version(&self) -> FirmwareVersion {
-        self.firmware.clone().expect("sensor is initialized")
-    }
 }
 
-impl<RW> SDS011<RW, Polling>
-where
-    RW: Read + Write,
-{
-    /// In this state, measurements are triggered by calling this function.
-    /// The sensor is woken up and the fan spins for the configured delay time,
-    /// after which we send the measurement query and put it back to sleep.
-    ///
-    /// # Errors
-    /// This communicates with the sensor over serial and may fail with any
-    /// [`SDS011Error`].
-    #[maybe_async]
-    pub async fn measure<D: DelayNs>(
-        &mut self,
-        delay: &mut D,
-    ) -> Result<Measurement, SDS011Error<RW::Error>> {
-        // sleep a short moment to make sure the sensor is ready
-        delay.delay_ms(self.config.sleep_delay).await;
-        self.wake().await?;
-
-        // do a dummy measurement, spin for a few secs, then do real measurement
-        _ = self.read_sensor(true).await?;
-        delay.delay_ms(self.config.measure_delay).await;
-        let res = self.read_sensor(true).await?;
-        self.sleep().await?;
-
-        Ok(res)
-    }
-
-    /// Set the sensor into periodic measurement mode, in which it performs
-    /// a measurement every 0-30 `minutes`.
-    /// If > 0, the sensor will go to sleep between measurements.
-    ///
-    /// # Errors
-    /// This communicates with the sensor over serial and may fail with any
-    /// [`SDS011Error`].
-    #[maybe_async]
-    pub async fn make_periodic<D: DelayNs>(
-        mut self,
-        delay: &mut D,
-        minutes: u8,
-    ) -> Result<SDS011<RW, Periodic>, SDS011Error<RW::Error>> {
-        if minutes > 30 {
-            return Err(SDS011Error::Invalid);
-        }
-
-        // sleep a short moment to make sure the sensor is ready
-        delay.delay_ms(self.config.sleep_delay).await;
-        self.wake().await?;
-
-        self.set_period(minutes).await?;
-        self.set_runmode_active().await?;
-
-        Ok(SDS011::<RW, Periodic> {
-            serial: self.serial,
-            config: self.config,
-            sensor_id: self.sensor_id,
-            firmware: self.firmware,
-            _state: PhantomData,
-        })
-    }
-
-    /// Get the sensor's ID.
-    #[expect(clippy::missing_panics_doc, reason = "should never panic")]
-    pub const fn id(&self) -> u16 {
-        self.sensor_id.expect("sensor is initialized")
-    }
-
-    /// Get the sensor's firmware version.
-    #[expect(clippy::missing_panics_doc, reason = "should never panic")]
-    pub fn version(&self) -> FirmwareVersion {
-        self.firmware.clone().expect("sensor is initialized")
-    }
-}
+pub use sensor_state::{SensorState, State};
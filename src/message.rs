@@ -1,32 +1,105 @@
+use crate::quirks::{Quirk, Quirks};
 use core::fmt::{Display, Formatter};
 use thiserror::Error;
 
-#[derive(Debug, Error)]
+/// Why a frame was rejected.
+///
+/// [`Checksum`](Self::Checksum) and [`HeadTail`](Self::HeadTail) usually
+/// point to line noise, the others to a sensor speaking a different dialect.
+#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
 pub enum ParseError {
+    /// A field that must be 0 or 1 held another value.
     #[error("{0} is out-of-range for boolean (0, 1)")]
     BooleanField(u8),
+    /// A working period longer than 30 minutes.
     #[error("{0} is out-of-range for time [0..=30]")]
     TimeField(u8),
+    /// The frame did not start with 0xAA or end with 0xAB.
     #[error("all messages must start with 0xAA and end with 0xAB")]
     HeadTail,
+    /// The command byte is unknown.
     #[error("{0:#04X} is an unknown command")]
     CommandID(u8),
+    /// The subcommand byte is unknown.
     #[error("{0} is an unknown subcommand")]
     SubCommand(u8),
+    /// The checksum did not match (computed, received).
     #[error("checksum mismatch: {0} != {1}")]
     Checksum(u8, u8),
+    /// The frame had neither query nor reply length.
+    #[error("{0} bytes is not a valid frame length")]
+    Length(usize),
+    /// A device ID containing an 0xFF byte.
+    #[error("{0:#06X} is not a valid device ID")]
+    DeviceID(u16),
+    /// The reply came from another sensor than the one addressed.
+    #[error("reply from unexpected sensor {0:04X}")]
+    SensorID(u16),
+    /// The reply showed a quirk that is not tolerated.
+    #[error("protocol deviation not tolerated: {0}")]
+    Quirk(Quirk),
 }
 
 pub const RECV_BUF_SIZE: usize = 10;
-const SEND_BUF_SIZE: usize = 19;
+pub const SEND_BUF_SIZE: usize = 19;
+
+/// The checksum of a frame: the sum of its data bytes (2..8 or 2..17).
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |acc: u8, i| acc.wrapping_add(*i))
+}
+
+/// The highest value the sensor reports, 999.9 µg/m3.
+pub const MAX_VALUE: u16 = 9_999;
+
+/// Where a measurement came from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// The reply to a query, in polling mode.
+    Query,
+    /// A report the sensor sent unasked, in periodic mode.
+    Periodic,
+}
+
+/// How a measurement was obtained, to judge how far to trust it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct Quality {
+    /// How long the fan ran before the reading, in milliseconds, if known.
+    pub warm_up: Option<u32>,
+    /// Whether the reading was queried or reported unasked, if known.
+    pub source: Option<Source>,
+    /// The ID of the sensor that sent the reading, if known.
+    pub sensor_id: Option<u16>,
+    /// Whether a raw value was at the end of the sensor's range,
+    /// 0.0 or 999.9 µg/m3, where it may be clipped. After a
+    /// [`Filter`](crate::filter::Filter), whether a filtered value is.
+    pub at_limit: bool,
+    /// Whether a calibration was applied.
+    pub calibrated: bool,
+    /// Whether a [`Filter`](crate::filter::Filter) changed the values.
+    pub filtered: bool,
+}
 
 /// A measurement of PM2.5 and PM10 fine dust pollution.
-#[derive(Debug)]
+///
+/// Besides the values, it carries its [`Quality`]: how it was obtained and
+/// processed. Two measurements are equal if their values are, whatever
+/// their qualities; compare [`Measurement::quality()`] for those.
+#[derive(Clone, Copy, Debug)]
 pub struct Measurement {
     pm25: u16,
     pm10: u16,
+    quality: Quality,
+}
+
+impl PartialEq for Measurement {
+    fn eq(&self, other: &Self) -> bool {
+        (self.pm25, self.pm10) == (other.pm25, other.pm10)
+    }
 }
 
+impl Eq for Measurement {}
+
 impl Display for Measurement {
     fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
         let pm25: f32 = self.pm25.into();
@@ -41,13 +114,40 @@ impl Display for Measurement {
 }
 
 impl Measurement {
-    fn from_bytes(data: &[u8]) -> Self {
+    /// Create a measurement from raw PM2.5 and PM10 values (in 0.1 µg/m3).
+    /// Of its quality, only [`Quality::at_limit`] is known.
+    #[must_use]
+    pub const fn new(pm25: u16, pm10: u16) -> Self {
+        let at_limit = matches!(pm25, 0 | MAX_VALUE..) || matches!(pm10, 0 | MAX_VALUE..);
         Self {
-            pm25: u16::from_le_bytes(data[2..4].try_into().expect("slice size is 2")),
-            pm10: u16::from_le_bytes(data[4..6].try_into().expect("slice size is 2")),
+            pm25,
+            pm10,
+            quality: Quality {
+                warm_up: None,
+                source: None,
+                sensor_id: None,
+                at_limit,
+                calibrated: false,
+                filtered: false,
+            },
         }
     }
 
+    const fn from_bytes(data: &[u8; RECV_BUF_SIZE]) -> Self {
+        let mut measurement = Self::new(
+            u16::from_le_bytes([data[2], data[3]]),
+            u16::from_le_bytes([data[4], data[5]]),
+        );
+        measurement.quality.sensor_id = Some(u16::from_be_bytes([data[6], data[7]]));
+        measurement
+    }
+
+    #[cfg(any(test, feature = "mock"))]
+    const fn populate_reply(self, data: &mut [u8; RECV_BUF_SIZE]) {
+        [data[2], data[3]] = self.pm25.to_le_bytes();
+        [data[4], data[5]] = self.pm10.to_le_bytes();
+    }
+
     /// Retrieve the PM2.5 fine dust value. Divide by ten to get µg/m3.
     #[must_use]
     pub const fn pm25(&self) -> u16 {
@@ -59,36 +159,80 @@ impl Measurement {
     pub const fn pm10(&self) -> u16 {
         self.pm10
     }
+
+    /// How the measurement was obtained and processed.
+    #[must_use]
+    pub const fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    /// Replace the quality, e.g. to mark the measurement as filtered.
+    #[must_use]
+    pub const fn with_quality(mut self, quality: Quality) -> Self {
+        self.quality = quality;
+        self
+    }
 }
 
+/// Payload of the command that assigns a new ID to a sensor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct NewDeviceID(u16);
 
 impl NewDeviceID {
-    fn from_bytes(data: &[u8]) -> Self {
-        Self(u16::from_be_bytes(
-            data[6..8].try_into().expect("slice size is 2"),
-        ))
+    /// Assign the ID `id`.
+    ///
+    /// # Errors
+    /// IDs containing an 0xFF byte are reserved (0xFFFF addresses all
+    /// sensors) and return [`ParseError::DeviceID`].
+    pub const fn new(id: u16) -> Result<Self, ParseError> {
+        let bytes = id.to_be_bytes();
+        if bytes[0] == 0xFF || bytes[1] == 0xFF {
+            Err(ParseError::DeviceID(id))
+        } else {
+            Ok(Self(id))
+        }
     }
 
-    fn populate_query(&self, data: &mut [u8]) {
-        let bytes = self.0.to_be_bytes();
+    /// The new ID.
+    #[must_use]
+    pub const fn id(self) -> u16 {
+        self.0
+    }
 
-        if bytes[0] == 0xFF || bytes[1] == 0xFF {
-            unimplemented!("This device ID is invalid")
-        }
+    const fn from_bytes(data: &[u8; RECV_BUF_SIZE]) -> Result<Self, ParseError> {
+        Self::new(u16::from_be_bytes([data[6], data[7]]))
+    }
+
+    const fn from_query_bytes(data: &[u8; SEND_BUF_SIZE]) -> Result<Self, ParseError> {
+        Self::new(u16::from_be_bytes([data[13], data[14]]))
+    }
 
-        data[13] = bytes[0];
-        data[14] = bytes[1];
+    #[cfg(any(test, feature = "mock"))]
+    const fn populate_reply(self, data: &mut [u8; RECV_BUF_SIZE]) {
+        [data[6], data[7]] = self.0.to_be_bytes();
+    }
+
+    const fn populate_query(self, data: &mut [u8; SEND_BUF_SIZE]) {
+        [data[13], data[14]] = self.0.to_be_bytes();
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 enum QueryMode {
     Query,
     Set,
 }
 
+impl QueryMode {
+    const fn verb(self) -> &'static str {
+        match self {
+            Self::Query => "get",
+            Self::Set => "set",
+        }
+    }
+}
+
 impl TryFrom<u8> for QueryMode {
     type Error = ParseError;
 
@@ -101,13 +245,25 @@ impl TryFrom<u8> for QueryMode {
     }
 }
 
+/// Whether the sensor reports on its own or waits to be queried.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum ReportingMode {
+    /// The sensor reports periodically.
     Active,
+    /// The sensor only reports when queried.
     Query,
 }
 
+impl Display for ReportingMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Active => "active",
+            Self::Query => "query",
+        })
+    }
+}
+
 impl TryFrom<u8> for ReportingMode {
     type Error = ParseError;
 
@@ -120,27 +276,27 @@ impl TryFrom<u8> for ReportingMode {
     }
 }
 
+/// Payload of the reporting mode command (query or set).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Reporting {
     query: QueryMode,
     reporting: ReportingMode,
 }
 
 impl Reporting {
-    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        let mode = data[3].try_into()?;
-        let query = data[4].try_into()?;
+    fn from_fields([mode, query]: [u8; 2]) -> Result<Self, ParseError> {
         Ok(Self {
-            query: mode,
-            reporting: query,
+            query: mode.try_into()?,
+            reporting: query.try_into()?,
         })
     }
 
-    fn populate_query(&self, data: &mut [u8]) {
-        data[3] = self.query as u8;
-        data[4] = self.reporting as u8;
+    const fn fields(self) -> [u8; 2] {
+        [self.query as u8, self.reporting as u8]
     }
 
-    #[allow(dead_code)]
+    /// Ask for the current reporting mode.
+    #[must_use]
     pub const fn new_query() -> Self {
         Self {
             query: QueryMode::Query,
@@ -148,6 +304,8 @@ impl Reporting {
         }
     }
 
+    /// Switch to the given reporting mode.
+    #[must_use]
     pub const fn new_set(reporting: ReportingMode) -> Self {
         Self {
             query: QueryMode::Set,
@@ -155,18 +313,32 @@ impl Reporting {
         }
     }
 
+    /// The reporting mode carried by this payload.
+    #[must_use]
     pub const fn mode(self) -> ReportingMode {
         self.reporting
     }
 }
 
+/// Whether the sensor (and its fan) is asleep or working.
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum SleepMode {
+    /// Fan and laser are off.
     Sleep,
+    /// Fan and laser are on.
     Work,
 }
 
+impl Display for SleepMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::Sleep => "sleep",
+            Self::Work => "work",
+        })
+    }
+}
+
 impl TryFrom<u8> for SleepMode {
     type Error = ParseError;
 
@@ -179,27 +351,27 @@ impl TryFrom<u8> for SleepMode {
     }
 }
 
+/// Payload of the sleep/work command (query or set).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Sleep {
     query: QueryMode,
     sleep: SleepMode,
 }
 
 impl Sleep {
-    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        let mode = data[3].try_into()?;
-        let work = data[4].try_into()?;
+    fn from_fields([mode, work]: [u8; 2]) -> Result<Self, ParseError> {
         Ok(Self {
-            query: mode,
-            sleep: work,
+            query: mode.try_into()?,
+            sleep: work.try_into()?,
         })
     }
 
-    fn populate_query(&self, data: &mut [u8]) {
-        data[3] = self.query as u8;
-        data[4] = self.sleep as u8;
+    const fn fields(self) -> [u8; 2] {
+        [self.query as u8, self.sleep as u8]
     }
 
-    #[allow(dead_code)]
+    /// Ask for the current sleep mode.
+    #[must_use]
     pub const fn new_query() -> Self {
         Self {
             query: QueryMode::Query,
@@ -207,6 +379,8 @@ impl Sleep {
         }
     }
 
+    /// Put the sensor to sleep or wake it up.
+    #[must_use]
     pub const fn new_set(sleep: SleepMode) -> Self {
         Self {
             query: QueryMode::Set,
@@ -214,20 +388,23 @@ impl Sleep {
         }
     }
 
+    /// The sleep mode carried by this payload.
+    #[must_use]
     pub const fn sleep_mode(self) -> SleepMode {
         self.sleep
     }
 }
 
+/// Payload of the working period command (query or set).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct WorkingPeriod {
     query: QueryMode,
     minutes: u8,
 }
 
 impl WorkingPeriod {
-    fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
-        let mode = data[3].try_into()?;
-        let time = data[4];
+    fn from_fields([mode, time]: [u8; 2]) -> Result<Self, ParseError> {
+        let mode = mode.try_into()?;
 
         if time > 30 {
             Err(ParseError::TimeField(time))
@@ -239,12 +416,12 @@ impl WorkingPeriod {
         }
     }
 
-    fn populate_query(&self, data: &mut [u8]) {
-        data[3] = self.query as u8;
-        data[4] = self.minutes;
+    const fn fields(self) -> [u8; 2] {
+        [self.query as u8, self.minutes]
     }
 
-    #[allow(dead_code)]
+    /// Ask for the current working period.
+    #[must_use]
     pub const fn new_query() -> Self {
         Self {
             query: QueryMode::Query,
@@ -252,6 +429,8 @@ impl WorkingPeriod {
         }
     }
 
+    /// Set the working period to `minutes` (0 means continuous).
+    #[must_use]
     pub const fn new_set(minutes: u8) -> Self {
         Self {
             query: QueryMode::Set,
@@ -259,13 +438,15 @@ impl WorkingPeriod {
         }
     }
 
+    /// The working period in minutes carried by this payload.
+    #[must_use]
     pub const fn period(&self) -> u8 {
         self.minutes
     }
 }
 
 /// The firmware version of the sensor.
-#[derive(Clone, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct FirmwareVersion {
     year: u8,
     month: u8,
@@ -284,33 +465,81 @@ impl Display for FirmwareVersion {
 }
 
 impl FirmwareVersion {
-    fn from_bytes(data: &[u8]) -> Self {
+    /// Create a firmware version from its date (`year` counts from 2000).
+    #[must_use]
+    pub const fn new(year: u8, month: u8, day: u8) -> Self {
+        Self { year, month, day }
+    }
+
+    const fn from_bytes(data: &[u8; RECV_BUF_SIZE]) -> Self {
         Self {
             year: data[3],
             month: data[4],
             day: data[5],
         }
     }
+
+    #[cfg(any(test, feature = "mock"))]
+    const fn populate_reply(self, data: &mut [u8; RECV_BUF_SIZE]) {
+        data[3] = self.year;
+        data[4] = self.month;
+        data[5] = self.day;
+    }
 }
 
+/// The command carried by a frame, along with its payload.
+///
+/// Queries sent to the sensor leave the `Option` payloads empty;
+/// the sensor's replies fill them in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Kind {
+    /// Get or set the reporting mode.
     ReportingMode(Reporting),
+    /// Request or report a measurement.
     Query(Option<Measurement>),
+    /// Assign a new sensor ID.
     SetDeviceID(NewDeviceID),
+    /// Get or set the sleep mode.
     Sleep(Sleep),
+    /// Get or set the working period.
     WorkingPeriod(WorkingPeriod),
+    /// Request or report the firmware version.
     FWVersion(Option<FirmwareVersion>),
 }
 
 impl Kind {
-    fn parse(data: &[u8]) -> Result<Self, ParseError> {
+    /// Whether this reply answers `command`: the same command, echoing the
+    /// value to set, or the value asked for.
+    pub(crate) fn answers(&self, command: &Self) -> bool {
+        match (self, command) {
+            (Self::Query(Some(_)), Self::Query(None))
+            | (Self::FWVersion(Some(_)), Self::FWVersion(None))
+            | (Self::SetDeviceID(_), Self::SetDeviceID(_)) => true,
+            (Self::ReportingMode(reply), Self::ReportingMode(command)) => {
+                reply.query == command.query
+                    && (reply.query == QueryMode::Query || reply == command)
+            }
+            (Self::Sleep(reply), Self::Sleep(command)) => {
+                reply.query == command.query
+                    && (reply.query == QueryMode::Query || reply == command)
+            }
+            (Self::WorkingPeriod(reply), Self::WorkingPeriod(command)) => {
+                reply.query == command.query
+                    && (reply.query == QueryMode::Query || reply == command)
+            }
+            _ => false,
+        }
+    }
+
+    fn parse(data: &[u8; RECV_BUF_SIZE]) -> Result<Self, ParseError> {
+        let fields = [data[3], data[4]];
         match data[1] {
             0xC0 => Ok(Self::Query(Some(Measurement::from_bytes(data)))),
             0xC5 => match data[2] {
-                2 => Ok(Self::ReportingMode(Reporting::from_bytes(data)?)),
-                5 => Ok(Self::SetDeviceID(NewDeviceID::from_bytes(data))),
-                6 => Ok(Self::Sleep(Sleep::from_bytes(data)?)),
-                8 => Ok(Self::WorkingPeriod(WorkingPeriod::from_bytes(data)?)),
+                2 => Ok(Self::ReportingMode(Reporting::from_fields(fields)?)),
+                5 => Ok(Self::SetDeviceID(NewDeviceID::from_bytes(data)?)),
+                6 => Ok(Self::Sleep(Sleep::from_fields(fields)?)),
+                8 => Ok(Self::WorkingPeriod(WorkingPeriod::from_fields(fields)?)),
                 7 => Ok(Self::FWVersion(Some(FirmwareVersion::from_bytes(data)))),
                 s => Err(ParseError::SubCommand(s)),
             },
@@ -318,25 +547,143 @@ impl Kind {
         }
     }
 
-    fn populate_query(&self, data: &mut [u8]) {
+    fn parse_query(data: &[u8; SEND_BUF_SIZE]) -> Result<Self, ParseError> {
+        let fields = [data[3], data[4]];
+        match data[1] {
+            0xB4 => match data[2] {
+                2 => Ok(Self::ReportingMode(Reporting::from_fields(fields)?)),
+                4 => Ok(Self::Query(None)),
+                5 => Ok(Self::SetDeviceID(NewDeviceID::from_query_bytes(data)?)),
+                6 => Ok(Self::Sleep(Sleep::from_fields(fields)?)),
+                8 => Ok(Self::WorkingPeriod(WorkingPeriod::from_fields(fields)?)),
+                7 => Ok(Self::FWVersion(None)),
+                s => Err(ParseError::SubCommand(s)),
+            },
+            c => Err(ParseError::CommandID(c)),
+        }
+    }
+
+    /// Describe this kind as a command to the sensor,
+    /// e.g. "set sleep mode: work".
+    #[must_use]
+    pub const fn describe_query(&self) -> impl Display + '_ {
+        Described(self, false)
+    }
+
+    /// Describe this kind as a reply from the sensor,
+    /// e.g. "sleep mode is work (set)".
+    #[must_use]
+    pub const fn describe_reply(&self) -> impl Display + '_ {
+        Described(self, true)
+    }
+
+    fn describe(self, f: &mut Formatter<'_>, from_sensor: bool) -> core::fmt::Result {
+        match (self, from_sensor) {
+            (Self::ReportingMode(r), false) => match r.query {
+                QueryMode::Query => f.write_str("get reporting mode"),
+                QueryMode::Set => write!(f, "set reporting mode: {}", r.reporting),
+            },
+            (Self::ReportingMode(r), true) => {
+                write!(f, "reporting mode is {} ({})", r.reporting, r.query.verb())
+            }
+            (Self::Query(None), _) => f.write_str("query measurement"),
+            (Self::Query(Some(m)), _) => write!(f, "measurement: {m}"),
+            (Self::SetDeviceID(d), false) => write!(f, "set device id: {:04X}", d.0),
+            (Self::SetDeviceID(d), true) => write!(f, "device id changed to {:04X}", d.0),
+            (Self::Sleep(s), false) => match s.query {
+                QueryMode::Query => f.write_str("get sleep mode"),
+                QueryMode::Set => write!(f, "set sleep mode: {}", s.sleep),
+            },
+            (Self::Sleep(s), true) => write!(f, "sleep mode is {} ({})", s.sleep, s.query.verb()),
+            (Self::WorkingPeriod(w), false) if matches!(w.query, QueryMode::Query) => {
+                f.write_str("get working period")
+            }
+            (Self::WorkingPeriod(w), from_sensor) => {
+                f.write_str(if from_sensor {
+                    "working period is "
+                } else {
+                    "set working period: "
+                })?;
+                match w.minutes {
+                    0 => f.write_str("continuous")?,
+                    m => write!(f, "{m} min")?,
+                }
+                if from_sensor {
+                    write!(f, " ({})", w.query.verb())?;
+                }
+                Ok(())
+            }
+            (Self::FWVersion(None), _) => f.write_str("get firmware version"),
+            (Self::FWVersion(Some(v)), _) => write!(f, "firmware version {v}"),
+        }
+    }
+
+    /// The bytes of a reply the datasheet marks as reserved.
+    const fn reserved(self, data: &[u8; RECV_BUF_SIZE]) -> [u8; 3] {
+        match self {
+            Self::ReportingMode(_) | Self::Sleep(_) | Self::WorkingPeriod(_) => [data[5], 0, 0],
+            Self::SetDeviceID(_) => [data[3], data[4], data[5]],
+            Self::Query(_) | Self::FWVersion(_) => [0; 3],
+        }
+    }
+
+    /// The data fields shared by queries and replies (bytes 3 and 4).
+    const fn fields(self) -> Option<[u8; 2]> {
+        match self {
+            Self::ReportingMode(r) => Some(r.fields()),
+            Self::Sleep(s) => Some(s.fields()),
+            Self::WorkingPeriod(w) => Some(w.fields()),
+            Self::Query(_) | Self::SetDeviceID(_) | Self::FWVersion(_) => None,
+        }
+    }
+
+    #[cfg(any(test, feature = "mock"))]
+    const fn populate_reply(self, data: &mut [u8; RECV_BUF_SIZE]) {
+        if let Some(fields) = self.fields() {
+            [data[3], data[4]] = fields;
+        }
+
         let subcommand = match self {
-            Self::ReportingMode(r) => {
-                r.populate_query(data);
-                2
+            Self::Query(m) => {
+                if let Some(m) = m {
+                    m.populate_reply(data);
+                }
+                data[1] = 0xC0;
+                return;
             }
-            Self::Query(_) => 4,
+            Self::ReportingMode(_) => 2,
             Self::SetDeviceID(d) => {
-                d.populate_query(data);
+                d.populate_reply(data);
                 5
             }
-            Self::Sleep(s) => {
-                s.populate_query(data);
-                6
+            Self::Sleep(_) => 6,
+            Self::WorkingPeriod(_) => 8,
+            Self::FWVersion(f) => {
+                if let Some(f) = f {
+                    f.populate_reply(data);
+                }
+                7
             }
-            Self::WorkingPeriod(w) => {
-                w.populate_query(data);
-                8
+        };
+
+        data[1] = 0xC5;
+        data[2] = subcommand;
+    }
+
+    const fn populate_query(self, data: &mut [u8; SEND_BUF_SIZE]) {
+        if let Some(fields) = self.fields() {
+            [data[3], data[4]] = fields;
+        }
+
+        let subcommand = match self {
+            Self::ReportingMode(_) => 2,
+            Self::Query(_) => 4,
+            Self::SetDeviceID(d) => {
+                d.populate_query(data);
+                5
             }
+            Self::Sleep(_) => 6,
+            Self::WorkingPeriod(_) => 8,
             Self::FWVersion(_) => 7,
         };
 
@@ -345,41 +692,151 @@ impl Kind {
     }
 }
 
+struct Described<'a>(&'a Kind, bool);
+
+impl Display for Described<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.0.describe(f, self.1)
+    }
+}
+
+#[derive(Debug)]
 pub struct Message {
     pub kind: Kind,
     pub sensor_id: Option<u16>,
 }
 
 impl Message {
+    #[cfg(test)]
     pub fn parse_reply(data: &[u8; RECV_BUF_SIZE]) -> Result<Self, ParseError> {
+        Self::parse_reply_with(data, None, Quirks::default()).map(|(msg, _)| msg)
+    }
+
+    /// Parse a reply, tolerating the quirks in `tolerate`.
+    /// If `expected_id` is given, the reply must come from that sensor.
+    /// Returns the message and the quirks it showed.
+    pub fn parse_reply_with(
+        data: &[u8; RECV_BUF_SIZE],
+        expected_id: Option<u16>,
+        tolerate: Quirks,
+    ) -> Result<(Self, Quirks), ParseError> {
         // checksum = sum of data bytes
-        let chksum = data[2..8].iter().fold(0, |acc: u8, i| acc.wrapping_add(*i));
+        let chksum = checksum(&data[2..8]);
         if chksum != data[8] {
             return Err(ParseError::Checksum(chksum, data[8]));
         }
 
         let msg = Kind::parse(data)?;
-        let sensor_id = u16::from_be_bytes(data[6..8].try_into().expect("slice size is 2"));
+        let sensor_id = u16::from_be_bytes([data[6], data[7]]);
 
-        // check head and tail
-        if data[0] != 0xAA || data[9] != 0xAB {
-            match &msg {
-                Kind::Sleep(s) => {
-                    // quirk: sleep reply messages end with 0xFF?!
-                    if matches!(s.query, QueryMode::Set) && data[9] != 0xFF {
-                        return Err(ParseError::HeadTail);
-                    }
-                }
-                _ => return Err(ParseError::HeadTail),
+        if data[0] != 0xAA {
+            return Err(ParseError::HeadTail);
+        }
+
+        let mut seen = Quirks::STRICT;
+        if data[9] != 0xAB {
+            let sleep_set = matches!(&msg, Kind::Sleep(s) if matches!(s.query, QueryMode::Set));
+            seen = seen.with(if sleep_set && data[9] == 0xFF {
+                Quirk::SleepReplyTail
+            } else {
+                Quirk::WrongTail
+            });
+        }
+        if msg.reserved(data).iter().any(|&b| b != 0) {
+            seen = seen.with(Quirk::ReservedBytes);
+        }
+        if let Some(expected) = expected_id
+            && sensor_id != expected
+        {
+            if sensor_id == expected.swap_bytes() {
+                seen = seen.with(Quirk::SwappedId);
+            } else if sensor_id == 0xFFFF {
+                seen = seen.with(Quirk::BroadcastId);
+            } else {
+                return Err(ParseError::SensorID(sensor_id));
             }
         }
 
+        if let Some(quirk) = seen.difference(tolerate).iter().next() {
+            return Err(ParseError::Quirk(quirk));
+        }
+
+        Ok((
+            Self {
+                kind: msg,
+                sensor_id: Some(sensor_id),
+            },
+            seen,
+        ))
+    }
+
+    #[cfg(any(test, feature = "mock"))]
+    pub fn parse_query(data: &[u8; SEND_BUF_SIZE]) -> Result<Self, ParseError> {
+        // checksum = sum of data bytes
+        let chksum = checksum(&data[2..17]);
+        if chksum != data[17] {
+            return Err(ParseError::Checksum(chksum, data[17]));
+        }
+
+        let msg = Kind::parse_query(data)?;
+        let sensor_id = match u16::from_be_bytes([data[15], data[16]]) {
+            0xFFFF => None,
+            id => Some(id),
+        };
+
+        // check head and tail
+        if data[0] != 0xAA || data[18] != 0xAB {
+            return Err(ParseError::HeadTail);
+        }
+
         Ok(Self {
             kind: msg,
-            sensor_id: Some(sensor_id),
+            sensor_id,
         })
     }
 
+    /// Decode a query or reply frame without checking head, tail or checksum.
+    pub fn inspect(data: &[u8]) -> Result<Self, ParseError> {
+        if let Ok(data) = <&[u8; SEND_BUF_SIZE]>::try_from(data) {
+            Ok(Self {
+                kind: Kind::parse_query(data)?,
+                sensor_id: match u16::from_be_bytes([data[15], data[16]]) {
+                    0xFFFF => None,
+                    id => Some(id),
+                },
+            })
+        } else if let Ok(data) = <&[u8; RECV_BUF_SIZE]>::try_from(data) {
+            Ok(Self {
+                kind: Kind::parse(data)?,
+                sensor_id: Some(u16::from_be_bytes([data[6], data[7]])),
+            })
+        } else {
+            Err(ParseError::Length(data.len()))
+        }
+    }
+
+    /// Describe the message for humans, e.g. "set sleep mode: work".
+    /// Replies (`from_sensor`) are worded as statements about the sensor.
+    pub fn describe(&self, f: &mut Formatter<'_>, from_sensor: bool) -> core::fmt::Result {
+        self.kind.describe(f, from_sensor)
+    }
+
+    #[cfg(any(test, feature = "mock"))]
+    pub fn create_reply(&self) -> [u8; RECV_BUF_SIZE] {
+        let mut output = [0u8; RECV_BUF_SIZE];
+        output[0] = 0xAA;
+        output[9] = 0xAB;
+
+        [output[6], output[7]] = self.sensor_id.unwrap_or(0xFFFF).to_be_bytes();
+
+        self.kind.populate_reply(&mut output);
+
+        // calculate checksum
+        output[8] = checksum(&output[2..8]);
+
+        output
+    }
+
     pub fn create_query(&self) -> [u8; SEND_BUF_SIZE] {
         let mut output = [0u8; SEND_BUF_SIZE];
         output[0] = 0xAA;
@@ -387,27 +844,15 @@ impl Message {
 
         self.kind.populate_query(&mut output);
 
-        match self.sensor_id {
-            None => {
-                output[15] = 0xFF;
-                output[16] = 0xFF;
-            }
-            Some(id) => {
-                let bytes = id.to_be_bytes();
-                output[15] = bytes[0];
-                output[16] = bytes[1];
-            }
-        }
+        [output[15], output[16]] = self.sensor_id.unwrap_or(0xFFFF).to_be_bytes();
 
         // calculate checksum
-        let chksum = output[2..17]
-            .iter()
-            .fold(0, |acc: u8, i| acc.wrapping_add(*i));
-        output[17] = chksum;
+        output[17] = checksum(&output[2..17]);
 
         output
     }
 
+    #[must_use]
     pub const fn new(kind: Kind, target_sensor: Option<u16>) -> Self {
         Self {
             kind,
@@ -420,9 +865,13 @@ impl Message {
 /// Tests from the control protocol PDF
 mod tests {
     use super::{
-        FirmwareVersion, Kind, Measurement, Message, NewDeviceID, QueryMode, RECV_BUF_SIZE,
-        Reporting, ReportingMode, SEND_BUF_SIZE, Sleep, SleepMode, WorkingPeriod,
+        FirmwareVersion, Kind, Measurement, Message, NewDeviceID, ParseError, Quality, QueryMode,
+        Quirk, Quirks, RECV_BUF_SIZE, Reporting, ReportingMode, SEND_BUF_SIZE, Sleep, SleepMode,
+        WorkingPeriod, checksum,
     };
+    use alloc::format;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
 
     // tests for the reporting mode (active / query), p.4
     #[test]
@@ -528,12 +977,57 @@ mod tests {
             msg.kind,
             Kind::Query(Some(Measurement {
                 pm25: 1236,
-                pm10: 2618
+                pm10: 2618,
+                ..
             }))
         ));
         assert_eq!(msg.sensor_id, Some(0xA160));
     }
 
+    #[test]
+    fn answers() {
+        let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+        let sleep = Kind::Sleep(Sleep::new_set(SleepMode::Sleep));
+        let get = Kind::Sleep(Sleep::new_query());
+        assert!(wake.answers(&wake));
+        assert!(!wake.answers(&sleep));
+        assert!(!wake.answers(&get));
+        assert!(Kind::Sleep(Sleep::new_query()).answers(&get));
+        let data = Kind::Query(Some(Measurement::new(1, 2)));
+        assert!(data.answers(&Kind::Query(None)));
+        assert!(!data.answers(&wake));
+        assert!(!Kind::FWVersion(None).answers(&Kind::FWVersion(None)));
+        let period = Kind::WorkingPeriod(WorkingPeriod::new_set(5));
+        assert!(period.answers(&period));
+        assert!(!period.answers(&Kind::WorkingPeriod(WorkingPeriod::new_set(6))));
+    }
+
+    #[test]
+    fn measurement_quality() {
+        const MSG: [u8; RECV_BUF_SIZE] =
+            [0xAA, 0xC0, 0x0F, 0x27, 0x3A, 0x0A, 0xA1, 0x60, 0x7B, 0xAB];
+        let Kind::Query(Some(m)) = Message::parse_reply(&MSG).unwrap().kind else {
+            panic!("not a measurement");
+        };
+        // 999.9 µg/m3
+        assert_eq!(m.pm25(), 9_999);
+        let quality = m.quality();
+        assert_eq!(quality.sensor_id, Some(0xA160));
+        assert!(quality.at_limit);
+        assert_eq!((quality.source, quality.warm_up), (None, None));
+
+        assert!(Measurement::new(0, 10).quality().at_limit);
+        assert!(!Measurement::new(1, 9_998).quality().at_limit);
+        let filtered = Quality {
+            filtered: true,
+            ..Quality::default()
+        };
+        let m = Measurement::new(1, 2).with_quality(filtered);
+        assert_eq!(m.quality(), filtered);
+        assert_eq!(m, Measurement::new(1, 2));
+        assert!(size_of::<Measurement>() <= 20);
+    }
+
     // tests setting the device ID, p.7
     #[test]
     fn device_id_send_command() {
@@ -541,7 +1035,7 @@ mod tests {
             0xAA, 0xB4, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xA0,
             0x01, 0xA1, 0x60, 0xA7, 0xAB,
         ];
-        let query = NewDeviceID(0xA001);
+        let query = NewDeviceID::new(0xA001).unwrap();
         let msg = Message::new(Kind::SetDeviceID(query), Some(0xA160));
         assert_eq!(msg.create_query(), EXPECTED);
     }
@@ -764,4 +1258,213 @@ mod tests {
         ));
         assert_eq!(msg.sensor_id, Some(0xA160));
     }
+
+    // encoding replies and decoding queries, as needed by the mock
+    #[test]
+    fn replies_roundtrip() {
+        const MSGS: [[u8; RECV_BUF_SIZE]; 5] = [
+            [0xAA, 0xC5, 0x02, 0x01, 0x01, 0x00, 0xA1, 0x60, 0x05, 0xAB],
+            [0xAA, 0xC0, 0xD4, 0x04, 0x3A, 0x0A, 0xA1, 0x60, 0x1D, 0xAB],
+            [0xAA, 0xC5, 0x05, 0x00, 0x00, 0x00, 0xA0, 0x01, 0xA6, 0xAB],
+            [0xAA, 0xC5, 0x08, 0x00, 0x02, 0x00, 0xA1, 0x60, 0x0B, 0xAB],
+            [0xAA, 0xC5, 0x07, 0x0F, 0x07, 0x0A, 0xA1, 0x60, 0x28, 0xAB],
+        ];
+
+        for data in MSGS {
+            let msg = Message::parse_reply(&data).unwrap();
+            assert_eq!(msg.create_reply(), data);
+        }
+    }
+
+    #[test]
+    fn queries_roundtrip() {
+        let queries = [
+            Message::new(Kind::ReportingMode(Reporting::new_query()), None),
+            Message::new(Kind::Query(None), Some(0xA160)),
+            Message::new(
+                Kind::SetDeviceID(NewDeviceID::new(0xA001).unwrap()),
+                Some(0xA160),
+            ),
+            Message::new(Kind::Sleep(Sleep::new_set(SleepMode::Work)), None),
+            Message::new(Kind::WorkingPeriod(WorkingPeriod::new_set(5)), Some(0xA160)),
+            Message::new(Kind::FWVersion(None), None),
+        ];
+
+        for msg in queries {
+            let data = msg.create_query();
+            let parsed = Message::parse_query(&data).unwrap();
+            assert_eq!(parsed.sensor_id, msg.sensor_id);
+            assert_eq!(parsed.create_query(), data);
+        }
+    }
+
+    fn reply_frame(bytes: [u8; 7]) -> [u8; RECV_BUF_SIZE] {
+        let mut data = [0xAA, 0, 0, 0, 0, 0, 0, 0, 0, 0xAB];
+        data[1..8].copy_from_slice(&bytes);
+        data[8] = checksum(&data[2..8]);
+        data
+    }
+
+    fn query_frame(command: u8, subcommand: u8, fields: [u8; 2]) -> [u8; SEND_BUF_SIZE] {
+        let mut data = [0u8; SEND_BUF_SIZE];
+        [data[0], data[1], data[2], data[3], data[4]] =
+            [0xAA, command, subcommand, fields[0], fields[1]];
+        [data[15], data[16], data[18]] = [0xA1, 0x60, 0xAB];
+        data[17] = checksum(&data[2..17]);
+        data
+    }
+
+    // exhaustive checks of the command and field bytes, with valid checksums
+    #[test]
+    fn every_reply_command() {
+        for [command, subcommand] in (0..=u16::MAX).map(u16::to_be_bytes) {
+            let res =
+                Message::parse_reply(&reply_frame([command, subcommand, 0, 0, 0, 0xA1, 0x60]));
+            match (command, subcommand) {
+                (0xC0, _) | (0xC5, 2 | 5 | 6 | 7 | 8) => assert!(res.is_ok()),
+                (0xC5, s) => assert_eq!(res.unwrap_err(), ParseError::SubCommand(s)),
+                (c, _) => assert_eq!(res.unwrap_err(), ParseError::CommandID(c)),
+            }
+        }
+    }
+
+    #[test]
+    fn every_reply_field() {
+        for [a, b] in (0..=u16::MAX).map(u16::to_be_bytes) {
+            for subcommand in [2, 5, 6, 7, 8] {
+                let data = reply_frame([0xC5, subcommand, a, b, 0, a, b]);
+                let valid = match subcommand {
+                    2 | 6 => a <= 1 && b <= 1,
+                    5 => a != 0xFF && b != 0xFF,
+                    8 => a <= 1 && b <= 30,
+                    _ => true,
+                };
+                let res = Message::parse_reply(&data);
+                assert_eq!(res.is_ok(), valid, "{data:02X?}");
+                if let Ok(msg) = res {
+                    // reserved bytes are not kept, everything else is
+                    let again = msg.create_reply();
+                    assert_eq!(Message::parse_reply(&again).unwrap().create_reply(), again);
+                }
+            }
+            let data = reply_frame([0xC0, a, b, a, b, 0xA1, 0x60]);
+            assert_eq!(Message::parse_reply(&data).unwrap().create_reply(), data);
+        }
+    }
+
+    #[test]
+    fn every_query_command() {
+        for [command, subcommand] in (0..=u16::MAX).map(u16::to_be_bytes) {
+            let res = Message::parse_query(&query_frame(command, subcommand, [0, 0]));
+            match (command, subcommand) {
+                (0xB4, 2 | 4 | 5 | 6 | 7 | 8) => assert!(res.is_ok()),
+                (0xB4, s) => assert_eq!(res.unwrap_err(), ParseError::SubCommand(s)),
+                (c, _) => assert_eq!(res.unwrap_err(), ParseError::CommandID(c)),
+            }
+        }
+    }
+
+    #[test]
+    fn every_query_field() {
+        for [a, b] in (0..=u16::MAX).map(u16::to_be_bytes) {
+            for subcommand in [2, 4, 6, 7, 8] {
+                let res = Message::parse_query(&query_frame(0xB4, subcommand, [a, b]));
+                let valid = match subcommand {
+                    2 | 6 => a <= 1 && b <= 1,
+                    8 => a <= 1 && b <= 30,
+                    _ => true,
+                };
+                assert_eq!(res.is_ok(), valid);
+            }
+
+            let mut data = query_frame(0xB4, 5, [0, 0]);
+            [data[13], data[14]] = [a, b];
+            data[17] = checksum(&data[2..17]);
+            let res = Message::parse_query(&data);
+            assert_eq!(res.is_ok(), a != 0xFF && b != 0xFF);
+            if let Ok(msg) = res {
+                assert_eq!(msg.create_query(), data);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_device_ids() {
+        for id in 0..=u16::MAX {
+            let [hi, lo] = id.to_be_bytes();
+            match NewDeviceID::new(id) {
+                Ok(new) => assert_eq!(new.0, id),
+                Err(e) => {
+                    assert!(hi == 0xFF || lo == 0xFF);
+                    assert_eq!(e, ParseError::DeviceID(id));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn reply_quirks() {
+        // sleep reply with the 0xFF tail, reserved byte set, from sensor 0x60A1
+        let mut data = reply_frame([0xC5, 6, 1, 1, 0x42, 0x60, 0xA1]);
+        data[9] = 0xFF;
+
+        let (_, seen) = Message::parse_reply_with(&data, Some(0xA160), Quirks::LENIENT).unwrap();
+        let expected = Quirks::from(Quirk::SleepReplyTail)
+            .with(Quirk::ReservedBytes)
+            .with(Quirk::SwappedId);
+        assert_eq!(seen, expected);
+
+        for quirk in expected.iter() {
+            let res = Message::parse_reply_with(&data, Some(0xA160), expected.without(quirk));
+            assert_eq!(res.unwrap_err(), ParseError::Quirk(quirk));
+        }
+        assert_eq!(
+            Message::parse_reply_with(&data, None, expected).unwrap().1,
+            expected.without(Quirk::SwappedId)
+        );
+        assert_eq!(
+            Message::parse_reply_with(&data, Some(0x1234), Quirks::LENIENT).unwrap_err(),
+            ParseError::SensorID(0x60A1)
+        );
+
+        // the 0xFF tail only counts as a sleep quirk on "set sleep" replies
+        let mut data = reply_frame([0xC0, 1, 0, 2, 0, 0xFF, 0xFF]);
+        data[9] = 0xFF;
+        let (_, seen) = Message::parse_reply_with(&data, Some(0xA160), Quirks::LENIENT).unwrap();
+        assert_eq!(
+            seen,
+            Quirks::from(Quirk::WrongTail).with(Quirk::BroadcastId)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn parse_any_reply(data in any::<[u8; RECV_BUF_SIZE]>()) {
+            if let Ok(msg) = Message::parse_reply(&data) {
+                let again = msg.create_reply();
+                prop_assert_eq!(Message::parse_reply(&again).unwrap().create_reply(), again);
+            }
+        }
+
+        #[test]
+        fn parse_any_query(data in any::<[u8; SEND_BUF_SIZE]>()) {
+            if let Ok(msg) = Message::parse_query(&data) {
+                let again = msg.create_query();
+                prop_assert_eq!(Message::parse_query(&again).unwrap().create_query(), again);
+            }
+        }
+
+        #[test]
+        fn inspect_anything(data in vec(any::<u8>(), 0..32)) {
+            if let Ok(msg) = Message::inspect(&data) {
+                prop_assert!(data.len() == RECV_BUF_SIZE || data.len() == SEND_BUF_SIZE);
+                let text = if data.len() == RECV_BUF_SIZE {
+                    format!("{}", msg.kind.describe_reply())
+                } else {
+                    format!("{}", msg.kind.describe_query())
+                };
+                prop_assert!(!text.is_empty());
+            }
+        }
+    }
 }
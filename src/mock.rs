@@ -0,0 +1,508 @@
+//! Scripted serial port and delay mocks for unit tests.
+//!
+//! A [`Mock`] is built from a list of [`Transaction`]s, each naming a frame
+//! the driver is expected to send and the bytes the sensor answers with.
+//! If the driver sends anything else, the mock panics and prints the expected
+//! and actual frames side by side. [`MockDelay`] returns immediately and
+//! records every requested delay, so tests can check timing without waiting.
+//!
+//! Both mocks implement the blocking as well as the async traits, so they
+//! work with either driver.
+//!
+//! ```ignore
+//! use sds011::mock::{Kind, Mock, MockDelay, Sleep, SleepMode, Transaction};
+//! use sds011::{Config, SDS011};
+//!
+//! let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+//! let mut serial = Mock::new([
+//!     Transaction::command(wake, None).reply(Kind::Sleep(Sleep::new_set(SleepMode::Work)), 0xA160),
+//!     // ...
+//! ]);
+//! let mut delay = MockDelay::new();
+//!
+//! let sensor = SDS011::new(&mut serial, Config::default());
+//! let sensor = sensor.init(&mut delay).await.unwrap();
+//! serial.done();
+//! ```
+
+#![allow(clippy::panic, reason = "script violations fail the test by panicking")]
+
+use crate::message::{Message, SEND_BUF_SIZE};
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write as _;
+use core::time::Duration;
+use embedded_io::{ErrorKind, ErrorType};
+
+pub use crate::message::{
+    Kind, NewDeviceID, Reporting, ReportingMode, Sleep, SleepMode, WorkingPeriod,
+};
+
+/// One step of a scripted exchange: a frame the driver must send,
+/// followed by the bytes the sensor answers with.
+#[derive(Debug, Clone)]
+pub struct Transaction {
+    expect: Option<[u8; SEND_BUF_SIZE]>,
+    reply: Vec<u8>,
+}
+
+impl Transaction {
+    /// Expect the driver to send a `kind` command to `target`
+    /// (`None` means broadcast).
+    #[must_use]
+    pub fn command(kind: Kind, target: Option<u16>) -> Self {
+        Self::raw(Message::new(kind, target).create_query())
+    }
+
+    /// Expect the driver to send exactly this frame.
+    #[must_use]
+    pub const fn raw(frame: [u8; SEND_BUF_SIZE]) -> Self {
+        Self {
+            expect: Some(frame),
+            reply: Vec::new(),
+        }
+    }
+
+    /// Expect no frame at all; the sensor sends its reply unprompted,
+    /// as it does in periodic mode.
+    #[must_use]
+    pub const fn unsolicited() -> Self {
+        Self {
+            expect: None,
+            reply: Vec::new(),
+        }
+    }
+
+    /// Append a well-formed `kind` reply from sensor `sensor_id`.
+    #[must_use]
+    pub fn reply(self, kind: Kind, sensor_id: u16) -> Self {
+        self.reply_raw(&Message::new(kind, Some(sensor_id)).create_reply())
+    }
+
+    /// Append raw bytes to the reply, e.g. to simulate a corrupted line.
+    #[must_use]
+    pub fn reply_raw(mut self, bytes: &[u8]) -> Self {
+        self.reply.extend_from_slice(bytes);
+        self
+    }
+}
+
+/// A serial port that plays back a script of [`Transaction`]s.
+///
+/// Frames sent by the driver are compared against the script once complete;
+/// a mismatch panics. Reads return the scripted replies, and `Ok(0)` (EOF)
+/// if there is nothing left to read.
+#[derive(Debug)]
+pub struct Mock {
+    script: VecDeque<Transaction>,
+    step: usize,
+    written: Vec<u8>,
+    pending: VecDeque<u8>,
+}
+
+impl Mock {
+    /// Create a mock that expects the given transactions, in order.
+    pub fn new(transactions: impl IntoIterator<Item = Transaction>) -> Self {
+        Self {
+            script: transactions.into_iter().collect(),
+            step: 0,
+            written: Vec::new(),
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Assert that the whole script was played and every reply byte was read.
+    ///
+    /// # Panics
+    /// If transactions, a partially written frame or unread replies remain.
+    pub fn done(&self) {
+        assert!(
+            self.script.is_empty(),
+            "{} of {} scripted transactions were never performed",
+            self.script.len(),
+            self.script.len() + self.step
+        );
+        assert!(
+            self.written.is_empty(),
+            "driver left a partial frame: {}",
+            hex(&self.written)
+        );
+        assert!(
+            self.pending.is_empty(),
+            "driver left {} reply bytes unread",
+            self.pending.len()
+        );
+    }
+
+    fn next(&mut self) -> Option<Transaction> {
+        self.step += 1;
+        self.script.pop_front()
+    }
+
+    fn on_write(&mut self, buf: &[u8]) -> usize {
+        let n = buf.len().min(SEND_BUF_SIZE - self.written.len());
+        self.written.extend_from_slice(&buf[..n]);
+        if self.written.len() < SEND_BUF_SIZE {
+            return n;
+        }
+
+        let mut actual = [0u8; SEND_BUF_SIZE];
+        actual.copy_from_slice(&self.written);
+        self.written.clear();
+
+        match self.next() {
+            Some(Transaction {
+                expect: Some(expected),
+                reply,
+            }) => {
+                assert!(expected == actual, "{}", self.diff(&expected, &actual));
+                self.pending.extend(reply);
+            }
+            Some(Transaction { expect: None, .. }) => panic!(
+                "transaction {}: expected the sensor to report unprompted, but the driver sent\n{}",
+                self.step,
+                describe(&actual)
+            ),
+            None => panic!(
+                "script exhausted after {} transactions, but the driver sent\n{}",
+                self.step - 1,
+                describe(&actual)
+            ),
+        }
+
+        n
+    }
+
+    fn on_read(&mut self, buf: &mut [u8]) -> usize {
+        let unsolicited = matches!(self.script.front(), Some(t) if t.expect.is_none());
+        if self.pending.is_empty()
+            && self.written.is_empty()
+            && unsolicited
+            && let Some(t) = self.next()
+        {
+            self.pending.extend(t.reply);
+        }
+
+        let n = buf.len().min(self.pending.len());
+        for (dst, src) in buf.iter_mut().zip(self.pending.drain(..n)) {
+            *dst = src;
+        }
+        n
+    }
+
+    fn diff(&self, expected: &[u8], actual: &[u8]) -> String {
+        let mut marks = String::new();
+        for (e, a) in expected.iter().zip(actual) {
+            marks.push_str(if e == a { "   " } else { "^^ " });
+        }
+
+        let mut out = String::new();
+        _ = writeln!(
+            out,
+            "transaction {}: driver sent an unexpected frame",
+            self.step
+        );
+        _ = writeln!(out, "expected: {}", hex(expected));
+        _ = writeln!(out, "  actual: {}", hex(actual));
+        _ = writeln!(out, "          {}", marks.trim_end());
+        _ = writeln!(out, "expected: {}", decode(expected));
+        _ = write!(out, "  actual: {}", decode(actual));
+        out
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for b in bytes {
+        _ = write!(out, "{b:02X} ");
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+fn decode(frame: &[u8]) -> String {
+    let mut out = String::new();
+    match frame.try_into().map(Message::parse_query) {
+        Ok(Ok(msg)) => _ = write!(out, "{msg:?}"),
+        Ok(Err(e)) => _ = write!(out, "<{e}>"),
+        Err(_) => _ = write!(out, "<{} bytes>", frame.len()),
+    }
+    out
+}
+
+fn describe(frame: &[u8]) -> String {
+    let mut out = hex(frame);
+    _ = write!(out, "\n{}", decode(frame));
+    out
+}
+
+impl ErrorType for Mock {
+    type Error = ErrorKind;
+}
+
+impl embedded_io::Read for Mock {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(self.on_read(buf))
+    }
+}
+
+impl embedded_io::Write for Mock {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Ok(self.on_write(buf))
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// Only reply bytes the sensor has already sent are ready;
+/// unsolicited reports arrive with the next read.
+impl embedded_io::ReadReady for Mock {
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        Ok(!self.pending.is_empty())
+    }
+}
+
+impl embedded_io_async::Read for Mock {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        Ok(self.on_read(buf))
+    }
+}
+
+impl embedded_io_async::Write for Mock {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        Ok(self.on_write(buf))
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// A delay provider that returns immediately and records every request.
+#[derive(Debug, Default)]
+pub struct MockDelay {
+    delays: Vec<Duration>,
+}
+
+impl MockDelay {
+    /// Create a delay with an empty record.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { delays: Vec::new() }
+    }
+
+    /// All delays requested so far, in order.
+    #[must_use]
+    pub fn delays(&self) -> &[Duration] {
+        &self.delays
+    }
+
+    /// The sum of all delays requested so far.
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.delays.iter().sum()
+    }
+
+    /// Forget all recorded delays.
+    pub fn clear(&mut self) {
+        self.delays.clear();
+    }
+}
+
+impl embedded_hal::delay::DelayNs for MockDelay {
+    fn delay_ns(&mut self, ns: u32) {
+        self.delays.push(Duration::from_nanos(ns.into()));
+    }
+
+    fn delay_us(&mut self, us: u32) {
+        self.delays.push(Duration::from_micros(us.into()));
+    }
+
+    fn delay_ms(&mut self, ms: u32) {
+        self.delays.push(Duration::from_millis(ms.into()));
+    }
+}
+
+impl embedded_hal_async::delay::DelayNs for MockDelay {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.delays.push(Duration::from_nanos(ns.into()));
+    }
+
+    async fn delay_us(&mut self, us: u32) {
+        self.delays.push(Duration::from_micros(us.into()));
+    }
+
+    async fn delay_ms(&mut self, ms: u32) {
+        self.delays.push(Duration::from_millis(ms.into()));
+    }
+}
+
+/// Scripts and test doubles shared by the driver's tests.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::{Kind, Mock, Reporting, ReportingMode, Sleep, SleepMode, Transaction};
+    use crate::{FirmwareVersion, Measurement};
+    use alloc::vec::Vec;
+    use core::pin::pin;
+    use core::sync::atomic::AtomicUsize;
+    use core::sync::atomic::Ordering::Relaxed;
+    use core::task::{Context, Waker};
+
+    // the flavor under test, blocking with the `sync` feature
+    #[cfg(not(feature = "sync"))]
+    pub use crate::asynch::{DynSDS011, SDS011, Supervisor};
+    #[cfg(feature = "sync")]
+    pub use crate::blocking::{DynSDS011, SDS011, Supervisor};
+
+    /// The ID of the scripted sensor.
+    pub const ID: u16 = 0xA160;
+
+    pub fn sleep_set(mode: SleepMode, target: Option<u16>) -> Transaction {
+        Transaction::command(Kind::Sleep(Sleep::new_set(mode)), target)
+            .reply(Kind::Sleep(Sleep::new_set(mode)), ID)
+    }
+
+    pub fn query_mode(target: Option<u16>) -> Transaction {
+        let mode = Kind::ReportingMode(Reporting::new_set(ReportingMode::Query));
+        Transaction::command(mode, target).reply(mode, ID)
+    }
+
+    /// What `init()` sends to `target`, ending with the sensor asleep.
+    fn init_to(target: Option<u16>) -> [Transaction; 4] {
+        let firmware = Kind::FWVersion(Some(FirmwareVersion::new(15, 7, 10)));
+        [
+            sleep_set(SleepMode::Work, target),
+            query_mode(target),
+            Transaction::command(Kind::FWVersion(None), target).reply(firmware, ID),
+            sleep_set(SleepMode::Sleep, target),
+        ]
+    }
+
+    /// What `init()` sends to a sensor not yet known.
+    pub fn init_script() -> Vec<Transaction> {
+        init_to(None).to_vec()
+    }
+
+    /// `init_script()`, addressed to the sensor found before.
+    pub fn reinit_script() -> [Transaction; 4] {
+        init_to(Some(ID))
+    }
+
+    pub fn query(pm25: u16, pm10: u16) -> Transaction {
+        Transaction::command(Kind::Query(None), Some(ID))
+            .reply(Kind::Query(Some(Measurement::new(pm25, pm10))), ID)
+    }
+
+    /// What `measure()` sends to a sensor in polling mode.
+    pub fn measurement(pm25: u16, pm10: u16) -> [Transaction; 4] {
+        [
+            sleep_set(SleepMode::Work, Some(ID)),
+            query(1, 2),
+            query(pm25, pm10),
+            sleep_set(SleepMode::Sleep, Some(ID)),
+        ]
+    }
+
+    /// A power switch remembering every state it was set to.
+    #[derive(Default)]
+    pub struct Switch(pub Vec<bool>);
+
+    impl embedded_hal::digital::ErrorType for &mut Switch {
+        type Error = core::convert::Infallible;
+    }
+
+    impl embedded_hal::digital::OutputPin for &mut Switch {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.0.push(false);
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.0.push(true);
+            Ok(())
+        }
+    }
+
+    /// A serial port that stalls, like a UART waiting for data,
+    /// once it has read or written its budget of bytes.
+    pub struct Stall<'a> {
+        pub serial: &'a mut Mock,
+        pub reads: &'a AtomicUsize,
+        pub writes: &'a AtomicUsize,
+    }
+
+    impl Stall<'_> {
+        async fn spend(budget: &AtomicUsize, len: usize) -> usize {
+            if budget.load(Relaxed) == 0 {
+                core::future::pending::<()>().await;
+            }
+            let n = len.min(budget.load(Relaxed));
+            budget.fetch_sub(n, Relaxed);
+            n
+        }
+    }
+
+    impl embedded_io::ErrorType for Stall<'_> {
+        type Error = embedded_io::ErrorKind;
+    }
+
+    impl embedded_io_async::Read for Stall<'_> {
+        async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let n = Self::spend(self.reads, buf.len()).await;
+            let read = self.serial.on_read(&mut buf[..n]);
+            // give back what the mock did not have
+            self.reads.fetch_add(n - read, Relaxed);
+            Ok(read)
+        }
+    }
+
+    impl embedded_io_async::Write for Stall<'_> {
+        async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            let n = Self::spend(self.writes, buf.len()).await;
+            Ok(self.serial.on_write(&buf[..n]))
+        }
+
+        async fn flush(&mut self) -> Result<(), Self::Error> {
+            Ok(())
+        }
+    }
+
+    /// Only what the budget allows is ready.
+    impl embedded_io::ReadReady for Stall<'_> {
+        fn read_ready(&mut self) -> Result<bool, Self::Error> {
+            Ok(self.reads.load(Relaxed) > 0 && !self.serial.pending.is_empty())
+        }
+    }
+
+    /// Poll `fut` until it stalls, then drop it.
+    pub fn cancel<F: Future>(fut: F) {
+        let mut fut = pin!(fut);
+        let res = fut.as_mut().poll(&mut Context::from_waker(Waker::noop()));
+        assert!(res.is_pending(), "nothing to cancel");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fixtures::{SDS011, query, sleep_set};
+    use super::{Mock, MockDelay, SleepMode};
+    use crate::Config;
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    #[should_panic(expected = "driver sent an unexpected frame")]
+    async fn wrong_frame_panics() {
+        // the driver wakes the sensor first, not put it to sleep
+        let mut serial = Mock::new([sleep_set(SleepMode::Sleep, None)]);
+        let sensor = SDS011::new(&mut serial, Config::default());
+        _ = sensor.init(&mut MockDelay::new()).await;
+    }
+
+    #[test]
+    #[should_panic(expected = "1 of 1 scripted transactions were never performed")]
+    fn unfinished_script_panics() {
+        Mock::new([query(1, 2)]).done();
+    }
+}
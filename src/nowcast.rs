@@ -0,0 +1,144 @@
+//! The EPA's `NowCast`, for a current US AQI.
+//!
+//! The US EPA AQI for particulate matter is defined on 24-hour averages,
+//! which lag behind quickly changing air quality. The EPA reports the AQI of
+//! a 12-hour average instead, the `NowCast`, which weights recent hours the
+//! more, the more the concentrations vary. [`NowCast`] keeps the last 12
+//! hourly averages; it is up to the caller to average the measurements
+//! of each hour:
+//!
+//! ```ignore
+//! let mut nowcast = NowCast::new();
+//! loop {
+//!     // average an hour's measurements, or None if there were too few
+//!     nowcast.push(hourly_average(&mut sensor).await);
+//!     if let Some(aqi) = nowcast.aqi() {
+//!         println!("AQI {} ({})", aqi.index, aqi.category.name);
+//!     }
+//! }
+//! ```
+
+use crate::Measurement;
+use crate::aqi::{Aqi, Scale};
+
+/// How many hours the `NowCast` spans.
+const HOURS: usize = 12;
+
+/// The last 12 hourly averages, and their `NowCast`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NowCast {
+    /// the most recent hour first
+    hours: [Option<Measurement>; HOURS],
+}
+
+impl NowCast {
+    /// Start without any hours.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            hours: [None; HOURS],
+        }
+    }
+
+    /// Add the average of the hour that just ended,
+    /// or `None` if it is missing.
+    pub const fn push(&mut self, hour: Option<Measurement>) {
+        self.hours.rotate_right(1);
+        self.hours[0] = hour;
+    }
+
+    /// The `NowCast` of both pollutants, in 0.1 µg/m3 (truncated),
+    /// or `None` if two of the last three hours are missing.
+    #[must_use]
+    pub fn measurement(&self) -> Option<Measurement> {
+        let pm25 = self.nowcast(Measurement::pm25)?;
+        let pm10 = self.nowcast(Measurement::pm10)?;
+        Some(Measurement::new(pm25, pm10))
+    }
+
+    /// The US EPA AQI of the `NowCast`, see [`Scale::UsEpa`].
+    #[must_use]
+    pub fn aqi(&self) -> Option<Aqi> {
+        self.measurement().map(|m| Scale::UsEpa.index(&m))
+    }
+
+    fn nowcast(&self, pollutant: fn(&Measurement) -> u16) -> Option<u16> {
+        let recent = self.hours.iter().take(3).flatten().count();
+        if recent < 2 {
+            return None;
+        }
+
+        let values = || self.hours.iter().flatten().map(pollutant);
+        let min = f32::from(values().min()?);
+        let max = f32::from(values().max()?);
+        if max == 0.0 {
+            return Some(0);
+        }
+        let factor = (min / max).max(0.5);
+
+        // missing hours keep their weight out of the sum
+        let (mut sum, mut weights, mut weight) = (0.0, 0.0, 1.0);
+        for hour in &self.hours {
+            if let Some(m) = hour {
+                sum += weight * f32::from(pollutant(m));
+                weights += weight;
+            }
+            weight *= factor;
+        }
+
+        // a weighted average lies between min and max
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        Some((sum / weights) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NowCast;
+    use crate::Measurement;
+
+    fn nowcast(hours: &[Option<u16>]) -> Option<u16> {
+        let mut nowcast = NowCast::new();
+        // oldest first
+        for hour in hours.iter().rev() {
+            nowcast.push(hour.map(|pm| Measurement::new(pm, pm)));
+        }
+        nowcast.measurement().map(|m| m.pm25())
+    }
+
+    #[test]
+    fn weights() {
+        // steady air: the plain average
+        assert_eq!(nowcast(&[Some(100); 12]), Some(100));
+        // 0.8 per hour
+        assert_eq!(nowcast(&[Some(100), Some(80)]), Some(91));
+        // at least 0.5 per hour
+        assert_eq!(nowcast(&[Some(400), Some(100)]), Some(300));
+        assert_eq!(nowcast(&[Some(0), Some(0), Some(0)]), Some(0));
+    }
+
+    #[test]
+    fn missing_hours() {
+        // a missing hour keeps its place
+        assert_eq!(nowcast(&[Some(100), None, Some(80)]), Some(92));
+        assert_eq!(nowcast(&[None, Some(100), Some(80)]), Some(91));
+        // two of the last three hours are needed
+        assert_eq!(nowcast(&[Some(100), None, None, Some(80)]), None);
+        assert_eq!(nowcast(&[]), None);
+
+        // hours older than 12 drop out
+        let mut hours = [Some(100); 14];
+        hours[12] = Some(1_000);
+        hours[13] = Some(1_000);
+        assert_eq!(nowcast(&hours), Some(100));
+    }
+
+    #[test]
+    fn aqi() {
+        let mut nowcast = NowCast::new();
+        nowcast.push(Some(Measurement::new(359, 200)));
+        assert_eq!(nowcast.aqi(), None);
+        nowcast.push(Some(Measurement::new(359, 200)));
+        assert_eq!(nowcast.aqi().map(|aqi| aqi.index), Some(102));
+    }
+}
@@ -0,0 +1,288 @@
+//! Watching what the driver does.
+//!
+//! An [`Observer`] attached with `SDS011::set_observer()` is told about every
+//! frame sent and received, every transition of the sensor (waking up, going
+//! to sleep, switching modes), every reply that could not be decoded and
+//! every measurement. All methods do nothing by default, so an observer only
+//! implements what it is interested in, e.g. to count parse errors:
+//!
+//! ```ignore
+//! #[derive(Default)]
+//! struct Errors(u32);
+//!
+//! impl Observer for Errors {
+//!     fn parse_error(&mut self, _: &ParseError, _: &[u8]) {
+//!         self.0 += 1;
+//!     }
+//! }
+//!
+//! let sds011 = SDS011::new(uart, Config::default()).set_observer(Errors::default());
+//! ```
+//!
+//! With the `log` or `defmt` feature, [`LogObserver`] or [`DefmtObserver`]
+//! write all events to the respective logger.
+
+use crate::{Measurement, ParseError, ReportingMode, State};
+use core::fmt::{self, Display, Formatter};
+
+/// A change of the sensor's state, as seen by an [`Observer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Transition {
+    /// The sensor woke up (spinning up its fan).
+    Woke,
+    /// The sensor went to sleep.
+    Slept,
+    /// The power switch turned the sensor on.
+    PoweredOn,
+    /// The power switch turned the sensor off.
+    PoweredOff,
+    /// The sensor switched its reporting mode.
+    Reporting(ReportingMode),
+    /// The sensor switched its working period, in minutes.
+    Period(u8),
+    /// The driver reached a new state (after `init()` or `make_periodic()`).
+    State(State),
+}
+
+impl Display for Transition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Woke => f.write_str("woke up"),
+            Self::Slept => f.write_str("went to sleep"),
+            Self::PoweredOn => f.write_str("powered on"),
+            Self::PoweredOff => f.write_str("powered off"),
+            Self::Reporting(mode) => write!(f, "switched to {mode} reporting"),
+            Self::Period(minutes) => write!(f, "switched to a {minutes} min working period"),
+            Self::State(state) => write!(f, "now {state}"),
+        }
+    }
+}
+
+/// Hooks called by the driver, see [the module documentation](self).
+///
+/// The hooks run in the middle of the driver's operations,
+/// so they should return quickly.
+pub trait Observer {
+    /// A command `frame` was sent (and flushed).
+    fn frame_sent(&mut self, frame: &[u8]) {
+        _ = frame;
+    }
+
+    /// A reply `frame` was received, before it is decoded.
+    fn frame_received(&mut self, frame: &[u8]) {
+        _ = frame;
+    }
+
+    /// The sensor's state changed.
+    fn transition(&mut self, transition: Transition) {
+        _ = transition;
+    }
+
+    /// The received `frame` could not be decoded.
+    fn parse_error(&mut self, error: &ParseError, frame: &[u8]) {
+        _ = (error, frame);
+    }
+
+    /// A measurement was taken (or arrived, in periodic mode), as the sensor
+    /// reported it, before any calibration.
+    fn raw_measurement(&mut self, measurement: &Measurement) {
+        _ = measurement;
+    }
+
+    /// A measurement was taken (or arrived, in periodic mode),
+    /// as the driver returns it.
+    fn measurement(&mut self, measurement: &Measurement) {
+        _ = measurement;
+    }
+}
+
+impl<T: Observer + ?Sized> Observer for &mut T {
+    fn frame_sent(&mut self, frame: &[u8]) {
+        (**self).frame_sent(frame);
+    }
+
+    fn frame_received(&mut self, frame: &[u8]) {
+        (**self).frame_received(frame);
+    }
+
+    fn transition(&mut self, transition: Transition) {
+        (**self).transition(transition);
+    }
+
+    fn parse_error(&mut self, error: &ParseError, frame: &[u8]) {
+        (**self).parse_error(error, frame);
+    }
+
+    fn raw_measurement(&mut self, measurement: &Measurement) {
+        (**self).raw_measurement(measurement);
+    }
+
+    fn measurement(&mut self, measurement: &Measurement) {
+        (**self).measurement(measurement);
+    }
+}
+
+/// The observer of a sensor without one; ignores everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NoObserver;
+
+impl Observer for NoObserver {}
+
+/// Writes all events to the [`log`] crate: frames at trace level,
+/// transitions at debug level, measurements at info level
+/// and parse errors as warnings.
+#[cfg(feature = "log")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogObserver;
+
+#[cfg(feature = "log")]
+impl Observer for LogObserver {
+    fn frame_sent(&mut self, frame: &[u8]) {
+        log::trace!("sent {frame:02X?}");
+    }
+
+    fn frame_received(&mut self, frame: &[u8]) {
+        log::trace!("received {frame:02X?}");
+    }
+
+    fn transition(&mut self, transition: Transition) {
+        log::debug!("sensor {transition}");
+    }
+
+    fn parse_error(&mut self, error: &ParseError, frame: &[u8]) {
+        log::warn!("could not decode {frame:02X?}: {error}");
+    }
+
+    fn measurement(&mut self, measurement: &Measurement) {
+        log::info!("{measurement}");
+    }
+}
+
+/// Writes all events to the [`defmt`] logger, at the same levels
+/// as the `LogObserver`.
+#[cfg(feature = "defmt")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DefmtObserver;
+
+#[cfg(feature = "defmt")]
+impl Observer for DefmtObserver {
+    fn frame_sent(&mut self, frame: &[u8]) {
+        defmt::trace!("sent {=[u8]:X}", frame);
+    }
+
+    fn frame_received(&mut self, frame: &[u8]) {
+        defmt::trace!("received {=[u8]:X}", frame);
+    }
+
+    fn transition(&mut self, transition: Transition) {
+        defmt::debug!("sensor {}", defmt::Display2Format(&transition));
+    }
+
+    fn parse_error(&mut self, error: &ParseError, frame: &[u8]) {
+        defmt::warn!(
+            "could not decode {=[u8]:X}: {}",
+            frame,
+            defmt::Display2Format(error)
+        );
+    }
+
+    fn measurement(&mut self, measurement: &Measurement) {
+        defmt::info!("{}", defmt::Display2Format(measurement));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Observer, Transition};
+    use crate::mock::fixtures::{ID, SDS011, init_script, query, sleep_set};
+    use crate::mock::{Kind, Mock, MockDelay, ReportingMode, SleepMode, Transaction};
+    use crate::{Config, Measurement, ParseError, SDS011Error, State};
+    use alloc::vec::Vec;
+
+    /// Records everything but the frames, which it only counts.
+    #[derive(Default)]
+    struct Events {
+        sent: usize,
+        received: usize,
+        transitions: Vec<Transition>,
+        errors: Vec<ParseError>,
+        measurements: Vec<Measurement>,
+    }
+
+    impl Observer for Events {
+        fn frame_sent(&mut self, frame: &[u8]) {
+            assert_eq!(frame.len(), 19);
+            self.sent += 1;
+        }
+
+        fn frame_received(&mut self, frame: &[u8]) {
+            assert_eq!(frame.len(), 10);
+            self.received += 1;
+        }
+
+        fn transition(&mut self, transition: Transition) {
+            self.transitions.push(transition);
+        }
+
+        fn parse_error(&mut self, error: &ParseError, _: &[u8]) {
+            self.errors.push(*error);
+        }
+
+        fn measurement(&mut self, measurement: &Measurement) {
+            self.measurements.push(*measurement);
+        }
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn sees_everything() {
+        let mut script = init_script();
+        script.extend([
+            sleep_set(SleepMode::Work, Some(ID)),
+            query(1, 2),
+            query(1236, 2618),
+            sleep_set(SleepMode::Sleep, Some(ID)),
+        ]);
+        let mut serial = Mock::new(script);
+        let mut events = Events::default();
+
+        let sensor = SDS011::new(&mut serial, Config::default()).set_observer(&mut events);
+        let mut sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        sensor.measure(&mut MockDelay::new()).await.unwrap();
+        serial.done();
+
+        assert_eq!((events.sent, events.received), (8, 8));
+        assert_eq!(
+            events.transitions,
+            [
+                Transition::Woke,
+                Transition::Reporting(ReportingMode::Query),
+                Transition::Slept,
+                Transition::State(State::Polling),
+                Transition::Woke,
+                Transition::Slept,
+            ]
+        );
+        assert!(events.errors.is_empty());
+        // not the dummy measurement
+        assert_eq!(events.measurements, [Measurement::new(1236, 2618)]);
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn sees_parse_errors() {
+        let wake = Kind::Sleep(crate::Sleep::new_set(SleepMode::Work));
+        let mut serial = Mock::new([Transaction::command(wake, None).reply_raw(&[0xAA; 10])]);
+        let mut events = Events::default();
+
+        let sensor = SDS011::new(&mut serial, Config::default()).set_observer(&mut events);
+        let err = sensor.init(&mut MockDelay::new()).await.err().unwrap();
+        let SDS011Error::ParseError { source, .. } = err else {
+            panic!("unexpected error: {err}");
+        };
+        serial.done();
+
+        assert_eq!((events.sent, events.received), (1, 1));
+        assert_eq!(events.errors, [source]);
+        assert!(events.transitions.is_empty());
+    }
+}
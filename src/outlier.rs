@@ -0,0 +1,307 @@
+//! Detecting spikes in the readings.
+//!
+//! Insects, steam or cooking make the sensor report single readings far
+//! above the air around them. A [`Detector`] compares each measurement with
+//! the last `N` and reports an [`Outlier`], explaining why, if one of the
+//! pollutants deviates too much:
+//!
+//! ```ignore
+//! let mut detector = Detector::<7>::new(Rule::HAMPEL);
+//! loop {
+//!     match detector.check(sensor.measure().await?) {
+//!         Ok(measurement) => publish(measurement),
+//!         Err(outlier) => log::warn!("dropped: {outlier}"),
+//!     }
+//! }
+//! ```
+//!
+//! Outliers still enter the window, so a lasting change of the air is
+//! accepted once it makes up most of the window. As a [`Filter`], a detector
+//! replaces outliers with what it expected instead (for [`Rule::Hampel`],
+//! this is the Hampel filter).
+
+use crate::Measurement;
+use crate::aqi::Pollutant;
+use crate::filter::{Filter, output};
+use crate::stats::{Ring, median};
+use core::fmt::{self, Display, Formatter};
+
+/// When a reading counts as an outlier.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rule {
+    /// More than `threshold` times the (scaled) median absolute deviation
+    /// away from the median of the window. Robust against earlier outliers.
+    Hampel {
+        /// How many deviations are still normal.
+        threshold: f32,
+    },
+    /// More than `threshold` standard deviations away from the mean of the
+    /// window.
+    ZScore {
+        /// How many standard deviations are still normal.
+        threshold: f32,
+    },
+}
+
+impl Rule {
+    /// The Hampel rule with the usual threshold of 3.
+    pub const HAMPEL: Self = Self::Hampel { threshold: 3.0 };
+
+    /// The z-score rule with the usual threshold of 3.
+    pub const ZSCORE: Self = Self::ZScore { threshold: 3.0 };
+}
+
+/// A measurement found to be an outlier, and why.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct Outlier {
+    /// The measurement.
+    pub measurement: Measurement,
+    /// The pollutant that deviates (the more deviating one if both do).
+    pub pollutant: Pollutant,
+    /// The value the window suggests instead, the median or mean.
+    /// Divide by ten to get µg/m3.
+    pub expected: u16,
+    /// How many (median absolute or standard) deviations the reading is
+    /// away from the expected value.
+    pub score: f32,
+    /// The rule the reading broke.
+    pub rule: Rule,
+}
+
+impl Display for Outlier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (value, name) = match self.pollutant {
+            Pollutant::Pm25 => (self.measurement.pm25(), "PM2.5"),
+            Pollutant::Pm10 => (self.measurement.pm10(), "PM10"),
+        };
+        let (center, deviation) = match self.rule {
+            Rule::Hampel { .. } => ("median", "median absolute deviations"),
+            Rule::ZScore { .. } => ("mean", "standard deviations"),
+        };
+        write!(
+            f,
+            "{name} of {} µg/m3 is {:.1} {deviation} from the {center} of {} µg/m3",
+            f32::from(value) / 10.0,
+            self.score,
+            f32::from(self.expected) / 10.0
+        )
+    }
+}
+
+impl core::error::Error for Outlier {}
+
+/// Checks measurements against the last `N`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detector<const N: usize> {
+    rule: Rule,
+    floor: u16,
+    /// PM2.5 and PM10 of each measurement
+    ring: Ring<[u16; 2], N>,
+}
+
+impl<const N: usize> Detector<N> {
+    /// Start with an empty window, checking by `rule`.
+    #[must_use]
+    pub const fn new(rule: Rule) -> Self {
+        Self {
+            rule,
+            floor: 10,
+            ring: Ring::new(),
+        }
+    }
+
+    /// Set the smallest deviation assumed for the window, in 0.1 µg/m3
+    /// (default: 1 µg/m3). In clean, steady air, the readings hardly vary,
+    /// and without a floor, any small change would count as an outlier.
+    #[must_use]
+    pub const fn set_floor(mut self, floor: u16) -> Self {
+        self.floor = floor;
+        self
+    }
+
+    /// Check `measurement` against the window and add it.
+    /// Until the window holds three measurements, everything passes.
+    ///
+    /// # Errors
+    /// The [`Outlier`] if one of the pollutants deviates too much.
+    pub fn check(&mut self, measurement: Measurement) -> Result<Measurement, Outlier> {
+        let outlier = [Pollutant::Pm25, Pollutant::Pm10]
+            .into_iter()
+            .filter_map(|pollutant| self.judge(measurement, pollutant))
+            .max_by(|a, b| a.score.total_cmp(&b.score));
+        self.ring.push([measurement.pm25(), measurement.pm10()]);
+        outlier.map_or(Ok(measurement), Err)
+    }
+
+    /// Forget the window, e.g. after the sensor slept.
+    pub const fn clear(&mut self) {
+        self.ring = Ring::new();
+    }
+
+    fn judge(&self, measurement: Measurement, pollutant: Pollutant) -> Option<Outlier> {
+        let index = match pollutant {
+            Pollutant::Pm25 => 0,
+            Pollutant::Pm10 => 1,
+        };
+        let mut window = [0.0; N];
+        let mut len = 0;
+        for (slot, m) in window.iter_mut().zip(self.ring.iter()) {
+            *slot = f32::from(m[index]);
+            len += 1;
+        }
+        let window = window.get_mut(..len).filter(|w| w.len() >= 3)?;
+
+        let (center, spread, threshold) = match self.rule {
+            Rule::Hampel { threshold } => {
+                let median = median(window)?;
+                for x in window.iter_mut() {
+                    *x = (*x - median).abs();
+                }
+                // scaled to estimate the standard deviation of normal noise
+                (median, 1.4826 * self::median(window)?, threshold)
+            }
+            Rule::ZScore { threshold } => {
+                #[allow(clippy::cast_precision_loss)]
+                let n = len as f32;
+                let mean = window.iter().sum::<f32>() / n;
+                let variance = window.iter().map(|x| (x - mean) * (x - mean)).sum::<f32>() / n;
+                (mean, sqrt(variance), threshold)
+            }
+        };
+
+        let value = f32::from([measurement.pm25(), measurement.pm10()][index]);
+        let score = (value - center).abs() / spread.max(f32::from(self.floor));
+        (score > threshold).then_some(Outlier {
+            measurement,
+            pollutant,
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            expected: (center + 0.5) as u16,
+            score,
+            rule: self.rule,
+        })
+    }
+}
+
+impl<const N: usize> Filter for Detector<N> {
+    fn update(&mut self, measurement: Measurement) -> Measurement {
+        let expected = |pollutant| {
+            self.judge(measurement, pollutant)
+                .map(|outlier| outlier.expected)
+        };
+        let pm25 = expected(Pollutant::Pm25).unwrap_or_else(|| measurement.pm25());
+        let pm10 = expected(Pollutant::Pm10).unwrap_or_else(|| measurement.pm10());
+        self.ring.push([measurement.pm25(), measurement.pm10()]);
+        output(&measurement, pm25, pm10)
+    }
+
+    fn reset(&mut self) {
+        self.clear();
+    }
+}
+
+/// The square root by Newton's method, as `core` lacks one.
+fn sqrt(x: f32) -> f32 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    let mut root = x.max(1.0);
+    for _ in 0..32 {
+        root = f32::midpoint(root, x / root);
+    }
+    root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Detector, Outlier, Rule, sqrt};
+    use crate::Measurement;
+    use crate::aqi::Pollutant;
+    use crate::filter::Filter;
+    use alloc::string::ToString;
+
+    const fn m(pm: u16) -> Measurement {
+        Measurement::new(pm, pm)
+    }
+
+    #[test]
+    fn hampel() {
+        let mut detector = Detector::<5>::new(Rule::HAMPEL);
+        // too few to judge
+        assert!(detector.check(m(100)).is_ok());
+        assert!(detector.check(m(1_000)).is_ok());
+        for pm in [102, 98, 101] {
+            assert!(detector.check(m(pm)).is_ok());
+        }
+
+        // median 101, deviations 1, 899, 1, 3 and 0: MAD 1, floored to 10
+        let spike = Measurement::new(3_000, 110);
+        let outlier = detector.check(spike).unwrap_err();
+        assert_eq!(outlier.measurement, spike);
+        assert_eq!(outlier.pollutant, Pollutant::Pm25);
+        assert_eq!(outlier.expected, 101);
+        assert!((outlier.score - 289.9).abs() < 0.1);
+        assert_eq!(
+            outlier.to_string(),
+            "PM2.5 of 300 µg/m3 is 289.9 median absolute deviations \
+             from the median of 10.1 µg/m3"
+        );
+
+        // a lasting change is accepted
+        let mut detector = Detector::<5>::new(Rule::HAMPEL);
+        let out: alloc::vec::Vec<bool> = [100, 100, 100, 100, 100, 500, 500, 500, 500]
+            .into_iter()
+            .map(|pm| detector.check(m(pm)).is_ok())
+            .collect();
+        assert_eq!(
+            out,
+            [true, true, true, true, true, false, false, false, true]
+        );
+    }
+
+    #[test]
+    fn zscore() {
+        let mut detector = Detector::<4>::new(Rule::ZSCORE).set_floor(0);
+        for pm in [100, 120, 100, 120] {
+            assert!(detector.check(m(pm)).is_ok());
+        }
+        // mean 110, standard deviation 10
+        assert!(detector.check(m(135)).is_ok());
+        let Err(Outlier {
+            pollutant, score, ..
+        }) = detector.check(Measurement::new(120, 200))
+        else {
+            panic!("not an outlier");
+        };
+        assert_eq!(pollutant, Pollutant::Pm10);
+        assert!(score > 3.0);
+
+        detector.clear();
+        assert!(detector.check(m(10_000)).is_ok());
+    }
+
+    #[test]
+    fn filter() {
+        let mut detector = Detector::<3>::new(Rule::HAMPEL);
+        let out: alloc::vec::Vec<u16> = [100, 100, 100, 900, 100]
+            .into_iter()
+            .map(|pm| detector.update(Measurement::new(pm, 50)).pm25())
+            .collect();
+        assert_eq!(out, [100, 100, 100, 100, 100]);
+        // both pollutants at once
+        let replaced = detector.update(m(900));
+        assert_eq!(replaced, Measurement::new(100, 50));
+        assert!(replaced.quality().filtered);
+        detector.reset();
+        let passed = detector.update(m(900));
+        assert_eq!(passed, m(900));
+        assert!(!passed.quality().filtered);
+    }
+
+    #[test]
+    fn square_root() {
+        for x in [0.0, 0.25, 2.0, 100.0, 1e6] {
+            assert!((sqrt(x) * sqrt(x) - x).abs() <= x * 1e-5);
+        }
+    }
+}
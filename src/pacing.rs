@@ -0,0 +1,284 @@
+//! Spacing out commands and discarding stale input.
+//!
+//! The firmware occasionally drops a command that arrives right after its
+//! previous reply, and bytes left over from an earlier failure end up in
+//! front of the next reply. [`Paced`] wraps a serial interface and, before
+//! each command, waits a fixed gap and then discards whatever input is
+//! waiting (as far as the interface reports via `ReadReady`), including
+//! replies that arrived late, during the gap.
+//! [`Paced::discarded`] tells how noisy the link is.
+//!
+//! ```ignore
+//! let serial = Paced::new(uart, delay).set_gap(100);
+//! let sds011 = SDS011::new(serial, Config::default());
+//! ```
+
+use embedded_io::ErrorType;
+
+/// A serial interface that waits and drains its input before each command.
+///
+/// Implements the blocking as well as the async traits,
+/// depending on what the serial interface and delay `D` implement.
+#[derive(Debug)]
+pub struct Paced<T, D> {
+    serial: T,
+    delay: D,
+    gap: u32,
+    discarded: u32,
+    sending: bool,
+}
+
+impl<T, D> Paced<T, D> {
+    /// Wrap `serial`, using `delay` to wait between commands.
+    pub const fn new(serial: T, delay: D) -> Self {
+        Self {
+            serial,
+            delay,
+            gap: 50,
+            discarded: 0,
+            sending: false,
+        }
+    }
+
+    /// How many milliseconds to wait before sending a command; defaults to 50.
+    /// This is a fixed delay, however long ago the last reply arrived.
+    #[must_use]
+    pub const fn set_gap(mut self, gap: u32) -> Self {
+        self.gap = gap;
+        self
+    }
+
+    /// How many unread bytes were discarded so far.
+    pub const fn discarded(&self) -> u32 {
+        self.discarded
+    }
+
+    /// Give back the serial interface and delay.
+    pub fn into_inner(self) -> (T, D) {
+        (self.serial, self.delay)
+    }
+
+    fn count(&mut self, n: usize) {
+        let n = u32::try_from(n).unwrap_or(u32::MAX);
+        self.discarded = self.discarded.saturating_add(n);
+    }
+}
+
+impl<T: ErrorType, D> ErrorType for Paced<T, D> {
+    type Error = T::Error;
+}
+
+impl<T, D> embedded_io::ReadReady for Paced<T, D>
+where
+    T: embedded_io::ReadReady,
+{
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.serial.read_ready()
+    }
+}
+
+impl<T, D> embedded_io::Read for Paced<T, D>
+where
+    T: embedded_io::Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.sending = false;
+        self.serial.read(buf)
+    }
+}
+
+impl<T, D> embedded_io::Write for Paced<T, D>
+where
+    T: embedded_io::Read + embedded_io::ReadReady + embedded_io::Write,
+    D: embedded_hal::delay::DelayNs,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if !self.sending {
+            self.delay.delay_ms(self.gap);
+            let mut stale = [0u8; 16];
+            while self.serial.read_ready()? {
+                match self.serial.read(&mut stale)? {
+                    0 => break,
+                    n => self.count(n),
+                }
+            }
+            self.sending = true;
+        }
+        self.serial.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.serial.flush()
+    }
+}
+
+impl<T, D> embedded_io_async::Read for Paced<T, D>
+where
+    T: embedded_io_async::Read,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.sending = false;
+        self.serial.read(buf).await
+    }
+}
+
+impl<T, D> embedded_io_async::Write for Paced<T, D>
+where
+    T: embedded_io_async::Read + embedded_io::ReadReady + embedded_io_async::Write,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if !self.sending {
+            self.delay.delay_ms(self.gap).await;
+            let mut stale = [0u8; 16];
+            while self.serial.read_ready()? {
+                match self.serial.read(&mut stale).await? {
+                    0 => break,
+                    n => self.count(n),
+                }
+            }
+            self.sending = true;
+        }
+        self.serial.write(buf).await
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.serial.flush().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Paced;
+    use crate::message::Message;
+    use crate::mock::fixtures::{ID, SDS011, init_script, query, sleep_set};
+    use crate::mock::{Kind, Mock, MockDelay, Sleep, SleepMode, Transaction};
+    use crate::{Config, Measurement};
+    use alloc::collections::VecDeque;
+    use core::cell::RefCell;
+    use core::time::Duration;
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn drains_and_waits() {
+        let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+        let mut script = init_script();
+        script.extend([
+            // three stale bytes after the reply
+            Transaction::command(wake, Some(ID))
+                .reply(wake, ID)
+                .reply_raw(&[0xAA, 0xC0, 0x00]),
+            query(1, 2),
+            query(1236, 2618),
+            sleep_set(SleepMode::Sleep, Some(ID)),
+        ]);
+        let mut serial = Mock::new(script);
+        let mut gaps = MockDelay::new();
+
+        let paced = Paced::new(&mut serial, &mut gaps).set_gap(20);
+        let sensor = SDS011::new(paced, Config::default());
+        let mut sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        let m = sensor.measure(&mut MockDelay::new()).await.unwrap();
+        assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+
+        assert_eq!(gaps.delays(), [Duration::from_millis(20); 8]);
+        serial.done();
+    }
+
+    #[test]
+    fn counts_discarded_bytes() {
+        use embedded_io::{Read, Write};
+
+        let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+        let mut serial = Mock::new([
+            Transaction::command(wake, None)
+                .reply(wake, ID)
+                .reply_raw(&[0; 20]),
+            Transaction::command(wake, None).reply(wake, ID),
+        ]);
+        let frame = Message::new(wake, None).create_query();
+        let mut reply = [0u8; 10];
+
+        let mut paced = Paced::new(&mut serial, MockDelay::new());
+        paced.write_all(&frame).unwrap();
+        paced.read_exact(&mut reply).unwrap();
+        paced.write_all(&frame).unwrap();
+        paced.read_exact(&mut reply).unwrap();
+        assert_eq!(paced.discarded(), 20);
+        serial.done();
+    }
+
+    /// A serial interface receiving bytes that were not asked for.
+    struct Noisy<'a> {
+        serial: &'a mut Mock,
+        late: &'a RefCell<VecDeque<u8>>,
+    }
+
+    impl embedded_io::ErrorType for Noisy<'_> {
+        type Error = embedded_io::ErrorKind;
+    }
+
+    impl embedded_io::Read for Noisy<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+            let mut late = self.late.borrow_mut();
+            if late.is_empty() {
+                return self.serial.read(buf);
+            }
+            let n = buf.len().min(late.len());
+            for (dst, src) in buf.iter_mut().zip(late.drain(..n)) {
+                *dst = src;
+            }
+            Ok(n)
+        }
+    }
+
+    impl embedded_io::ReadReady for Noisy<'_> {
+        fn read_ready(&mut self) -> Result<bool, Self::Error> {
+            Ok(!self.late.borrow().is_empty() || self.serial.read_ready()?)
+        }
+    }
+
+    impl embedded_io::Write for Noisy<'_> {
+        fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+            self.serial.write(buf)
+        }
+
+        fn flush(&mut self) -> Result<(), Self::Error> {
+            self.serial.flush()
+        }
+    }
+
+    /// A delay during which a late reply arrives.
+    struct Late<'a>(&'a RefCell<VecDeque<u8>>, Option<[u8; 10]>);
+
+    impl embedded_hal::delay::DelayNs for Late<'_> {
+        fn delay_ns(&mut self, _: u32) {
+            if let Some(reply) = self.1.take() {
+                self.0.borrow_mut().extend(reply);
+            }
+        }
+    }
+
+    #[test]
+    fn drains_after_gap() {
+        use embedded_io::{Read, Write};
+
+        let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+        let mut serial = Mock::new([Transaction::command(wake, None).reply(wake, ID)]);
+        let frame = Message::new(wake, None).create_query();
+        let stale =
+            Message::new(Kind::Query(Some(Measurement::new(1, 2))), Some(ID)).create_reply();
+        let late = RefCell::new(VecDeque::new());
+        let mut reply = [0u8; 10];
+
+        let noisy = Noisy {
+            serial: &mut serial,
+            late: &late,
+        };
+        let mut paced = Paced::new(noisy, Late(&late, Some(stale)));
+        paced.write_all(&frame).unwrap();
+        paced.read_exact(&mut reply).unwrap();
+        assert_eq!(reply, Message::new(wake, Some(ID)).create_reply());
+        assert_eq!(paced.discarded(), 10);
+        serial.done();
+    }
+}
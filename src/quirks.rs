@@ -0,0 +1,255 @@
+use core::fmt::{Display, Formatter};
+
+/// A known deviation from the protocol as documented by Nova Fitness.
+///
+/// Some of these show up on genuine sensors, most on clones.
+/// Each one can be tolerated individually via [`Quirks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Quirk {
+    /// Replies to "set sleep mode" end in 0xFF instead of 0xAB.
+    /// Seen on genuine sensors.
+    SleepReplyTail,
+    /// Any other reply ends in a byte other than 0xAB.
+    WrongTail,
+    /// Bytes marked as reserved in the datasheet are not zero.
+    ReservedBytes,
+    /// Replies carry the sensor's ID with its bytes swapped.
+    SwappedId,
+    /// Replies carry the broadcast ID 0xFFFF instead of the sensor's ID.
+    BroadcastId,
+}
+
+impl Quirk {
+    /// All known quirks.
+    pub const ALL: [Self; 5] = [
+        Self::SleepReplyTail,
+        Self::WrongTail,
+        Self::ReservedBytes,
+        Self::SwappedId,
+        Self::BroadcastId,
+    ];
+
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl Display for Quirk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        f.write_str(match self {
+            Self::SleepReplyTail => "sleep reply ends in 0xFF",
+            Self::WrongTail => "wrong tail byte",
+            Self::ReservedBytes => "reserved bytes not zero",
+            Self::SwappedId => "sensor ID byte-swapped",
+            Self::BroadcastId => "sensor ID replaced by 0xFFFF",
+        })
+    }
+}
+
+/// A set of [`Quirk`]s.
+///
+/// In [`Config`](crate::Config), this is the parsing policy: replies showing
+/// a quirk outside the set are rejected. The driver also keeps the set of
+/// quirks it actually tolerated, see [`SDS011::quirks`](crate::SDS011::quirks).
+///
+/// The default tolerates what this driver always accepted
+/// ([`SleepReplyTail`](Quirk::SleepReplyTail) and
+/// [`ReservedBytes`](Quirk::ReservedBytes)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks(u8);
+
+impl Quirks {
+    /// No quirks: strict protocol conformance.
+    pub const STRICT: Self = Self(0);
+
+    /// All known quirks.
+    pub const LENIENT: Self = {
+        let mut set = Self::STRICT;
+        let mut i = 0;
+        while i < Quirk::ALL.len() {
+            set = set.with(Quirk::ALL[i]);
+            i += 1;
+        }
+        set
+    };
+
+    /// This set, plus `quirk`.
+    #[must_use]
+    pub const fn with(self, quirk: Quirk) -> Self {
+        Self(self.0 | quirk.bit())
+    }
+
+    /// This set, minus `quirk`.
+    #[must_use]
+    pub const fn without(self, quirk: Quirk) -> Self {
+        Self(self.0 & !quirk.bit())
+    }
+
+    /// Whether `quirk` is in this set.
+    #[must_use]
+    pub const fn contains(self, quirk: Quirk) -> bool {
+        self.0 & quirk.bit() != 0
+    }
+
+    /// Whether this set is empty.
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// The union of both sets.
+    #[must_use]
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    /// The quirks in this set that are not in `other`.
+    #[must_use]
+    pub const fn difference(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+
+    /// Iterate over the quirks in this set.
+    pub fn iter(self) -> impl Iterator<Item = Quirk> {
+        Quirk::ALL.into_iter().filter(move |q| self.contains(*q))
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::STRICT
+            .with(Quirk::SleepReplyTail)
+            .with(Quirk::ReservedBytes)
+    }
+}
+
+impl From<Quirk> for Quirks {
+    fn from(quirk: Quirk) -> Self {
+        Self::STRICT.with(quirk)
+    }
+}
+
+impl Display for Quirks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        if self.is_empty() {
+            return f.write_str("none");
+        }
+        for (i, quirk) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{quirk}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Quirk, Quirks};
+    use crate::message::ParseError;
+    use crate::mock::fixtures::{ID, SDS011, init_script, query, sleep_set};
+    use crate::mock::{Kind, Mock, MockDelay, Sleep, SleepMode, Transaction};
+    use crate::{Config, Measurement, SDS011Error};
+    use alloc::string::ToString;
+
+    // "sleep mode is work (set)", ending in 0xFF
+    const WAKE_REPLY_FF: [u8; 10] = [0xAA, 0xC5, 0x06, 0x01, 0x01, 0x00, 0xA1, 0x60, 0x09, 0xFF];
+
+    fn wake_with_tail_ff() -> Transaction {
+        Transaction::command(Kind::Sleep(Sleep::new_set(SleepMode::Work)), None)
+            .reply_raw(&WAKE_REPLY_FF)
+    }
+
+    #[test]
+    fn sets() {
+        assert!(Quirks::STRICT.is_empty());
+        assert!(Quirk::ALL.iter().all(|&q| Quirks::LENIENT.contains(q)));
+        assert_eq!(Quirks::LENIENT.iter().count(), Quirk::ALL.len());
+
+        let set = Quirks::default();
+        assert!(set.contains(Quirk::SleepReplyTail));
+        assert!(!set.contains(Quirk::WrongTail));
+        assert!(
+            !set.without(Quirk::SleepReplyTail)
+                .contains(Quirk::SleepReplyTail)
+        );
+        assert_eq!(
+            Quirks::from(Quirk::SwappedId).union(set).difference(set),
+            Quirk::SwappedId.into()
+        );
+        assert_eq!(
+            set.to_string(),
+            "sleep reply ends in 0xFF, reserved bytes not zero"
+        );
+        assert_eq!(Quirks::STRICT.to_string(), "none");
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn strict_rejects_quirks() {
+        let mut serial = Mock::new([wake_with_tail_ff()]);
+        let sensor = SDS011::new(&mut serial, Config::default().set_quirks(Quirks::STRICT));
+
+        let res = sensor.init(&mut MockDelay::new()).await;
+        assert!(matches!(
+            res,
+            Err(SDS011Error::ParseError {
+                source: ParseError::Quirk(Quirk::SleepReplyTail),
+                ..
+            })
+        ));
+        serial.done();
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn tolerated_quirks_are_reported() {
+        let mut script = init_script();
+        script[0] = wake_with_tail_ff();
+        let swapped = Transaction::command(Kind::Query(None), Some(ID))
+            .reply(Kind::Query(Some(Measurement::new(1, 2))), ID.swap_bytes());
+        script.extend([
+            sleep_set(SleepMode::Work, Some(ID)),
+            swapped,
+            query(1236, 2618),
+            sleep_set(SleepMode::Sleep, Some(ID)),
+        ]);
+        let mut serial = Mock::new(script);
+        let config = Config::default().set_quirks(Quirks::default().with(Quirk::SwappedId));
+
+        let sensor = SDS011::new(&mut serial, config);
+        let mut sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        assert_eq!(sensor.quirks(), Quirk::SleepReplyTail.into());
+
+        let m = sensor.measure(&mut MockDelay::new()).await.unwrap();
+        assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+        assert_eq!(
+            sensor.quirks(),
+            Quirks::from(Quirk::SleepReplyTail).with(Quirk::SwappedId)
+        );
+        serial.done();
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn foreign_sensor_rejected() {
+        let mut script = init_script();
+        script.extend([Transaction::command(
+            Kind::Sleep(Sleep::new_set(SleepMode::Work)),
+            Some(ID),
+        )
+        .reply(Kind::Sleep(Sleep::new_set(SleepMode::Work)), 0x1234)]);
+        let mut serial = Mock::new(script);
+
+        let sensor = SDS011::new(&mut serial, Config::default().set_quirks(Quirks::LENIENT));
+        let mut sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        let res = sensor.measure(&mut MockDelay::new()).await;
+        assert!(matches!(
+            res,
+            Err(SDS011Error::ParseError {
+                source: ParseError::SensorID(0x1234),
+                sensor_id: ID,
+                ..
+            })
+        ));
+        serial.done();
+    }
+}
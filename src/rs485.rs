@@ -0,0 +1,291 @@
+//! Half-duplex RS485 with a direction-control pin.
+//!
+//! RS485 transceivers such as the MAX485 only drive the bus while their
+//! DE/RE pin is asserted. [`Rs485`] wraps a serial interface and asserts the
+//! pin before each command, then flushes and releases the bus so the sensor
+//! can answer. Together with [`Config::set_sensor_id`](crate::Config::set_sensor_id),
+//! this lets the driver talk to sensors on a multi-drop bus.
+//!
+//! ```ignore
+//! let serial = Rs485::new(uart, de_pin, delay).set_release_delay(2_000);
+//! let sds011 = SDS011::new(serial, Config::default().set_sensor_id(0xA160));
+//! ```
+
+use embedded_hal::digital::{self, OutputPin};
+use embedded_io::{ErrorKind, ErrorType};
+use thiserror::Error;
+
+/// A serial interface behind an RS485 transceiver,
+/// with `pin` driving the transceiver's DE/RE input (high = transmit).
+///
+/// Implements the blocking as well as the async traits,
+/// depending on what the serial interface and delay `D` implement.
+#[derive(Debug)]
+pub struct Rs485<T, P, D> {
+    serial: T,
+    pin: P,
+    delay: D,
+    enable_delay: u32,
+    release_delay: u32,
+    transmitting: bool,
+}
+
+impl<T, P, D> Rs485<T, P, D> {
+    /// Wrap `serial`, using `pin` for direction control and `delay`
+    /// for the turnaround times. The pin should start out low (receive).
+    pub const fn new(serial: T, pin: P, delay: D) -> Self {
+        Self {
+            serial,
+            pin,
+            delay,
+            enable_delay: 50,
+            release_delay: 1_100,
+            transmitting: false,
+        }
+    }
+
+    /// How many microseconds to wait after asserting the pin before
+    /// transmitting, for the transceiver to enable its driver; defaults to 50.
+    #[must_use]
+    pub const fn set_enable_delay(mut self, enable_delay: u32) -> Self {
+        self.enable_delay = enable_delay;
+        self
+    }
+
+    /// How many microseconds to wait after flushing before releasing the pin.
+    /// Many UARTs report a flush as soon as the last byte has entered the
+    /// shift register, so this defaults to one character at 9600 baud (1100).
+    #[must_use]
+    pub const fn set_release_delay(mut self, release_delay: u32) -> Self {
+        self.release_delay = release_delay;
+        self
+    }
+
+    /// Give back the serial interface, pin and delay.
+    pub fn into_inner(self) -> (T, P, D) {
+        (self.serial, self.pin, self.delay)
+    }
+}
+
+/// Error type of an [`Rs485`] interface.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum Rs485Error<E, P> {
+    /// The serial interface returned an error.
+    #[error("{0}")]
+    Serial(E),
+    /// The direction-control pin could not be set.
+    #[error("direction pin: {0:?}")]
+    Pin(P),
+}
+
+impl<E, P> embedded_io::Error for Rs485Error<E, P>
+where
+    E: embedded_io::Error,
+    P: digital::Error,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Serial(e) => e.kind(),
+            Self::Pin(_) => ErrorKind::Other,
+        }
+    }
+}
+
+impl<T: ErrorType, P: digital::ErrorType, D> ErrorType for Rs485<T, P, D> {
+    type Error = Rs485Error<T::Error, P::Error>;
+}
+
+impl<T, P, D> embedded_io::Read for Rs485<T, P, D>
+where
+    T: embedded_io::Read + embedded_io::Write,
+    P: OutputPin,
+    D: embedded_hal::delay::DelayNs,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        // nobody will answer while we hold the bus
+        if self.transmitting {
+            embedded_io::Write::flush(self)?;
+        }
+        self.serial.read(buf).map_err(Rs485Error::Serial)
+    }
+}
+
+impl<T, P, D> embedded_io::ReadReady for Rs485<T, P, D>
+where
+    T: embedded_io::ReadReady,
+    P: digital::ErrorType,
+{
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.serial.read_ready().map_err(Rs485Error::Serial)
+    }
+}
+
+impl<T, P, D> embedded_io::Write for Rs485<T, P, D>
+where
+    T: embedded_io::Write,
+    P: OutputPin,
+    D: embedded_hal::delay::DelayNs,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if !self.transmitting {
+            self.pin.set_high().map_err(Rs485Error::Pin)?;
+            self.transmitting = true;
+            self.delay.delay_us(self.enable_delay);
+        }
+        self.serial.write(buf).map_err(Rs485Error::Serial)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        let res = self.serial.flush().map_err(Rs485Error::Serial);
+        if self.transmitting {
+            self.delay.delay_us(self.release_delay);
+            self.pin.set_low().map_err(Rs485Error::Pin)?;
+            self.transmitting = false;
+        }
+        res
+    }
+}
+
+impl<T, P, D> embedded_io_async::Read for Rs485<T, P, D>
+where
+    T: embedded_io_async::Read + embedded_io_async::Write,
+    P: OutputPin,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        // nobody will answer while we hold the bus
+        if self.transmitting {
+            embedded_io_async::Write::flush(self).await?;
+        }
+        self.serial.read(buf).await.map_err(Rs485Error::Serial)
+    }
+}
+
+impl<T, P, D> embedded_io_async::Write for Rs485<T, P, D>
+where
+    T: embedded_io_async::Write,
+    P: OutputPin,
+    D: embedded_hal_async::delay::DelayNs,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        if !self.transmitting {
+            self.pin.set_high().map_err(Rs485Error::Pin)?;
+            self.transmitting = true;
+            self.delay.delay_us(self.enable_delay).await;
+        }
+        self.serial.write(buf).await.map_err(Rs485Error::Serial)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        let res = self.serial.flush().await.map_err(Rs485Error::Serial);
+        if self.transmitting {
+            self.delay.delay_us(self.release_delay).await;
+            self.pin.set_low().map_err(Rs485Error::Pin)?;
+            self.transmitting = false;
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Rs485;
+    use crate::mock::fixtures::{ID, SDS011, init_script, query, sleep_set};
+    use crate::mock::{Kind, Mock, MockDelay, Reporting, ReportingMode, SleepMode, Transaction};
+    use crate::{Config, FirmwareVersion};
+    use core::convert::Infallible;
+    use core::time::Duration;
+    use embedded_hal::digital::{ErrorType, OutputPin};
+
+    /// Counts how often the bus was taken and released.
+    #[derive(Default)]
+    struct Pin {
+        high: bool,
+        taken: usize,
+        released: usize,
+    }
+
+    impl ErrorType for Pin {
+        type Error = Infallible;
+    }
+
+    impl OutputPin for Pin {
+        fn set_low(&mut self) -> Result<(), Self::Error> {
+            self.released += usize::from(self.high);
+            self.high = false;
+            Ok(())
+        }
+
+        fn set_high(&mut self) -> Result<(), Self::Error> {
+            self.taken += usize::from(!self.high);
+            self.high = true;
+            Ok(())
+        }
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn pin_around_each_command() {
+        let mut script = init_script();
+        script.extend([
+            sleep_set(SleepMode::Work, Some(ID)),
+            query(1, 2),
+            query(3, 4),
+            sleep_set(SleepMode::Sleep, Some(ID)),
+        ]);
+        let mut serial = Mock::new(script);
+        let mut pin = Pin::default();
+        let mut turnaround = MockDelay::new();
+
+        let rs485 = Rs485::new(&mut serial, &mut pin, &mut turnaround).set_enable_delay(10);
+        let sensor = SDS011::new(rs485, Config::default());
+        let mut sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        sensor.measure(&mut MockDelay::new()).await.unwrap();
+
+        serial.done();
+        assert!(!pin.high);
+        assert_eq!((pin.taken, pin.released), (8, 8));
+        assert_eq!(turnaround.delays().len(), 16);
+        assert_eq!(
+            turnaround.delays()[..2],
+            [Duration::from_micros(10), Duration::from_micros(1_100)]
+        );
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn addressed_sensor() {
+        let query = Kind::ReportingMode(Reporting::new_set(ReportingMode::Query));
+        let firmware = Kind::FWVersion(Some(FirmwareVersion::new(15, 7, 10)));
+        let mut serial = Mock::new([
+            sleep_set(SleepMode::Work, Some(ID)),
+            Transaction::command(query, Some(ID)).reply(query, ID),
+            Transaction::command(Kind::FWVersion(None), Some(ID)).reply(firmware, ID),
+            sleep_set(SleepMode::Sleep, Some(ID)),
+        ]);
+
+        let rs485 = Rs485::new(&mut serial, Pin::default(), MockDelay::new());
+        let sensor = SDS011::new(rs485, Config::default().set_sensor_id(ID));
+        let sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        assert_eq!(sensor.id(), ID);
+        serial.done();
+    }
+
+    #[test]
+    fn read_releases_the_bus() {
+        use embedded_io::{Read, Write};
+
+        let mut serial = Mock::new([sleep_set(SleepMode::Work, None)]);
+        let mut pin = Pin::default();
+        let mut rs485 = Rs485::new(&mut serial, &mut pin, MockDelay::new());
+
+        let wake = crate::message::Message::new(
+            crate::Kind::Sleep(crate::Sleep::new_set(SleepMode::Work)),
+            None,
+        );
+        rs485.write_all(&wake.create_query()).unwrap();
+        let mut reply = [0u8; 10];
+        rs485.read_exact(&mut reply).unwrap();
+
+        serial.done();
+        assert_eq!((pin.high, pin.taken, pin.released), (false, 1, 1));
+    }
+}
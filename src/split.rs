@@ -0,0 +1,177 @@
+//! Combining separate receive and transmit halves into one serial port.
+//!
+//! HALs like embassy or esp-hal hand out UARTs as independent `UartRx` and
+//! `UartTx` halves, and on some boards they even belong to different
+//! peripherals. [`Split`] joins any reader and writer into the
+//! `Read + Write` the driver expects; `SDS011::new_split()` does this for you.
+
+use embedded_io::{ErrorKind, ErrorType};
+use thiserror::Error;
+
+/// A serial port made of a separate receive (`R`) and transmit (`W`) half.
+///
+/// Implements the blocking as well as the async traits,
+/// depending on what the halves implement.
+#[derive(Debug)]
+pub struct Split<R, W> {
+    rx: R,
+    tx: W,
+}
+
+impl<R, W> Split<R, W> {
+    /// Join the receive half `rx` and the transmit half `tx`.
+    pub const fn new(rx: R, tx: W) -> Self {
+        Self { rx, tx }
+    }
+
+    /// Give back both halves.
+    pub fn into_inner(self) -> (R, W) {
+        (self.rx, self.tx)
+    }
+}
+
+/// Error type of a [`Split`], telling which half failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum SplitError<R, W> {
+    /// The receive half returned an error.
+    #[error("receive half: {0}")]
+    Read(R),
+    /// The transmit half returned an error.
+    #[error("transmit half: {0}")]
+    Write(W),
+}
+
+impl<R, W> embedded_io::Error for SplitError<R, W>
+where
+    R: embedded_io::Error,
+    W: embedded_io::Error,
+{
+    fn kind(&self) -> ErrorKind {
+        match self {
+            Self::Read(e) => e.kind(),
+            Self::Write(e) => e.kind(),
+        }
+    }
+}
+
+impl<R: ErrorType, W: ErrorType> ErrorType for Split<R, W> {
+    type Error = SplitError<R::Error, W::Error>;
+}
+
+impl<R, W> embedded_io::Read for Split<R, W>
+where
+    R: embedded_io::Read,
+    W: ErrorType,
+{
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.rx.read(buf).map_err(SplitError::Read)
+    }
+}
+
+impl<R, W> embedded_io::ReadReady for Split<R, W>
+where
+    R: embedded_io::ReadReady,
+    W: ErrorType,
+{
+    fn read_ready(&mut self) -> Result<bool, Self::Error> {
+        self.rx.read_ready().map_err(SplitError::Read)
+    }
+}
+
+impl<R, W> embedded_io::Write for Split<R, W>
+where
+    R: ErrorType,
+    W: embedded_io::Write,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.tx.write(buf).map_err(SplitError::Write)
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.tx.flush().map_err(SplitError::Write)
+    }
+}
+
+impl<R, W> embedded_io_async::Read for Split<R, W>
+where
+    R: embedded_io_async::Read,
+    W: ErrorType,
+{
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.rx.read(buf).await.map_err(SplitError::Read)
+    }
+}
+
+impl<R, W> embedded_io_async::Write for Split<R, W>
+where
+    R: ErrorType,
+    W: embedded_io_async::Write,
+{
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.tx.write(buf).await.map_err(SplitError::Write)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.tx.flush().await.map_err(SplitError::Write)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Split, SplitError};
+    use crate::message::{Message, SEND_BUF_SIZE};
+    use crate::mock::MockDelay;
+    use crate::mock::fixtures::SDS011;
+    use crate::{
+        Config, FirmwareVersion, Kind, Reporting, ReportingMode, SDS011Error, Sleep, SleepMode,
+    };
+    use alloc::vec::Vec;
+
+    const ID: u16 = 0xA160;
+
+    fn init_exchange() -> [Kind; 4] {
+        [
+            Kind::Sleep(Sleep::new_set(SleepMode::Work)),
+            Kind::ReportingMode(Reporting::new_set(ReportingMode::Query)),
+            Kind::FWVersion(None),
+            Kind::Sleep(Sleep::new_set(SleepMode::Sleep)),
+        ]
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn separate_halves() {
+        let mut replies = Vec::new();
+        for kind in init_exchange() {
+            let kind = match kind {
+                Kind::FWVersion(_) => Kind::FWVersion(Some(FirmwareVersion::new(15, 7, 10))),
+                kind => kind,
+            };
+            replies.extend(Message::new(kind, Some(ID)).create_reply());
+        }
+        let mut sent = [0u8; 4 * SEND_BUF_SIZE];
+
+        let sensor = SDS011::new_split(replies.as_slice(), sent.as_mut_slice(), Config::default());
+        let sensor = sensor.init(&mut MockDelay::new()).await.unwrap();
+        assert_eq!(sensor.id(), ID);
+
+        for (frame, kind) in sent.chunks(SEND_BUF_SIZE).zip(init_exchange()) {
+            assert_eq!(frame, Message::new(kind, None).create_query());
+        }
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn errors_name_the_half() {
+        let reply =
+            Message::new(Kind::Sleep(Sleep::new_set(SleepMode::Work)), Some(ID)).create_reply();
+        let mut sent = [0u8; SEND_BUF_SIZE + 1];
+
+        // the second command does not fit into the transmit buffer
+        let serial = Split::new(reply.as_slice(), sent.as_mut_slice());
+        let sensor = SDS011::new(serial, Config::default());
+        let res = sensor.init(&mut MockDelay::new()).await;
+        assert!(matches!(
+            res,
+            Err(SDS011Error::WriteError(SplitError::Write(_)))
+        ));
+    }
+}
@@ -0,0 +1,588 @@
+//! Rolling statistics of measurements.
+//!
+//! All accumulators are allocation-free and sized at compile time:
+//! * [`Summary`] keeps the count, minimum, maximum, mean and variance of
+//!   every measurement pushed since it was created or reset, in constant
+//!   space (Welford's algorithm),
+//! * [`Window`] averages the last `N` measurements,
+//! * [`TimeWindow`] averages the measurements of the last hour, day or
+//!   any other span, holding at most `N` of them.
+//!
+//! ```ignore
+//! // one reading per minute
+//! let mut hour = Window::<60>::new();
+//! // the 24 hourly averages of a day
+//! let mut day = TimeWindow::<24>::new(Duration::from_secs(24 * 3600));
+//! loop {
+//!     hour.push(sensor.measure().await?);
+//!     if hour.is_full() {
+//!         day.push(clock.now_us(), hour.mean().unwrap());
+//!         hour.clear();
+//!         publish(day.mean());
+//!     }
+//! }
+//! ```
+//!
+//! A long [`TimeWindow`] at the sensor's full rate takes a lot of memory;
+//! feeding it the averages of shorter windows, as above, keeps it small.
+
+use crate::Measurement;
+use crate::humidity::tenths;
+use core::cmp::Ordering;
+use core::time::Duration;
+
+/// Running statistics of one pollutant.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Running {
+    count: u32,
+    min: u16,
+    max: u16,
+    /// in µg/m3
+    mean: f32,
+    /// sum of squared differences from the mean
+    m2: f32,
+}
+
+impl Default for Running {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Running {
+    /// Start without any values.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            count: 0,
+            min: u16::MAX,
+            max: 0,
+            mean: 0.0,
+            m2: 0.0,
+        }
+    }
+
+    /// Add a raw value, in 0.1 µg/m3.
+    #[allow(clippy::cast_precision_loss)]
+    pub fn push(&mut self, value: u16) {
+        let x = f32::from(value) / 10.0;
+        self.count = self.count.saturating_add(1);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        let delta = x - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// How many values were added.
+    #[must_use]
+    pub const fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// The smallest value. Divide by ten to get µg/m3.
+    #[must_use]
+    pub const fn min(&self) -> Option<u16> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.min)
+        }
+    }
+
+    /// The largest value. Divide by ten to get µg/m3.
+    #[must_use]
+    pub const fn max(&self) -> Option<u16> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.max)
+        }
+    }
+
+    /// The mean, in µg/m3.
+    #[must_use]
+    pub const fn mean(&self) -> Option<f32> {
+        if self.count == 0 {
+            None
+        } else {
+            Some(self.mean)
+        }
+    }
+
+    /// The sample variance, in (µg/m3)², or `None` for less than two values.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn variance(&self) -> Option<f32> {
+        (self.count > 1).then(|| self.m2 / (self.count - 1) as f32)
+    }
+}
+
+/// Running statistics of both pollutants.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Summary {
+    pm25: Running,
+    pm10: Running,
+}
+
+impl Default for Summary {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Summary {
+    /// Start without any measurements.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            pm25: Running::new(),
+            pm10: Running::new(),
+        }
+    }
+
+    /// Add a measurement.
+    pub fn push(&mut self, measurement: &Measurement) {
+        self.pm25.push(measurement.pm25());
+        self.pm10.push(measurement.pm10());
+    }
+
+    /// Forget all measurements.
+    pub const fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    /// How many measurements were added.
+    #[must_use]
+    pub const fn count(&self) -> u32 {
+        self.pm25.count
+    }
+
+    /// The smallest values of both pollutants,
+    /// which may come from different measurements.
+    #[must_use]
+    pub fn min(&self) -> Option<Measurement> {
+        Some(Measurement::new(self.pm25.min()?, self.pm10.min()?))
+    }
+
+    /// The largest values of both pollutants,
+    /// which may come from different measurements.
+    #[must_use]
+    pub fn max(&self) -> Option<Measurement> {
+        Some(Measurement::new(self.pm25.max()?, self.pm10.max()?))
+    }
+
+    /// The mean of both pollutants, rounded to 0.1 µg/m3.
+    #[must_use]
+    pub fn mean(&self) -> Option<Measurement> {
+        Some(Measurement::new(
+            tenths(self.pm25.mean()?),
+            tenths(self.pm10.mean()?),
+        ))
+    }
+
+    /// The statistics of PM2.5 alone, e.g. for its variance.
+    #[must_use]
+    pub const fn pm25(&self) -> &Running {
+        &self.pm25
+    }
+
+    /// The statistics of PM10 alone, e.g. for its variance.
+    #[must_use]
+    pub const fn pm10(&self) -> &Running {
+        &self.pm10
+    }
+}
+
+/// A fixed-capacity queue, the oldest item first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Ring<T, const N: usize> {
+    items: [Option<T>; N],
+    head: usize,
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> Ring<T, N> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            items: [None; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    /// Append `item`, returning the oldest one if the ring was full.
+    pub(crate) const fn push(&mut self, item: T) -> Option<T> {
+        if N == 0 {
+            return Some(item);
+        }
+        let evicted = if self.len == N { self.pop() } else { None };
+        self.items[(self.head + self.len) % N] = Some(item);
+        self.len += 1;
+        evicted
+    }
+
+    const fn pop(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let item = self.items[self.head].take();
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        item
+    }
+
+    fn front(&self) -> Option<&T> {
+        self.items.get(self.head)?.as_ref().filter(|_| self.len > 0)
+    }
+
+    /// The items, the oldest first.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &T> {
+        (0..self.len).filter_map(|i| self.items.get((self.head + i) % N)?.as_ref())
+    }
+}
+
+/// Values that have a [`median()`].
+pub(crate) trait Midpoint: Copy {
+    /// A total order, even of floats.
+    fn compare(&self, other: &Self) -> Ordering;
+
+    /// The value halfway between `self` and `other`,
+    /// rounded down for integers.
+    fn midpoint(self, other: Self) -> Self;
+}
+
+impl Midpoint for u16 {
+    fn compare(&self, other: &Self) -> Ordering {
+        self.cmp(other)
+    }
+
+    fn midpoint(self, other: Self) -> Self {
+        Self::midpoint(self, other)
+    }
+}
+
+impl Midpoint for f32 {
+    fn compare(&self, other: &Self) -> Ordering {
+        self.total_cmp(other)
+    }
+
+    fn midpoint(self, other: Self) -> Self {
+        Self::midpoint(self, other)
+    }
+}
+
+impl Midpoint for f64 {
+    fn compare(&self, other: &Self) -> Ordering {
+        self.total_cmp(other)
+    }
+
+    fn midpoint(self, other: Self) -> Self {
+        Self::midpoint(self, other)
+    }
+}
+
+/// The median of `values`, the midpoint of the middle two for an even
+/// count, sorting `values` on the way. `None` if there are none.
+pub(crate) fn median<T: Midpoint>(values: &mut [T]) -> Option<T> {
+    values.sort_unstable_by(T::compare);
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        Some(values.get(mid.checked_sub(1)?)?.midpoint(*values.get(mid)?))
+    } else {
+        values.get(mid).copied()
+    }
+}
+
+/// Sums of the measurements in a window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Sums {
+    pm25: u64,
+    pm10: u64,
+}
+
+impl Sums {
+    fn add(&mut self, [pm25, pm10]: [u16; 2]) {
+        self.pm25 += u64::from(pm25);
+        self.pm10 += u64::from(pm10);
+    }
+
+    fn remove(&mut self, [pm25, pm10]: [u16; 2]) {
+        self.pm25 -= u64::from(pm25);
+        self.pm10 -= u64::from(pm10);
+    }
+
+    /// The mean of `len` measurements, rounded.
+    #[allow(clippy::cast_possible_truncation)]
+    fn mean(&self, len: usize) -> Option<Measurement> {
+        let len = u64::try_from(len).ok().filter(|&len| len > 0)?;
+        // the mean of u16 values fits a u16
+        let mean = |sum: u64| ((sum + len / 2) / len) as u16;
+        Some(Measurement::new(mean(self.pm25), mean(self.pm10)))
+    }
+}
+
+/// The moving average of the last `N` measurements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Window<const N: usize> {
+    /// PM2.5 and PM10 of each measurement
+    ring: Ring<[u16; 2], N>,
+    sums: Sums,
+}
+
+impl<const N: usize> Default for Window<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Window<N> {
+    /// Start without any measurements.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            ring: Ring::new(),
+            sums: Sums { pm25: 0, pm10: 0 },
+        }
+    }
+
+    /// Add a measurement, dropping the oldest one if there are `N` already.
+    pub fn push(&mut self, measurement: Measurement) {
+        let values = [measurement.pm25(), measurement.pm10()];
+        self.sums.add(values);
+        if let Some(old) = self.ring.push(values) {
+            self.sums.remove(old);
+        }
+    }
+
+    /// Forget all measurements.
+    pub const fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// How many measurements the window holds.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.ring.len
+    }
+
+    /// Whether the window holds no measurements.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.ring.len == 0
+    }
+
+    /// Whether the window holds `N` measurements.
+    #[must_use]
+    pub const fn is_full(&self) -> bool {
+        self.ring.len == N
+    }
+
+    /// The mean of the measurements, rounded to 0.1 µg/m3.
+    #[must_use]
+    pub fn mean(&self) -> Option<Measurement> {
+        self.sums.mean(self.ring.len)
+    }
+}
+
+/// The moving average of the measurements of a time span,
+/// holding at most `N` of them.
+///
+/// Timestamps are in microseconds, like [`capture::Clock::now_us()`],
+/// and must not decrease.
+///
+/// [`capture::Clock::now_us()`]: crate::capture::Clock::now_us
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeWindow<const N: usize> {
+    span_us: u64,
+    /// the time, PM2.5 and PM10 of each measurement
+    ring: Ring<(u64, [u16; 2]), N>,
+    sums: Sums,
+}
+
+impl<const N: usize> TimeWindow<N> {
+    /// Start without any measurements, averaging over `span`.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub const fn new(span: Duration) -> Self {
+        let micros = span.as_micros();
+        Self {
+            span_us: if micros > u64::MAX as u128 {
+                u64::MAX
+            } else {
+                micros as u64
+            },
+            ring: Ring::new(),
+            sums: Sums { pm25: 0, pm10: 0 },
+        }
+    }
+
+    /// Add a measurement taken at `now_us`. Measurements older than the
+    /// span are dropped, and so is the oldest one if there are `N` already.
+    pub fn push(&mut self, now_us: u64, measurement: Measurement) {
+        self.expire(now_us);
+        let values = [measurement.pm25(), measurement.pm10()];
+        self.sums.add(values);
+        if let Some((_, old)) = self.ring.push((now_us, values)) {
+            self.sums.remove(old);
+        }
+    }
+
+    /// Drop the measurements older than the span at `now_us`,
+    /// e.g. before reading the mean after a gap in the measurements.
+    pub fn expire(&mut self, now_us: u64) {
+        let Some(start) = now_us.checked_sub(self.span_us) else {
+            return;
+        };
+        while let Some(&(time, _)) = self.ring.front() {
+            if time > start {
+                break;
+            }
+            if let Some((_, old)) = self.ring.pop() {
+                self.sums.remove(old);
+            }
+        }
+    }
+
+    /// Forget all measurements.
+    pub const fn clear(&mut self) {
+        *self = Self::new(Duration::from_micros(self.span_us));
+    }
+
+    /// How many measurements the window holds.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.ring.len
+    }
+
+    /// Whether the window holds no measurements.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.ring.len == 0
+    }
+
+    /// The time span of the measurements held, from the oldest to the
+    /// newest, which tells whether the window is covered yet.
+    #[must_use]
+    pub fn covered(&self) -> Duration {
+        let oldest = self.ring.front().map_or(0, |&(time, _)| time);
+        let newest = self
+            .ring
+            .len
+            .checked_sub(1)
+            .and_then(|last| self.ring.items.get((self.ring.head + last) % N.max(1)))
+            .copied()
+            .flatten()
+            .map_or(0, |(time, _)| time);
+        Duration::from_micros(newest.saturating_sub(oldest))
+    }
+
+    /// The mean of the measurements, rounded to 0.1 µg/m3.
+    #[must_use]
+    pub fn mean(&self) -> Option<Measurement> {
+        self.sums.mean(self.ring.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Running, Summary, TimeWindow, Window, median};
+    use crate::Measurement;
+    use core::time::Duration;
+
+    const fn m(pm25: u16, pm10: u16) -> Measurement {
+        Measurement::new(pm25, pm10)
+    }
+
+    #[test]
+    fn running() {
+        let mut r = Running::new();
+        assert_eq!(
+            (r.min(), r.max(), r.mean(), r.variance()),
+            (None, None, None, None)
+        );
+        r.push(20);
+        assert_eq!((r.mean(), r.variance()), (Some(2.0), None));
+        for value in [40, 40, 40, 50, 50, 70, 90] {
+            r.push(value);
+        }
+        assert_eq!((r.count(), r.min(), r.max()), (8, Some(20), Some(90)));
+        assert!((r.mean().unwrap() - 5.0).abs() < 1e-6);
+        // population variance 4, sample variance 32 / 7
+        assert!((r.variance().unwrap() - 32.0 / 7.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn summary() {
+        let mut s = Summary::new();
+        assert_eq!(s.mean(), None);
+        s.push(&m(100, 300));
+        s.push(&m(202, 200));
+        assert_eq!(s.count(), 2);
+        assert_eq!(s.min(), Some(m(100, 200)));
+        assert_eq!(s.max(), Some(m(202, 300)));
+        assert_eq!(s.mean(), Some(m(151, 250)));
+        assert!((s.pm10().variance().unwrap() - 50.0).abs() < 1e-4);
+        s.reset();
+        assert_eq!((s.count(), s.max()), (0, None));
+    }
+
+    #[test]
+    fn medians() {
+        assert_eq!(median::<u16>(&mut []), None);
+        assert_eq!(median(&mut [3_u16, 1, 2]), Some(2));
+        // rounded down
+        assert_eq!(median(&mut [4_u16, 1, 2, 9]), Some(3));
+        assert_eq!(median(&mut [4.0_f32, 1.0, 2.0, 9.0]), Some(3.0));
+        assert_eq!(median(&mut [f64::NAN, 1.0, 0.5]), Some(1.0));
+    }
+
+    #[test]
+    fn window() {
+        let mut w = Window::<3>::new();
+        assert_eq!((w.mean(), w.is_empty()), (None, true));
+        w.push(m(10, 100));
+        w.push(m(20, 200));
+        assert_eq!(w.mean(), Some(m(15, 150)));
+        w.push(m(31, 300));
+        assert!(w.is_full());
+        assert_eq!(w.mean(), Some(m(20, 200)));
+        // the oldest drops out
+        w.push(m(40, 400));
+        assert_eq!((w.len(), w.mean()), (3, Some(m(30, 300))));
+        w.clear();
+        assert_eq!(w.mean(), None);
+
+        let mut empty = Window::<0>::new();
+        empty.push(m(10, 10));
+        assert_eq!(empty.mean(), None);
+    }
+
+    #[test]
+    fn time_window() {
+        const S: u64 = 1_000_000;
+        let mut w = TimeWindow::<4>::new(Duration::from_secs(10));
+        w.push(0, m(10, 10));
+        w.push(5 * S, m(20, 20));
+        assert_eq!(w.mean(), Some(m(15, 15)));
+        assert_eq!(w.covered(), Duration::from_secs(5));
+        // the first is 10 s old now
+        w.push(10 * S, m(30, 30));
+        assert_eq!((w.len(), w.mean()), (2, Some(m(25, 25))));
+
+        // capacity
+        for t in 11..14 {
+            w.push(t * S, m(100, 100));
+        }
+        assert_eq!((w.len(), w.mean()), (4, Some(m(83, 83))));
+        assert_eq!(w.covered(), Duration::from_secs(3));
+
+        // a gap in the measurements
+        w.expire(30 * S);
+        assert_eq!((w.len(), w.mean()), (0, None));
+        assert_eq!(w.covered(), Duration::ZERO);
+        w.push(31 * S, m(7, 7));
+        w.clear();
+        assert!(w.is_empty());
+    }
+}
@@ -0,0 +1,162 @@
+// The supervisor, shared by the `blocking` and `asynch` modules
+// like the driver it wraps (see `crate::supervisor`).
+
+use crate::supervisor::{Counters, Health};
+
+/// Owns a sensor and re-initializes it after errors,
+/// see [the supervisor module](crate::supervisor).
+///
+/// Polls the sensor by default; use `set_periodic()` to keep it in
+/// periodic mode instead. Needs a serial interface that implements
+/// `ReadReady`, so the line can be drained before re-initializing.
+pub struct Supervisor<RW, P = NoPower, O = NoObserver> {
+    // the sensor's actual state is tracked by `health` and `periodic`
+    sensor: SDS011<RW, Uninitialized, P, O>,
+    periodic: Option<u8>,
+    health: Health,
+    counters: Counters,
+    min_backoff: u32,
+    max_backoff: u32,
+}
+
+impl<RW, P, O> Supervisor<RW, P, O>
+where
+    RW: Read + Write + ReadReady,
+    P: OutputPin,
+    O: Observer,
+{
+    /// Supervise `sensor`, which is initialized with the first measurement.
+    pub const fn new(sensor: SDS011<RW, Uninitialized, P, O>) -> Self {
+        Self {
+            sensor,
+            periodic: None,
+            health: Health::Starting,
+            counters: Counters {
+                measurements: 0,
+                errors: 0,
+                consecutive_errors: 0,
+                recoveries: 0,
+                discarded_bytes: 0,
+            },
+            min_backoff: 1_000,
+            max_backoff: 300_000,
+        }
+    }
+
+    /// Keep the sensor in periodic mode, measuring every 0-30 `minutes`
+    /// (see `SDS011::make_periodic()`), instead of polling it.
+    #[must_use]
+    pub const fn set_periodic(mut self, minutes: u8) -> Self {
+        self.periodic = Some(minutes);
+        self
+    }
+
+    /// How many milliseconds to wait before re-initializing after an error:
+    /// `min`, doubled for every further error in a row, but at most `max`.
+    /// Defaults to 1 second and 5 minutes.
+    #[must_use]
+    pub const fn set_backoff(mut self, min: u32, max: u32) -> Self {
+        self.min_backoff = min;
+        self.max_backoff = max;
+        self
+    }
+
+    /// Take a measurement, (re-)initializing the sensor first if necessary.
+    ///
+    /// In polling mode, this triggers a measurement like
+    /// `SDS011::<_, Polling>::measure()`; in periodic mode,
+    /// it waits for the next one.
+    ///
+    /// # Errors
+    /// Any [`SDS011Error`] of the initialization or the measurement.
+    /// The sensor stays supervised: the next call waits out the backoff and
+    /// re-initializes it.
+    ///
+    /// # Cancel Safety
+    /// If the future is dropped before it completes, e.g. by a timeout,
+    /// the measurement counts as failed. The next call re-initializes the
+    /// sensor without waiting for the rest of an interrupted reply.
+    #[maybe_async]
+    pub async fn measure<D: DelayNs>(
+        &mut self,
+        delay: &mut D,
+    ) -> Result<Measurement, SDS011Error<RW::Error>> {
+        // a measurement cut short, e.g. by a timeout, failed as well
+        if self.health == Health::Healthy && self.sensor.interrupted() {
+            self.failed();
+        }
+        if self.health != Health::Healthy {
+            if let Err(e) = self.restart(delay).await {
+                self.failed();
+                return Err(e);
+            }
+            if self.health == Health::Recovering {
+                self.counters.recoveries = self.counters.recoveries.saturating_add(1);
+            }
+            self.health = Health::Healthy;
+        }
+
+        let res = match self.periodic {
+            Some(_) => self.sensor.report().await,
+            None => self.sensor.poll(delay).await,
+        };
+        match res {
+            Ok(_) => {
+                self.counters.measurements = self.counters.measurements.saturating_add(1);
+                self.counters.consecutive_errors = 0;
+            }
+            Err(_) => self.failed(),
+        }
+        res
+    }
+
+    /// Drain the line and bring the sensor into the requested mode,
+    /// after the backoff if it failed before.
+    #[maybe_async]
+    async fn restart<D: DelayNs>(&mut self, delay: &mut D) -> Result<(), SDS011Error<RW::Error>> {
+        if self.health == Health::Recovering {
+            let backoff = self.counters.backoff(self.min_backoff, self.max_backoff);
+            delay.delay_ms(backoff).await;
+        }
+
+        // give up on an exchange cut short without waiting for the sensor,
+        // which may be what stopped answering; it is woken up again anyway
+        self.sensor.abandon().await?;
+        self.sensor.measuring = false;
+        let discarded = self.sensor.drain().await?;
+        let discarded = u32::try_from(discarded).unwrap_or(u32::MAX);
+        self.counters.discarded_bytes = self.counters.discarded_bytes.saturating_add(discarded);
+
+        self.sensor.initialize(delay).await?;
+        if let Some(minutes) = self.periodic {
+            self.sensor.start_periodic(delay, minutes).await?;
+        }
+        Ok(())
+    }
+
+    const fn failed(&mut self) {
+        self.health = Health::Recovering;
+        self.counters.errors = self.counters.errors.saturating_add(1);
+        self.counters.consecutive_errors = self.counters.consecutive_errors.saturating_add(1);
+    }
+
+    /// The sensor's state.
+    pub const fn health(&self) -> Health {
+        self.health
+    }
+
+    /// What happened to the sensor so far.
+    pub const fn counters(&self) -> Counters {
+        self.counters
+    }
+
+    /// Get the sensor's ID (0xFFFF, or as configured, until initialized).
+    pub const fn id(&self) -> u16 {
+        self.sensor.sensor_id
+    }
+
+    /// Get the sensor's firmware version (all zeros until initialized).
+    pub const fn version(&self) -> FirmwareVersion {
+        self.sensor.firmware
+    }
+}
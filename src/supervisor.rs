@@ -0,0 +1,240 @@
+//! Keeping a sensor running across errors.
+//!
+//! On its own, the driver hands every error to the caller, and a failed
+//! state transition consumes the sensor. A `Supervisor` (in the
+//! [blocking](crate::blocking::Supervisor) and
+//! [async](crate::asynch::Supervisor) flavors) owns the sensor instead:
+//! when a measurement fails, it returns the error as usual, and on the next
+//! call it waits out a backoff, drains the line, re-initializes the sensor
+//! and restores periodic mode if that was requested.
+//! Its [`Health`] and [`Counters`] tell how well that is going.
+//!
+//! ```ignore
+//! let sensor = SDS011::new(uart, Config::default());
+//! let mut sensor = Supervisor::new(sensor).set_backoff(1_000, 60_000);
+//! loop {
+//!     match sensor.measure(&mut delay).await {
+//!         Ok(m) => println!("{m}"),
+//!         Err(e) => println!("{e}, {:?}", sensor.counters()),
+//!     }
+//! }
+//! ```
+
+/// The state of a supervised sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Health {
+    /// Not initialized yet.
+    Starting,
+    /// The last operation succeeded.
+    Healthy,
+    /// The last operation failed or was cancelled;
+    /// the sensor is re-initialized with the next measurement.
+    Recovering,
+}
+
+/// What happened to a supervised sensor so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Counters {
+    /// Measurements taken.
+    pub measurements: u32,
+    /// Failed operations, including failed re-initializations
+    /// and cancelled measurements.
+    pub errors: u32,
+    /// Failed operations since the last success.
+    pub consecutive_errors: u32,
+    /// Successful re-initializations after an error.
+    pub recoveries: u32,
+    /// Bytes drained from the line before re-initializing.
+    pub discarded_bytes: u32,
+}
+
+impl Counters {
+    /// How many milliseconds to wait before the next re-initialization:
+    /// `min`, doubled for every further consecutive error, at most `max`.
+    pub(crate) const fn backoff(&self, min: u32, max: u32) -> u32 {
+        let doublings = self.consecutive_errors.saturating_sub(1);
+        let factor = match 1u32.checked_shl(doublings) {
+            Some(factor) => factor,
+            None => u32::MAX,
+        };
+        let backoff = min.saturating_mul(factor);
+        if backoff < max { backoff } else { max }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Counters, Health};
+    use crate::message::{Message, RECV_BUF_SIZE};
+    use crate::mock::fixtures::{
+        ID, SDS011, Stall, Supervisor, cancel, init_script, measurement, reinit_script, sleep_set,
+    };
+    use crate::mock::{
+        Kind, Mock, MockDelay, Reporting, ReportingMode, Sleep, SleepMode, Transaction,
+        WorkingPeriod,
+    };
+    use crate::{Config, Measurement, SDS011Error};
+    use core::sync::atomic::AtomicUsize;
+    use core::sync::atomic::Ordering::Relaxed;
+    use core::time::Duration;
+
+    fn config() -> Config {
+        Config::default().set_sleep_delay(10).set_measure_delay(20)
+    }
+
+    #[test]
+    fn backoff() {
+        let mut counters = Counters::default();
+        let mut backoffs = [0; 6];
+        for b in &mut backoffs {
+            counters.consecutive_errors += 1;
+            *b = counters.backoff(100, 1_000);
+        }
+        assert_eq!(backoffs, [100, 200, 400, 800, 1_000, 1_000]);
+
+        counters.consecutive_errors = u32::MAX;
+        assert_eq!(counters.backoff(100, u32::MAX), u32::MAX);
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn recovers_after_error() {
+        let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+        let mut script = init_script();
+        script.extend(measurement(1, 2));
+        // a garbled reply, followed by more noise
+        script.push(Transaction::command(wake, Some(ID)).reply_raw(&[0xAA; 13]));
+        script.extend(reinit_script());
+        script.extend(measurement(1236, 2618));
+        let mut serial = Mock::new(script);
+        let mut delay = MockDelay::new();
+
+        let sensor = SDS011::new(&mut serial, config());
+        let mut sensor = Supervisor::new(sensor).set_backoff(100, 1_000);
+        assert_eq!(sensor.health(), Health::Starting);
+
+        sensor.measure(&mut delay).await.unwrap();
+        assert_eq!(sensor.health(), Health::Healthy);
+        assert_eq!(sensor.id(), ID);
+
+        let err = sensor.measure(&mut delay).await.err().unwrap();
+        assert!(matches!(err, SDS011Error::ParseError { .. }));
+        assert_eq!(sensor.health(), Health::Recovering);
+
+        let m = sensor.measure(&mut delay).await.unwrap();
+        assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+        assert_eq!(sensor.health(), Health::Healthy);
+
+        let counters = sensor.counters();
+        assert_eq!(
+            (
+                counters.measurements,
+                counters.errors,
+                counters.consecutive_errors,
+                counters.recoveries,
+                counters.discarded_bytes
+            ),
+            (2, 1, 0, 1, 3)
+        );
+        assert_eq!(delay.delays()[4], Duration::from_millis(100));
+        serial.done();
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn backs_off_while_failing() {
+        let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+        // the sensor does not answer at all
+        let mut serial = Mock::new([
+            Transaction::command(wake, None),
+            Transaction::command(wake, None),
+            Transaction::command(wake, None),
+        ]);
+        let mut delay = MockDelay::new();
+
+        let sensor = SDS011::new(&mut serial, config());
+        let mut sensor = Supervisor::new(sensor).set_backoff(100, 150);
+        for _ in 0..3 {
+            let err = sensor.measure(&mut delay).await.err().unwrap();
+            assert!(matches!(err, SDS011Error::UnexpectedEof));
+        }
+
+        assert_eq!(sensor.health(), Health::Recovering);
+        assert_eq!(sensor.counters().consecutive_errors, 3);
+        assert_eq!(sensor.counters().recoveries, 0);
+        assert_eq!(
+            delay.delays(),
+            [10, 100, 10, 150, 10].map(Duration::from_millis)
+        );
+        serial.done();
+    }
+
+    #[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+    async fn restores_periodic_mode() {
+        let period = Kind::WorkingPeriod(WorkingPeriod::new_set(5));
+        let active = Kind::ReportingMode(Reporting::new_set(ReportingMode::Active));
+        let start = [
+            sleep_set(SleepMode::Work, Some(ID)),
+            Transaction::command(period, Some(ID)).reply(period, ID),
+            Transaction::command(active, Some(ID)).reply(active, ID),
+        ];
+        let data = Kind::Query(Some(Measurement::new(1236, 2618)));
+
+        let mut script = init_script();
+        script.extend(start.clone());
+        // the line goes quiet in the middle of a report
+        script.push(Transaction::unsolicited().reply_raw(&[0xAA, 0xC0]));
+        script.extend(reinit_script());
+        script.extend(start);
+        script.push(Transaction::unsolicited().reply(data, ID));
+        let mut serial = Mock::new(script);
+        let mut delay = MockDelay::new();
+
+        let sensor = SDS011::new(&mut serial, config());
+        let mut sensor = Supervisor::new(sensor).set_periodic(5);
+        let err = sensor.measure(&mut delay).await.err().unwrap();
+        assert!(matches!(err, SDS011Error::UnexpectedEof));
+
+        let m = sensor.measure(&mut delay).await.unwrap();
+        assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+        assert_eq!(sensor.counters().recoveries, 1);
+        serial.done();
+    }
+
+    #[tokio::test]
+    async fn recovers_after_timeout_mid_reply() {
+        let reply =
+            Message::new(Kind::Query(Some(Measurement::new(1, 2))), Some(ID)).create_reply();
+        let mut script = init_script();
+        script.extend(measurement(1, 2));
+        script.extend([
+            sleep_set(SleepMode::Work, Some(ID)),
+            // the sensor hangs in the middle of its reply
+            Transaction::command(Kind::Query(None), Some(ID)).reply_raw(&reply[..4]),
+        ]);
+        script.extend(reinit_script());
+        script.extend(measurement(1236, 2618));
+        let mut serial = Mock::new(script);
+        let reads = AtomicUsize::new(usize::MAX);
+        let writes = AtomicUsize::new(usize::MAX);
+        let mut delay = MockDelay::new();
+
+        let stall = Stall {
+            serial: &mut serial,
+            reads: &reads,
+            writes: &writes,
+        };
+        let sensor = crate::asynch::SDS011::new(stall, config());
+        let mut sensor = crate::asynch::Supervisor::new(sensor).set_backoff(100, 1_000);
+        sensor.measure(&mut delay).await.unwrap();
+
+        reads.store(RECV_BUF_SIZE + 4, Relaxed);
+        cancel(sensor.measure(&mut delay));
+        reads.store(usize::MAX, Relaxed);
+        let m = sensor.measure(&mut delay).await.unwrap();
+        assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+        assert_eq!(sensor.health(), Health::Healthy);
+        let counters = sensor.counters();
+        assert_eq!((counters.errors, counters.recoveries), (1, 1));
+        serial.done();
+    }
+}
@@ -0,0 +1,8 @@
+//! Tests of the driver, run against the flavor selected by the `sync`
+//! feature (or both, where they say so), grouped by feature.
+
+mod cancel;
+mod driver;
+mod dynamic;
+mod flavors;
+mod power;
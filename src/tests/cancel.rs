@@ -0,0 +1,161 @@
+//! Cleaning up after a dropped `measure()` future.
+
+use crate::message::{Message, RECV_BUF_SIZE, SEND_BUF_SIZE};
+use crate::mock::fixtures::{ID, Stall, cancel, init_script, measurement, query, sleep_set};
+use crate::mock::{Kind, Mock, MockDelay, SleepMode, Transaction};
+use crate::{Config, Measurement};
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::Relaxed;
+
+/// Spins the fan forever.
+struct Spin;
+
+impl embedded_hal_async::delay::DelayNs for Spin {
+    async fn delay_ns(&mut self, _: u32) {}
+
+    async fn delay_ms(&mut self, ms: u32) {
+        if ms >= 1_000 {
+            core::future::pending::<()>().await;
+        }
+    }
+}
+
+#[tokio::test]
+async fn cancelled_while_spinning() {
+    let mut script = init_script();
+    script.extend([sleep_set(SleepMode::Work, Some(ID)), query(1, 2)]);
+    // the fan is still running
+    script.push(sleep_set(SleepMode::Sleep, Some(ID)));
+    script.extend(measurement(1236, 2618));
+    let mut serial = Mock::new(script);
+    let mut delay = MockDelay::new();
+
+    let config = Config::default().set_sleep_delay(10);
+    let sensor = crate::asynch::SDS011::new(&mut serial, config);
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+    cancel(sensor.measure(&mut Spin));
+
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+    serial.done();
+}
+
+#[tokio::test]
+async fn cancelled_mid_frame() {
+    let mut script = init_script();
+    script.extend([sleep_set(SleepMode::Work, Some(ID)), query(1, 2)]);
+    script.push(sleep_set(SleepMode::Sleep, Some(ID)));
+    script.extend(measurement(1236, 2618));
+    let mut serial = Mock::new(script.clone());
+    let reads = AtomicUsize::new(usize::MAX);
+    let writes = AtomicUsize::new(usize::MAX);
+    let mut delay = MockDelay::new();
+
+    let stall = Stall {
+        serial: &mut serial,
+        reads: &reads,
+        writes: &writes,
+    };
+    let sensor = crate::asynch::SDS011::new(stall, Config::default());
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+
+    // cut off in the middle of the first measurement reply
+    reads.store(RECV_BUF_SIZE + 4, Relaxed);
+    cancel(sensor.measure(&mut delay));
+    reads.store(usize::MAX, Relaxed);
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+    serial.done();
+
+    // cut off in the middle of the first measurement query
+    let mut serial = Mock::new(script);
+    let stall = Stall {
+        serial: &mut serial,
+        reads: &reads,
+        writes: &writes,
+    };
+    let sensor = crate::asynch::SDS011::new(stall, Config::default());
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+    writes.store(SEND_BUF_SIZE + 7, Relaxed);
+    cancel(sensor.measure(&mut delay));
+    writes.store(usize::MAX, Relaxed);
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+    serial.done();
+}
+
+#[tokio::test]
+async fn reply_never_finished() {
+    let reply = Message::new(Kind::Query(Some(Measurement::new(1, 2))), Some(ID)).create_reply();
+    let mut script = init_script();
+    script.extend([
+        sleep_set(SleepMode::Work, Some(ID)),
+        // the sensor hangs in the middle of its reply
+        Transaction::command(Kind::Query(None), Some(ID)).reply_raw(&reply[..4]),
+        // and answers again after the timeout
+        sleep_set(SleepMode::Sleep, Some(ID)),
+    ]);
+    script.extend(measurement(1236, 2618));
+    let mut serial = Mock::new(script);
+    let reads = AtomicUsize::new(usize::MAX);
+    let writes = AtomicUsize::new(usize::MAX);
+    let mut delay = MockDelay::new();
+
+    let stall = Stall {
+        serial: &mut serial,
+        reads: &reads,
+        writes: &writes,
+    };
+    let sensor = crate::asynch::SDS011::new(stall, Config::default());
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+
+    reads.store(RECV_BUF_SIZE + 4, Relaxed);
+    cancel(sensor.measure(&mut delay));
+    reads.store(usize::MAX, Relaxed);
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+    serial.done();
+}
+
+#[tokio::test]
+async fn cancelled_before_reply() {
+    let mut script = init_script();
+    script.extend([
+        // cut off before the sensor confirms it woke up
+        sleep_set(SleepMode::Work, Some(ID)),
+        sleep_set(SleepMode::Sleep, Some(ID)),
+    ]);
+    script.extend(measurement(1236, 2618));
+    script.extend([
+        sleep_set(SleepMode::Work, Some(ID)),
+        // cut off after the frame head, with more 0xAA among its data
+        query(0xAA, 0xAA),
+        sleep_set(SleepMode::Sleep, Some(ID)),
+    ]);
+    script.extend(measurement(1, 2));
+    let mut serial = Mock::new(script);
+    let reads = AtomicUsize::new(usize::MAX);
+    let writes = AtomicUsize::new(usize::MAX);
+    let mut delay = MockDelay::new();
+
+    let stall = Stall {
+        serial: &mut serial,
+        reads: &reads,
+        writes: &writes,
+    };
+    let sensor = crate::asynch::SDS011::new(stall, Config::default());
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+
+    reads.store(0, Relaxed);
+    cancel(sensor.measure(&mut delay));
+    reads.store(usize::MAX, Relaxed);
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+
+    reads.store(RECV_BUF_SIZE + 1, Relaxed);
+    cancel(sensor.measure(&mut delay));
+    reads.store(usize::MAX, Relaxed);
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1, 2));
+    serial.done();
+}
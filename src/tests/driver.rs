@@ -0,0 +1,122 @@
+//! Initializing, measuring and reporting errors.
+
+use crate::mock::fixtures::{ID, SDS011, init_script, query, sleep_set};
+use crate::mock::{
+    Kind, Mock, MockDelay, Reporting, ReportingMode, Sleep, SleepMode, Transaction, WorkingPeriod,
+};
+use crate::{Config, FirmwareVersion, Measurement, ParseError, SDS011Error};
+use alloc::string::ToString;
+use core::time::Duration;
+
+#[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+async fn init_and_measure() {
+    let mut script = init_script();
+    script.extend([
+        sleep_set(SleepMode::Work, Some(ID)),
+        query(1, 2),
+        query(1236, 2618),
+        sleep_set(SleepMode::Sleep, Some(ID)),
+    ]);
+    let mut serial = Mock::new(script);
+    let mut delay = MockDelay::new();
+    let config = Config::default()
+        .set_sleep_delay(100)
+        .set_measure_delay(2_500);
+
+    let sensor = SDS011::new(&mut serial, config);
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+    assert_eq!(sensor.id(), ID);
+
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+    let quality = m.quality();
+    assert_eq!(quality.source, Some(crate::Source::Query));
+    assert_eq!(quality.sensor_id, Some(ID));
+    assert_eq!(quality.warm_up, Some(2_500));
+    assert!(!quality.at_limit && !quality.calibrated && !quality.filtered);
+
+    serial.done();
+    assert_eq!(
+        delay.delays(),
+        [
+            Duration::from_millis(100),
+            Duration::from_millis(100),
+            Duration::from_millis(2_500)
+        ]
+    );
+}
+
+#[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+async fn periodic_reads_unsolicited() {
+    let mut script = init_script();
+    script.extend([
+        sleep_set(SleepMode::Work, Some(ID)),
+        Transaction::command(Kind::WorkingPeriod(WorkingPeriod::new_set(5)), Some(ID))
+            .reply(Kind::WorkingPeriod(WorkingPeriod::new_set(5)), ID),
+        Transaction::command(
+            Kind::ReportingMode(Reporting::new_set(ReportingMode::Active)),
+            Some(ID),
+        )
+        .reply(
+            Kind::ReportingMode(Reporting::new_set(ReportingMode::Active)),
+            ID,
+        ),
+        Transaction::unsolicited().reply(Kind::Query(Some(Measurement::new(40, 80))), ID),
+    ]);
+    let mut serial = Mock::new(script);
+    let mut delay = MockDelay::new();
+
+    let sensor = SDS011::new(&mut serial, Config::default());
+    let sensor = sensor.init(&mut delay).await.unwrap();
+    let mut sensor = sensor.make_periodic(&mut delay, 5).await.unwrap();
+    let m = sensor.measure().await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (40, 80));
+    assert_eq!(m.quality().source, Some(crate::Source::Periodic));
+    assert_eq!(m.quality().warm_up, Some(30_000));
+
+    serial.done();
+}
+
+#[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+async fn errors_carry_context() {
+    let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+    let asleep = Kind::Sleep(Sleep::new_set(SleepMode::Sleep));
+    let firmware = Kind::FWVersion(Some(FirmwareVersion::new(15, 7, 10)));
+    let noise = [0xAA, 0xC5, 0x06, 0x01, 0x01, 0x00, 0xA1, 0x60, 0x00, 0xAB];
+
+    let mut serial = Mock::new([Transaction::command(wake, None).reply(asleep, ID)]);
+    let sensor = SDS011::new(&mut serial, Config::default());
+    let err = sensor.init(&mut MockDelay::new()).await.err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "sensor FFFF refused \"set sleep mode: work\", replying \"sleep mode is sleep (set)\""
+    );
+    assert!(matches!(
+        err,
+        SDS011Error::OperationFailed { command, received, sensor_id: 0xFFFF }
+            if command == wake && received == asleep
+    ));
+
+    let mut serial = Mock::new([Transaction::command(wake, None).reply(firmware, ID)]);
+    let sensor = SDS011::new(&mut serial, Config::default());
+    let err = sensor.init(&mut MockDelay::new()).await.err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "expected a reply to \"set sleep mode: work\" from sensor FFFF, \
+         received \"firmware version 2015.07.10\""
+    );
+    assert!(matches!(
+        err,
+        SDS011Error::UnexpectedType { expected, received, .. }
+            if expected == wake && received == firmware
+    ));
+
+    let mut serial = Mock::new([Transaction::command(wake, None).reply_raw(&noise)]);
+    let sensor = SDS011::new(&mut serial, Config::default());
+    let err = sensor.init(&mut MockDelay::new()).await.err().unwrap();
+    assert!(matches!(
+        err,
+        SDS011Error::ParseError { source: ParseError::Checksum(0x09, 0x00), raw, command, .. }
+            if raw == noise && command == wake
+    ));
+}
@@ -0,0 +1,107 @@
+//! Driving a sensor whose state is only known at runtime.
+
+use crate::mock::fixtures::{DynSDS011, ID, SDS011, init_script, measurement, sleep_set};
+use crate::mock::{
+    Kind, Mock, MockDelay, Reporting, ReportingMode, Sleep, SleepMode, Transaction, WorkingPeriod,
+};
+use crate::sensor_state::{Polling, Uninitialized};
+use crate::{Config, Measurement, SDS011Error, State};
+use alloc::string::ToString;
+use alloc::vec;
+
+#[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+async fn dynamic_sensor() {
+    let period = Kind::WorkingPeriod(WorkingPeriod::new_set(5));
+    let active = Kind::ReportingMode(Reporting::new_set(ReportingMode::Active));
+    let mut script = init_script();
+    script.extend(measurement(1, 2));
+    script.extend([
+        sleep_set(SleepMode::Work, Some(ID)),
+        Transaction::command(period, Some(ID)).reply(period, ID),
+        Transaction::command(active, Some(ID)).reply(active, ID),
+        Transaction::unsolicited().reply(Kind::Query(Some(Measurement::new(3, 4))), ID),
+    ]);
+    let mut serial = Mock::new(script);
+    let mut delay = MockDelay::new();
+
+    let mut sensor = DynSDS011::from(SDS011::new(&mut serial, Config::default()));
+    assert_eq!(sensor.state(), State::Uninitialized);
+    sensor.init(&mut delay).await.unwrap();
+    assert_eq!((sensor.state(), sensor.id()), (State::Polling, ID));
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1, 2));
+
+    // round trip through the typed API
+    let sensor = SDS011::<_, Uninitialized>::try_from(sensor).err().unwrap();
+    let typed = SDS011::<_, Polling>::try_from(sensor).ok().unwrap();
+    let mut sensor = DynSDS011::from(typed);
+
+    sensor.make_periodic(&mut delay, 5).await.unwrap();
+    assert_eq!(sensor.state(), State::Periodic);
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (3, 4));
+    serial.done();
+}
+
+#[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+async fn dynamic_invalid_state() {
+    let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+    // the sensor does not answer
+    let mut script = vec![Transaction::command(wake, None)];
+    script.extend(init_script());
+    let mut serial = Mock::new(script);
+    let mut delay = MockDelay::new();
+
+    let mut sensor = DynSDS011::from(SDS011::new(&mut serial, Config::default()));
+    let err = sensor.measure(&mut delay).await.err().unwrap();
+    assert!(matches!(
+        err,
+        SDS011Error::InvalidState(State::Uninitialized)
+    ));
+    let err = sensor.make_periodic(&mut delay, 5).await.err().unwrap();
+    assert!(matches!(
+        err,
+        SDS011Error::InvalidState(State::Uninitialized)
+    ));
+
+    // a failed init can be retried
+    let err = sensor.init(&mut delay).await.err().unwrap();
+    assert!(matches!(err, SDS011Error::UnexpectedEof));
+    assert_eq!(sensor.state(), State::Uninitialized);
+    sensor.init(&mut delay).await.unwrap();
+
+    let err = sensor.init(&mut delay).await.err().unwrap();
+    assert!(matches!(err, SDS011Error::InvalidState(State::Polling)));
+    assert_eq!(
+        err.to_string(),
+        "operation not possible while the sensor is polling"
+    );
+    serial.done();
+}
+
+#[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+async fn dynamic_failed_transition() {
+    let period = Kind::WorkingPeriod(WorkingPeriod::new_set(5));
+    let mut script = init_script();
+    script.extend([
+        sleep_set(SleepMode::Work, Some(ID)),
+        // the sensor refuses the working period
+        Transaction::command(period, Some(ID))
+            .reply(Kind::WorkingPeriod(WorkingPeriod::new_set(0)), ID),
+        // and is sent back to sleep
+        sleep_set(SleepMode::Sleep, Some(ID)),
+    ]);
+    script.extend(measurement(1, 2));
+    let mut serial = Mock::new(script);
+    let mut delay = MockDelay::new();
+
+    let mut sensor = DynSDS011::from(SDS011::new(&mut serial, Config::default()));
+    sensor.init(&mut delay).await.unwrap();
+    let err = sensor.make_periodic(&mut delay, 5).await.err().unwrap();
+    assert!(matches!(err, SDS011Error::OperationFailed { .. }));
+    assert_eq!(sensor.state(), State::Polling);
+
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1, 2));
+    serial.done();
+}
@@ -0,0 +1,54 @@
+//! Both flavors are available in every build.
+
+use crate::Config;
+use crate::mock::fixtures::{ID, init_script, query, sleep_set};
+use crate::mock::{Mock, MockDelay, SleepMode};
+use crate::sensor_state::Uninitialized;
+
+#[test]
+fn blocking_flavor() {
+    let mut script = init_script();
+    script.extend([
+        sleep_set(SleepMode::Work, Some(ID)),
+        query(1, 2),
+        query(3, 4),
+        sleep_set(SleepMode::Sleep, Some(ID)),
+    ]);
+    let mut serial = Mock::new(script);
+    let mut delay = MockDelay::new();
+
+    let sensor = crate::blocking::SDS011::new(&mut serial, Config::default());
+    let mut sensor = sensor.init(&mut delay).unwrap();
+    let m = sensor.measure(&mut delay).unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (3, 4));
+    serial.done();
+}
+
+#[tokio::test]
+async fn async_flavor() {
+    let mut script = init_script();
+    script.extend([
+        sleep_set(SleepMode::Work, Some(ID)),
+        query(1, 2),
+        query(3, 4),
+        sleep_set(SleepMode::Sleep, Some(ID)),
+    ]);
+    let mut serial = Mock::new(script);
+    let mut delay = MockDelay::new();
+
+    let sensor = crate::asynch::SDS011::new(&mut serial, Config::default());
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (3, 4));
+    serial.done();
+}
+
+// features cannot switch the crate root to the blocking driver
+#[test]
+fn root_is_async() {
+    let mut serial = Mock::new([]);
+    let _: crate::asynch::SDS011<_, Uninitialized> =
+        crate::SDS011::new(&mut serial, Config::default());
+    let _: crate::asynch::Supervisor<_> =
+        crate::Supervisor::new(crate::asynch::SDS011::new(&mut serial, Config::default()));
+}
@@ -0,0 +1,147 @@
+//! Switching the sensor's supply.
+
+use crate::mock::fixtures::{
+    ID, SDS011, Stall, Switch, cancel, init_script, measurement, query, query_mode, reinit_script,
+    sleep_set,
+};
+use crate::mock::{Kind, Mock, MockDelay, Reporting, ReportingMode, Sleep, SleepMode, Transaction};
+use crate::{Config, SDS011Error};
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::Ordering::Relaxed;
+use core::time::Duration;
+
+fn power_config() -> Config {
+    Config::default()
+        .set_sleep_delay(10)
+        .set_measure_delay(20)
+        .set_power_delay(100)
+}
+
+#[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+async fn power_cycle_after_failures() {
+    let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+    let mut script = init_script();
+    script.extend([
+        // the sensor hangs
+        Transaction::command(wake, Some(ID)),
+        Transaction::command(wake, Some(ID)),
+    ]);
+    // until power cycled and re-initialized
+    script.extend(reinit_script());
+    script.extend(measurement(1236, 2618));
+    let mut serial = Mock::new(script);
+    let mut switch = Switch::default();
+    let mut delay = MockDelay::new();
+
+    let config = power_config().set_power_cycle_after(2);
+    let sensor = SDS011::new_with_power(&mut serial, &mut switch, config);
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+
+    let err = sensor.measure(&mut delay).await.err().unwrap();
+    assert!(matches!(err, SDS011Error::UnexpectedEof));
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+
+    serial.done();
+    assert_eq!(switch.0, [true, false, true]);
+    assert_eq!(
+        delay.delays(),
+        [100, 10, 10, 10, 100, 100, 10, 10, 20].map(Duration::from_millis)
+    );
+}
+
+#[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+async fn power_off_when_idle() {
+    let mut script = init_script();
+    // switched off instead of sent to sleep
+    script.pop();
+    script.extend([
+        query_mode(Some(ID)),
+        sleep_set(SleepMode::Work, Some(ID)),
+        query(1, 2),
+        query(1236, 2618),
+    ]);
+    let mut serial = Mock::new(script);
+    let mut switch = Switch::default();
+    let mut delay = MockDelay::new();
+
+    let config = power_config().set_power_off_when_idle(true);
+    let sensor = SDS011::new_with_power(&mut serial, &mut switch, config);
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+
+    serial.done();
+    assert_eq!(switch.0, [true, false, true, false]);
+    assert_eq!(
+        delay.delays(),
+        [100, 10, 100, 10, 20].map(Duration::from_millis)
+    );
+}
+
+#[maybe_async::test(feature = "sync", async(not(feature = "sync"), tokio::test))]
+async fn switched_off_after_failure() {
+    let mode = Kind::ReportingMode(Reporting::new_set(ReportingMode::Query));
+    let mut script = init_script();
+    script.pop();
+    // the sensor hangs
+    script.push(Transaction::command(mode, Some(ID)));
+    // is power cycled and re-initialized, but still fails to measure
+    script.extend(reinit_script().into_iter().take(3));
+    script.push(Transaction::command(mode, Some(ID)));
+    let mut serial = Mock::new(script);
+    let mut switch = Switch::default();
+    let mut delay = MockDelay::new();
+
+    let config = power_config()
+        .set_power_off_when_idle(true)
+        .set_power_cycle_after(1);
+    let sensor = SDS011::new_with_power(&mut serial, &mut switch, config);
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+    let err = sensor.measure(&mut delay).await.err().unwrap();
+    assert!(matches!(err, SDS011Error::UnexpectedEof));
+
+    serial.done();
+    assert_eq!(
+        switch.0,
+        [true, false, true, false, true, false, true, false]
+    );
+}
+
+#[tokio::test]
+async fn power_cycle_after_timeouts() {
+    let wake = Kind::Sleep(Sleep::new_set(SleepMode::Work));
+    let sleep = Kind::Sleep(Sleep::new_set(SleepMode::Sleep));
+    let mut script = init_script();
+    script.extend([
+        // the sensor stops answering, and the caller times out
+        Transaction::command(wake, Some(ID)),
+        Transaction::command(sleep, Some(ID)),
+    ]);
+    script.extend(reinit_script());
+    script.extend(measurement(1236, 2618));
+    let mut serial = Mock::new(script);
+    let reads = AtomicUsize::new(usize::MAX);
+    let writes = AtomicUsize::new(usize::MAX);
+    let mut switch = Switch::default();
+    let mut delay = MockDelay::new();
+
+    let stall = Stall {
+        serial: &mut serial,
+        reads: &reads,
+        writes: &writes,
+    };
+    let config = power_config().set_power_cycle_after(2);
+    let sensor = crate::asynch::SDS011::new_with_power(stall, &mut switch, config);
+    let mut sensor = sensor.init(&mut delay).await.unwrap();
+
+    reads.store(0, Relaxed);
+    cancel(sensor.measure(&mut delay));
+    cancel(sensor.measure(&mut delay));
+    reads.store(usize::MAX, Relaxed);
+    let m = sensor.measure(&mut delay).await.unwrap();
+    assert_eq!((m.pm25(), m.pm10()), (1236, 2618));
+
+    serial.done();
+    assert_eq!(switch.0, [true, false, true]);
+}